@@ -1,7 +1,11 @@
 pub mod redis_engine;
+pub mod server;
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::no_effect, clippy::useless_vec)]
 mod test {
+    use crate::redis_engine::Reply;
+
     #[test]
     fn commands_execute_properly() {
         let commands = vec![
@@ -18,19 +22,19 @@ mod test {
             "set dummy val",
             "unlink dummy",
         ];
-        let responses: Vec<core::result::Result<String, String>> = vec![
-            Ok("hi".to_string()),
-            Ok("pong".to_string()),
-            Ok("asd".to_string()),
-            Ok("Ok".to_string()),
-            Ok("hi".to_string()),
-            Ok("Ok".to_string()),
-            Ok("string".to_string()),
-            Ok("0".to_string()),
-            Ok("1".to_string()),
-            Ok("Ok".to_string()),
-            Ok("Ok".to_string()),
-            Ok("1".to_string()),
+        let responses = vec![
+            Ok(Reply::bulk("hi")),
+            Ok(Reply::Status("PONG".to_string())),
+            Ok(Reply::bulk("asd")),
+            Ok(Reply::ok()),
+            Ok(Reply::bulk("hi")),
+            Ok(Reply::Status("Ok".to_string())),
+            Ok(Reply::Status("string".to_string())),
+            Ok(Reply::Integer(0)),
+            Ok(Reply::Integer(1)),
+            Ok(Reply::ok()),
+            Ok(Reply::ok()),
+            Ok(Reply::Integer(1)),
         ];
 
         let executor = crate::redis_engine::setup_executor(true);
@@ -52,12 +56,12 @@ mod test {
     // anywhere OTUSIDE OF a #[test] cfg!
     let executor = crate::redis_engine::setup_executor(true);
     if executor.setup_properly {{
-      assert_eq!(executor.exec(\"set dummy asd\".to_string()) , Ok(\"Ok\".to_string()));
-      assert_eq!(executor.exec(\"expire dummy 1\".to_string()), Ok(\"1\".to_string()));
-      assert_eq!(executor.exec(\"get dummy\".to_string())   , Ok(\"asd\".to_string()));
+      assert_eq!(executor.exec(\"set dummy asd\".to_string()) , Ok(Reply::ok()));
+      assert_eq!(executor.exec(\"expire dummy 1\".to_string()), Ok(Reply::Integer(1)));
+      assert_eq!(executor.exec(\"get dummy\".to_string())   , Ok(Reply::bulk(\"asd\")));
       std::thread::sleep(std::time::Duration::from_secs(2)); // wait 2s and check if \"dummy\" expired
-      assert_ne!(executor.exec(\"get dummy\".to_string()), Ok(\"asd\".to_string()));
-      assert_eq!(executor.exec(\"get dummy\".to_string()), Ok(\"(nil)\".to_string()));
+      assert_ne!(executor.exec(\"get dummy\".to_string()), Ok(Reply::bulk(\"asd\")));
+      assert_eq!(executor.exec(\"get dummy\".to_string()), Ok(Reply::Nil));
       println!(\"Expiring works just fine!\");
     }} else {{
       assert!(false);
@@ -66,6 +70,446 @@ mod test {
         assert!(true)
     }
 
+    #[test]
+    fn expired_keys_are_deleted_lazily_and_actively() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+        let past = now.as_millis() as u64 - 1;
+
+        assert_eq!(executor.exec("expire list1 100".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rename list1 renamed".to_string()), Ok(Reply::ok()));
+        {
+            let mut keyspace = executor.context.keyspace.lock().unwrap();
+            assert!(keyspace.expiry("renamed").is_some());
+            assert!(!keyspace.set_expiry("missing", past));
+
+            for key in ["name", "renamed", "hash1"] {
+                assert!(keyspace.set_expiry(key, past));
+            }
+        }
+
+        // every type is expired as soon as it gets accessed
+        assert_eq!(executor.exec("get name".to_string()), Ok(Reply::Nil));
+        assert_eq!(executor.exec("llen renamed".to_string()), Ok(Reply::Integer(0)));
+        assert_eq!(executor.exec("type hash1".to_string()), Ok(Reply::Status("none".to_string())));
+        assert_eq!(executor.exec("rpush renamed fresh".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lindex renamed 0".to_string()), Ok(Reply::bulk("fresh")));
+
+        for i in 0..100 {
+            assert_eq!(executor.exec(format!("set temp{i} {i}")), Ok(Reply::ok()));
+        }
+        let mut keyspace = executor.context.keyspace.lock().unwrap();
+        for i in 0..100 {
+            assert!(keyspace.set_expiry(&format!("temp{i}"), past));
+        }
+
+        // reads only hide expired keys, they stay in memory until the active expiry gets to them
+        assert_eq!(keyspace.iter().count() + 102, keyspace.len());
+        while keyspace.active_expire_cycle() > 0 {}
+        assert_eq!(keyspace.iter().count(), keyspace.len());
+        assert!(keyspace.contains_key("renamed"));
+    }
+
+    #[test]
+    fn ttl_commands_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let int = |n| Ok(Reply::Integer(n));
+
+        assert_eq!(executor.exec("ttl missing".to_string()), int(-2));
+        assert_eq!(executor.exec("pttl name".to_string()), int(-1));
+        assert_eq!(executor.exec("expire missing 10".to_string()), int(0));
+        assert_eq!(executor.exec("expire name 10 xx".to_string()), int(0));
+        assert_eq!(executor.exec("expire name 10 nx".to_string()), int(1));
+        assert_eq!(executor.exec("expire name 20 nx".to_string()), int(0));
+        assert_eq!(executor.exec("ttl name".to_string()), int(10));
+        assert_eq!(executor.exec("expire name 5 gt".to_string()), int(0));
+        assert_eq!(executor.exec("expire name 100 gt".to_string()), int(1));
+        assert_eq!(executor.exec("pexpire name 50000 lt".to_string()), int(1));
+        assert_eq!(executor.exec("ttl name".to_string()), int(50));
+        assert!(matches!(executor.exec("pttl name".to_string()), Ok(Reply::Integer(n)) if n > 49000 && n <= 50000));
+
+        // GT never applies to keys without a TTL, LT always does
+        assert_eq!(executor.exec("expire age 10 gt".to_string()), int(0));
+        assert_eq!(executor.exec("expire age 10 lt".to_string()), int(1));
+        assert_eq!(executor.exec("persist age".to_string()), int(1));
+        assert_eq!(executor.exec("persist age".to_string()), int(0));
+        assert_eq!(executor.exec("ttl age".to_string()), int(-1));
+
+        assert_eq!(executor.exec("expireat list1 4102444800".to_string()), int(1));
+        assert_eq!(executor.exec("expiretime list1".to_string()), int(4102444800));
+        assert_eq!(executor.exec("pexpiretime list1".to_string()), int(4102444800000));
+        assert_eq!(executor.exec("pexpireat hash1 4102444800123".to_string()), int(1));
+        assert_eq!(executor.exec("pexpiretime hash1".to_string()), int(4102444800123));
+        assert_eq!(executor.exec("expiretime hash2".to_string()), int(-1));
+
+        // a deadline in the past deletes the key
+        assert_eq!(executor.exec("expireat list1 1".to_string()), int(1));
+        assert_eq!(executor.exec("ttl list1".to_string()), int(-2));
+        assert_eq!(executor.exec("pexpire hash1 -1".to_string()), int(1));
+        assert_eq!(executor.exec("type hash1".to_string()), Ok(Reply::Status("none".to_string())));
+
+        assert_eq!(
+            executor.exec("expire name 10 nx gt".to_string()),
+            Err("[ERROR]: NX and XX, GT or LT options at the same time are not compatible".to_string())
+        );
+        assert_eq!(
+            executor.exec("expire name 10 gt lt".to_string()),
+            Err("[ERROR]: GT and LT options at the same time are not compatible".to_string())
+        );
+        assert_eq!(
+            executor.exec("pexpireat name 9223372036854775807".to_string()),
+            int(1)
+        );
+        assert_eq!(
+            executor.exec("expire name 9223372036854775807".to_string()),
+            Err("[ERROR]: invalid expire time in 'expire' command".to_string())
+        );
+    }
+
+    #[test]
+    fn ttls_are_kept_in_the_dump() {
+        let executor = crate::redis_engine::setup_executor(true);
+
+        assert_eq!(executor.exec("pexpireat list1 4102444800123".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("pexpire hash1 100000".to_string()), Ok(Reply::Integer(1)));
+
+        let dump = executor.context.to_string();
+        assert!(dump.contains("\"list1\": 4102444800123"));
+
+        // one key runs out of time while "the server is down"
+        let dump = dump.replace("\"list1\": 4102444800123", "\"list1\": 1");
+        let restored = crate::redis_engine::ExecutionContext::from_file_contents(dump);
+        let keyspace = restored.keyspace.lock().unwrap();
+
+        assert!(!keyspace.contains_key("list1"));
+        assert!(keyspace.contains_key("list2"));
+        assert_eq!(keyspace.expiry("list2"), None);
+        assert!(keyspace.expiry("hash1").is_some());
+        assert_eq!(keyspace.expiry("hash1"), executor.context.keyspace.lock().unwrap().expiry("hash1"));
+    }
+
+    #[test]
+    fn set_options_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let syntax_error = Err("[ERROR]: syntax error".to_string());
+
+        // the options are no longer glued to the value
+        assert_eq!(exec("set lock owner1 nx ex 10"), Ok(Reply::ok()));
+        assert_eq!(exec("get lock"), Ok(Reply::bulk("owner1")));
+        assert_eq!(exec("ttl lock"), Ok(Reply::Integer(10)));
+        assert_eq!(exec("set lock owner2 nx ex 10"), Ok(Reply::Nil));
+        assert_eq!(exec("set lock owner2 nx get"), Ok(Reply::bulk("owner1")));
+        assert_eq!(exec("set lock owner3 xx keepttl get"), Ok(Reply::bulk("owner1")));
+        assert_eq!(exec("ttl lock"), Ok(Reply::Integer(10)));
+        assert_eq!(exec("set lock owner4"), Ok(Reply::ok()));
+        assert_eq!(exec("ttl lock"), Ok(Reply::Integer(-1)));
+        assert_eq!(exec("set missing value xx"), Ok(Reply::Nil));
+        assert_eq!(exec("set lock owner5 pxat 4102444800123"), Ok(Reply::ok()));
+        assert_eq!(exec("pexpiretime lock"), Ok(Reply::Integer(4102444800123)));
+        assert_eq!(exec("set lock owner6 exat 4102444800"), Ok(Reply::ok()));
+        assert_eq!(exec("pexpiretime lock"), Ok(Reply::Integer(4102444800000)));
+
+        assert_eq!(exec("set lock value nx xx"), syntax_error);
+        assert_eq!(exec("set lock value ex 10 px 100"), syntax_error);
+        assert_eq!(exec("set lock value keepttl ex 10"), syntax_error);
+        assert_eq!(exec("set lock value ex"), syntax_error);
+        assert_eq!(exec("set lock value extra"), syntax_error);
+        assert_eq!(exec("set lock value ex 0"), Err("[ERROR]: invalid expire time in 'set' command".to_string()));
+        assert_eq!(exec("set lock value ex ten"), Err("[ERROR]: value is not an integer or out of range".to_string()));
+        assert_eq!(
+            exec("set list1 value get"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+        assert_eq!(exec("type list1"), Ok(Reply::Status("list".to_string())));
+
+        assert_eq!(exec("setnx name Ivan"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("setnx fresh Ivan"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("setex session 100 token"), Ok(Reply::ok()));
+        assert_eq!(exec("ttl session"), Ok(Reply::Integer(100)));
+        assert_eq!(exec("psetex session 5000 token"), Ok(Reply::ok()));
+        assert_eq!(exec("ttl session"), Ok(Reply::Integer(5)));
+        assert_eq!(exec("getset session other"), Ok(Reply::bulk("token")));
+        assert_eq!(exec("ttl session"), Ok(Reply::Integer(-1)));
+        assert_eq!(exec("getex session ex 50"), Ok(Reply::bulk("other")));
+        assert_eq!(exec("ttl session"), Ok(Reply::Integer(50)));
+        assert_eq!(exec("getex session persist"), Ok(Reply::bulk("other")));
+        assert_eq!(exec("ttl session"), Ok(Reply::Integer(-1)));
+        assert_eq!(exec("getex session persist ex 10"), syntax_error);
+        assert_eq!(exec("getdel session"), Ok(Reply::bulk("other")));
+        assert_eq!(exec("getdel session"), Ok(Reply::Nil));
+        assert_eq!(exec("getex session"), Ok(Reply::Nil));
+    }
+
+    #[test]
+    fn counters_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let not_an_integer = Err("[ERROR]: value is not an integer or out of range".to_string());
+
+        assert_eq!(exec("incr counter"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("incrby counter 41"), Ok(Reply::Integer(42)));
+        assert_eq!(exec("decr counter"), Ok(Reply::Integer(41)));
+        assert_eq!(exec("decrby counter -9"), Ok(Reply::Integer(50)));
+        assert_eq!(exec("get counter"), Ok(Reply::bulk("50")));
+        assert_eq!(exec("incr age"), Ok(Reply::Integer(23)));
+
+        // the TTL stays untouched
+        assert_eq!(exec("expire counter 100"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("incr counter"), Ok(Reply::Integer(51)));
+        assert_eq!(exec("ttl counter"), Ok(Reply::Integer(100)));
+
+        assert_eq!(exec("incr name"), not_an_integer);
+        assert_eq!(exec("incrby counter 1.5"), not_an_integer);
+        assert_eq!(
+            exec("incr list1"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+        assert_eq!(exec("set big 9223372036854775807"), Ok(Reply::ok()));
+        assert_eq!(exec("incr big"), Err("[ERROR]: increment or decrement would overflow".to_string()));
+        assert_eq!(exec("decrby counter -9223372036854775808"), Err("[ERROR]: decrement would overflow".to_string()));
+        assert_eq!(exec("get big"), Ok(Reply::bulk("9223372036854775807")));
+
+        assert_eq!(exec("incrbyfloat price 10.5"), Ok(Reply::bulk("10.5")));
+        assert_eq!(exec("incrbyfloat price 0.1"), Ok(Reply::bulk("10.6")));
+        assert_eq!(exec("incrbyfloat price -5.6"), Ok(Reply::bulk("5")));
+        assert_eq!(exec("incrbyfloat price 5.0e3"), Ok(Reply::bulk("5005")));
+        assert_eq!(exec("incr price"), Ok(Reply::Integer(5006)));
+        assert_eq!(exec("incrbyfloat price abc"), Err("[ERROR]: value is not a valid float".to_string()));
+        assert_eq!(exec("incrbyfloat name 1"), Err("[ERROR]: value is not a valid float".to_string()));
+        assert_eq!(exec("set k 10.1"), Ok(Reply::ok()));
+        assert_eq!(exec("incrbyfloat k 0.2"), Ok(Reply::bulk("10.3")));
+        assert_eq!(exec("incrbyfloat k -10.3"), Ok(Reply::bulk("0")));
+        assert_eq!(exec("incrbyfloat k 1e20"), Ok(Reply::bulk("100000000000000000000")));
+        assert_eq!(exec("set max 1.7976931348623157e308"), Ok(Reply::ok()));
+        assert_eq!(exec("incrbyfloat max 0"), Ok(Reply::bulk(&format!("{}", f64::MAX))));
+        assert_eq!(exec("set huge 1.7976931348623157e308"), Ok(Reply::ok()));
+        assert_eq!(
+            exec("incrbyfloat huge 1e308"),
+            Err("[ERROR]: increment would produce NaN or Infinity".to_string())
+        );
+    }
+
+    #[test]
+    fn string_manipulation_works_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+
+        assert_eq!(exec("append log first"), Ok(Reply::Integer(5)));
+        assert_eq!(exec("append log ,second"), Ok(Reply::Integer(12)));
+        assert_eq!(exec("strlen log"), Ok(Reply::Integer(12)));
+        assert_eq!(exec("strlen missing"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("getrange log 0 4"), Ok(Reply::bulk("first")));
+        assert_eq!(exec("getrange log -6 -1"), Ok(Reply::bulk("second")));
+        assert_eq!(exec("getrange log 6 100"), Ok(Reply::bulk("second")));
+        assert_eq!(exec("getrange log 5 2"), Ok(Reply::bulk("")));
+        assert_eq!(exec("getrange missing 0 -1"), Ok(Reply::bulk("")));
+
+        // integers are treated as their decimal string
+        assert_eq!(exec("append age 5"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("incr age"), Ok(Reply::Integer(226)));
+        assert_eq!(exec("strlen age"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("getrange age 1 1"), Ok(Reply::bulk("2")));
+
+        assert_eq!(exec("setrange log 0 FIRST"), Ok(Reply::Integer(12)));
+        assert_eq!(exec("get log"), Ok(Reply::bulk("FIRST,second")));
+        assert_eq!(exec("setrange padded 3 abc"), Ok(Reply::Integer(6)));
+        assert_eq!(exec("get padded"), Ok(Reply::Bulk(b"\0\0\0abc".to_vec())));
+        assert_eq!(exec("setrange empty 5 \"\""), Ok(Reply::Integer(0)));
+        assert_eq!(exec("key empty"), Ok(Reply::Status("Nok".to_string())));
+        assert_eq!(exec("setrange log -1 x"), Err("[ERROR]: offset is out of range".to_string()));
+        assert_eq!(
+            exec("append list1 x"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+
+        assert_eq!(exec("set key1 ohmytext"), Ok(Reply::ok()));
+        assert_eq!(exec("set key2 mynewtext"), Ok(Reply::ok()));
+        assert_eq!(exec("lcs key1 key2"), Ok(Reply::bulk("mytext")));
+        assert_eq!(exec("lcs key1 key2 len"), Ok(Reply::Integer(6)));
+        assert_eq!(exec("lcs key1 missing"), Ok(Reply::bulk("")));
+
+        let range = |start, end| Reply::Array(vec![Reply::Integer(start), Reply::Integer(end)]);
+        assert_eq!(
+            exec("lcs key1 key2 idx"),
+            Ok(Reply::Map(vec![
+                (
+                    Reply::bulk("matches"),
+                    Reply::Array(vec![
+                        Reply::Array(vec![range(4, 7), range(5, 8)]),
+                        Reply::Array(vec![range(2, 3), range(0, 1)]),
+                    ])
+                ),
+                (Reply::bulk("len"), Reply::Integer(6)),
+            ]))
+        );
+        assert_eq!(
+            exec("lcs key1 key2 idx minmatchlen 4 withmatchlen"),
+            Ok(Reply::Map(vec![
+                (
+                    Reply::bulk("matches"),
+                    Reply::Array(vec![Reply::Array(vec![range(4, 7), range(5, 8), Reply::Integer(4)])])
+                ),
+                (Reply::bulk("len"), Reply::Integer(6)),
+            ]))
+        );
+        assert_eq!(
+            exec("lcs key1 key2 len idx"),
+            Err("[ERROR]: If you want both the length and indexes, please just use IDX.".to_string())
+        );
+        assert_eq!(
+            exec("lcs key1 list1"),
+            Err("[ERROR]: The specified keys must contain string values".to_string())
+        );
+    }
+
+    #[test]
+    fn multi_key_commands_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+
+        assert_eq!(exec("mset a 1 b two c \"three 3\""), Ok(Reply::ok()));
+        assert_eq!(
+            exec("mget a b c missing list1 name"),
+            Ok(Reply::Array(vec![
+                Reply::bulk("1"),
+                Reply::bulk("two"),
+                Reply::bulk("three 3"),
+                Reply::Nil,
+                Reply::Nil,
+                Reply::bulk("Petar"),
+            ]))
+        );
+
+        // mset overwrites any type and drops the TTL
+        assert_eq!(exec("expire a 100"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("mset a 2 list1 now-a-string"), Ok(Reply::ok()));
+        assert_eq!(exec("ttl a"), Ok(Reply::Integer(-1)));
+        assert_eq!(exec("type list1"), Ok(Reply::Status("string".to_string())));
+
+        assert_eq!(exec("msetnx d 4 a 5"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("mget d a"), Ok(Reply::Array(vec![Reply::Nil, Reply::bulk("2")])));
+        assert_eq!(exec("msetnx d 4 e 5"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("mget d e"), Ok(Reply::Array(vec![Reply::bulk("4"), Reply::bulk("5")])));
+
+        assert_eq!(exec("mset a 1 b"), Err("[ERROR]: wrong number of arguments for mset".to_string()));
+        assert_eq!(exec("msetnx"), Err("[ERROR]: wrong number of arguments for msetnx".to_string()));
+    }
+
+    #[test]
+    fn bitmaps_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let int = |n| Ok(Reply::Integer(n));
+
+        assert_eq!(exec("setbit bits 7 1"), int(0));
+        assert_eq!(exec("setbit bits 7 1"), int(1));
+        assert_eq!(exec("getbit bits 0"), int(0));
+        assert_eq!(exec("getbit bits 7"), int(1));
+        assert_eq!(exec("getbit bits 100"), int(0));
+        assert_eq!(exec("setbit bits 17 1"), int(0));
+        assert_eq!(exec("get bits"), Ok(Reply::Bulk(vec![0x01, 0x00, 0x40])));
+        assert_eq!(exec("setbit bits 7 2"), Err("[ERROR]: bit is not an integer or out of range".to_string()));
+        assert_eq!(exec("setbit bits -1 1"), Err("[ERROR]: bit offset is not an integer or out of range".to_string()));
+
+        // bits that happen to spell out a number don't get mangled
+        assert_eq!(exec("set digits 0"), Ok(Reply::ok()));
+        assert_eq!(exec("setbit digits 15 1"), int(0));
+        assert_eq!(exec("get digits"), Ok(Reply::bulk("0\u{1}")));
+        assert_eq!(exec("set padded 007"), Ok(Reply::ok()));
+        assert_eq!(exec("get padded"), Ok(Reply::bulk("007")));
+        // and the ones that do still count as one, as does a bitmap that keeps its TTL
+        assert_eq!(exec("set number 1 ex 100"), Ok(Reply::ok()));
+        assert_eq!(exec("setbit number 6 1"), int(0));
+        assert_eq!(exec("get number"), Ok(Reply::bulk("3")));
+        assert_eq!(exec("incr number"), int(4));
+        assert_eq!(exec("setbit number 6 0"), int(0));
+        assert_eq!(exec("ttl number"), int(100));
+
+        assert_eq!(exec("set text foobar"), Ok(Reply::ok()));
+        assert_eq!(exec("bitcount text"), int(26));
+        assert_eq!(exec("bitcount text 0 0"), int(4));
+        assert_eq!(exec("bitcount text 1 1 byte"), int(6));
+        assert_eq!(exec("bitcount text 5 30 bit"), int(17));
+        assert_eq!(exec("bitcount missing"), int(0));
+        assert_eq!(exec("bitcount text 0"), Err("[ERROR]: syntax error".to_string()));
+
+        assert_eq!(exec("set bytes \"\\xff\\xf0\\x00\""), Ok(Reply::ok()));
+        assert_eq!(exec("bitpos bytes 0"), int(12));
+        assert_eq!(exec("set bytes \"\\x00\\xff\\xf0\""), Ok(Reply::ok()));
+        assert_eq!(exec("bitpos bytes 1 0"), int(8));
+        assert_eq!(exec("bitpos bytes 1 2"), int(16));
+        assert_eq!(exec("bitpos bytes 1 2 -1 byte"), int(16));
+        assert_eq!(exec("bitpos bytes 1 7 15 bit"), int(8));
+        assert_eq!(exec("bitpos bytes 1 7 -3 bit"), int(8));
+        assert_eq!(exec("set bytes \"\\xff\\xff\""), Ok(Reply::ok()));
+        assert_eq!(exec("bitpos bytes 0"), int(16));
+        assert_eq!(exec("bitpos bytes 0 0 -1"), int(-1));
+        assert_eq!(exec("bitpos missing 0"), int(0));
+        assert_eq!(exec("bitpos missing 1"), int(-1));
+
+        assert_eq!(exec("set key1 foobar"), Ok(Reply::ok()));
+        assert_eq!(exec("set key2 abcdef"), Ok(Reply::ok()));
+        assert_eq!(exec("bitop and dest key1 key2"), int(6));
+        assert_eq!(exec("get dest"), Ok(Reply::bulk("`bc`ab")));
+        assert_eq!(exec("bitop or dest key1 missing"), int(6));
+        assert_eq!(exec("get dest"), Ok(Reply::bulk("foobar")));
+        assert_eq!(exec("bitop not dest bytes"), int(2));
+        assert_eq!(exec("get dest"), Ok(Reply::Bulk(vec![0, 0])));
+        assert_eq!(exec("bitop xor dest missing"), int(0));
+        assert_eq!(exec("key dest"), Ok(Reply::Status("Nok".to_string())));
+        assert_eq!(
+            exec("bitop not dest key1 key2"),
+            Err("[ERROR]: BITOP NOT must be called with a single source key.".to_string())
+        );
+        assert_eq!(
+            exec("bitop and dest key1 list1"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+    }
+
+    #[test]
+    fn bitfields_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let ints = |values: Vec<i64>| Ok(Reply::Array(values.into_iter().map(Reply::Integer).collect()));
+
+        assert_eq!(exec("bitfield field incrby i5 100 1 get u4 0"), ints(vec![1, 0]));
+
+        // the first counter wraps around, the second one saturates
+        for (wrapped, saturated) in [(1, 1), (2, 2), (3, 3), (0, 3)] {
+            assert_eq!(
+                exec("bitfield counters incrby u2 100 1 overflow sat incrby u2 102 1"),
+                ints(vec![wrapped, saturated])
+            );
+        }
+        assert_eq!(
+            exec("bitfield counters overflow fail incrby u2 102 1"),
+            Ok(Reply::Array(vec![Reply::Nil]))
+        );
+
+        assert_eq!(exec("bitfield signed set i8 0 127"), ints(vec![0]));
+        assert_eq!(exec("bitfield signed incrby i8 0 1"), ints(vec![-128]));
+        assert_eq!(exec("bitfield signed overflow sat incrby i8 0 -1000"), ints(vec![-128]));
+        assert_eq!(exec("bitfield signed overflow sat set i8 0 1000 get i8 0"), ints(vec![-128, 127]));
+        assert_eq!(exec("bitfield signed set u8 #1 255 get u8 8 get i8 #1"), ints(vec![0, 255, -1]));
+        assert_eq!(exec("bitfield wide set i64 0 -1 get i64 0 get u63 0"), ints(vec![0, -1, i64::MAX]));
+        assert_eq!(exec("bitfield_ro signed get u8 #1"), ints(vec![255]));
+        assert_eq!(exec("bitfield_ro missing get u8 0"), ints(vec![0]));
+        assert_eq!(exec("key missing"), Ok(Reply::Status("Nok".to_string())));
+
+        assert_eq!(
+            exec("bitfield_ro signed set u8 0 1"),
+            Err("[ERROR]: BITFIELD_RO only supports the GET subcommand".to_string())
+        );
+        assert_eq!(
+            exec("bitfield signed get u64 0"),
+            Err("[ERROR]: Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.".to_string())
+        );
+        assert_eq!(exec("bitfield signed get u8"), Err("[ERROR]: syntax error".to_string()));
+        assert_eq!(exec("bitfield signed overflow what"), Err("[ERROR]: Invalid OVERFLOW type specified".to_string()));
+    }
+
     #[test]
     fn renaming_works_as_expected() {
         let commands = vec![
@@ -76,10 +520,10 @@ mod test {
         ];
 
         let responses = vec![
-            Ok("Ok".to_string()),
-            Ok("123".to_string()),
-            Ok("Ok".to_string()),
-            Ok("123".to_string()),
+            Ok(Reply::ok()),
+            Ok(Reply::bulk("123")),
+            Ok(Reply::ok()),
+            Ok(Reply::bulk("123")),
         ];
 
         let executor = crate::redis_engine::setup_executor(true);
@@ -103,10 +547,10 @@ mod test {
         ];
 
         let responses = vec![
-            Ok("2".to_string()),
-            Ok("0".to_string()),
-            Ok("3".to_string()),
-            Ok("3".to_string()),
+            Ok(Reply::Integer(2)),
+            Ok(Reply::Integer(0)),
+            Ok(Reply::Integer(3)),
+            Ok(Reply::Integer(3)),
         ];
 
         let executor = crate::redis_engine::setup_executor(true);
@@ -124,205 +568,1155 @@ mod test {
     fn lrem_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
 
-        assert_eq!(executor.exec("lrem list -2 hello".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("2".to_string()));
+        assert_eq!(executor.exec("lrem list -2 hello".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(2)));
     }
 
     #[test]
     fn lindex_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("4".to_string()));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(4)));
 
-        assert_eq!(executor.exec("lindex list 1".to_string()), Ok("\"foo\"".to_string()));
-        assert_eq!(executor.exec("lindex list 2".to_string()), Ok("\"hello2\"".to_string()));
-        assert_eq!(executor.exec("lindex list -1".to_string()), Ok("\"hello3\"".to_string()));
-        assert_eq!(executor.exec("lindex list -2".to_string()), Ok("\"hello2\"".to_string()));
+        assert_eq!(executor.exec("lindex list 1".to_string()), Ok(Reply::bulk("foo")));
+        assert_eq!(executor.exec("lindex list 2".to_string()), Ok(Reply::bulk("hello2")));
+        assert_eq!(executor.exec("lindex list -1".to_string()), Ok(Reply::bulk("hello3")));
+        assert_eq!(executor.exec("lindex list -2".to_string()), Ok(Reply::bulk("hello2")));
     }
 
     #[test]
     fn lpop_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("4".to_string()));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(4)));
 
         assert_eq!(executor.exec("lpop list asd".to_string()), Err("[ERROR]: count value is not an integer. Usage: lpop LISTNAME [COUNT]".to_string()));
-        assert_eq!(executor.exec("lpop list".to_string()), Ok("hello".to_string()));
-        assert_eq!(executor.exec("lpop list 2".to_string()), Ok("[\"foo\", \"hello2\"]".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("lpop list".to_string()), Ok(Reply::bulk("hello")));
+        assert_eq!(executor.exec("lpop list 2".to_string()), Ok(Reply::Array(vec![Reply::bulk("foo"), Reply::bulk("hello2")])));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpop missing".to_string()), Ok(Reply::Nil));
+        assert_eq!(executor.exec("lpop missing 2".to_string()), Ok(Reply::NilArray));
     }
 
     #[test]
     fn rpop_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("rpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("4".to_string()));
+        assert_eq!(executor.exec("rpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello2".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpush list hello3".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(4)));
 
         assert_eq!(executor.exec("rpop list asd".to_string()), Err("[ERROR]: count value is not an integer. Usage: rpop LISTNAME [COUNT]".to_string()));
-        assert_eq!(executor.exec("rpop list".to_string()), Ok("hello3".to_string()));
-        assert_eq!(executor.exec("rpop list 2".to_string()), Ok("[\"hello2\", \"foo\"]".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("rpop list".to_string()), Ok(Reply::bulk("hello3")));
+        assert_eq!(executor.exec("rpop list 2".to_string()), Ok(Reply::Array(vec![Reply::bulk("hello2"), Reply::bulk("foo")])));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("rpop missing".to_string()), Ok(Reply::Nil));
+        assert_eq!(executor.exec("rpop missing 2".to_string()), Ok(Reply::NilArray));
     }
 
     #[test]
     fn lpush_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("lpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("lpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("lpush list hello2".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("lpush list hello3".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("4".to_string()));
+        assert_eq!(executor.exec("lpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpush list hello2".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpush list hello3".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(4)));
     }
 
     #[test]
     fn lset_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("lpush list hello".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("lpush list foo".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("lset list 0 hello2".to_string()), Ok("Ok".to_string()));
-        assert_eq!(executor.exec("lset list -2 hello3".to_string()), Ok("Ok".to_string()));
-        assert_eq!(executor.exec("llen list".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("lindex list 0".to_string()), Ok("\"hello3\"".to_string()));
+        assert_eq!(executor.exec("lpush list hello".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpush list foo".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lset list 0 hello2".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("lset list -2 hello3".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("llen list".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("lindex list 0".to_string()), Ok(Reply::bulk("hello3")));
     }
 
     #[test]
     fn hget_and_exists_work_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash3 asd".to_string()), Ok("0".to_string()));
-        assert_eq!(executor.exec("hget hash1 name".to_string()), Ok("Petar".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hget hash1 age".to_string()), Ok("22".to_string()));
-        assert_eq!(executor.exec("hget hash2 name".to_string()), Ok("this is a new hash".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash3 asd".to_string()), Ok(Reply::Integer(0)));
+        assert_eq!(executor.exec("hget hash1 name".to_string()), Ok(Reply::bulk("Petar")));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hget hash1 age".to_string()), Ok(Reply::bulk("22")));
+        assert_eq!(executor.exec("hget hash2 name".to_string()), Ok(Reply::bulk("this is a new hash")));
     }
 
     #[test]
     fn hdel_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hdel hash1 name age".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("0".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("0".to_string()));
-        assert_eq!(executor.exec("hdel hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("0".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hdel hash1 name age".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(0)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(0)));
+        assert_eq!(executor.exec("hdel hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(0)));
     }
 
     #[test]
     fn hgetall_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
 
         // this is needed since hgetall will return the key-values array in an arbitrary element order
         // to see why read more here: https://doc.rust-lang.org/std/collections/struct.HashMap.html#method.iter
         let val = executor.exec("hgetall hash1".to_string());
 
-        if val == Ok("[\"age\", \"22\", \"name\", \"Petar\"]".to_string()) ||
-            val == Ok("[\"name\", \"Petar\", \"age\", \"22\"]".to_string()) {
+        let age = (Reply::bulk("age"), Reply::bulk("22"));
+        let name = (Reply::bulk("name"), Reply::bulk("Petar"));
+        if val == Ok(Reply::Map(vec![age.clone(), name.clone()])) ||
+            val == Ok(Reply::Map(vec![name, age])) {
                 assert!(true);
             } else {
                 assert!(false);
             }
 
-        assert_eq!(executor.exec("hgetall hash2".to_string()), Ok("[\"name\", \"this is a new hash\"]".to_string()));
-        assert_eq!(executor.exec("hgetall doesnt_exist".to_string()), Ok("[]".to_string()));
+        assert_eq!(executor.exec("hgetall hash2".to_string()), Ok(Reply::Map(vec![(Reply::bulk("name"), Reply::bulk("this is a new hash"))])));
+        assert_eq!(executor.exec("hgetall doesnt_exist".to_string()), Ok(Reply::Map(vec![])));
     }
 
     #[test]
     fn hkeys_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
 
         // this is needed since hkeys will return the key-values array in an arbitrary element order
         // to see why read more here: https://doc.rust-lang.org/std/collections/struct.HashMap.html#method.iter
         let val = executor.exec("hkeys hash1".to_string());
 
-        if val == Ok("[\"age\", \"name\"]".to_string()) ||
-            val == Ok("[\"name\", \"age\"]".to_string()) {
+        if val == Ok(Reply::Array(vec![Reply::bulk("age"), Reply::bulk("name")])) ||
+            val == Ok(Reply::Array(vec![Reply::bulk("name"), Reply::bulk("age")])) {
                 assert!(true);
             } else {
                 assert!(false);
             }
 
-        assert_eq!(executor.exec("hkeys hash2".to_string()), Ok("[\"name\"]".to_string()));
-        assert_eq!(executor.exec("hkeys doesnt_exist".to_string()), Ok("[]".to_string()));
+        assert_eq!(executor.exec("hkeys hash2".to_string()), Ok(Reply::Array(vec![Reply::bulk("name")])));
+        assert_eq!(executor.exec("hkeys doesnt_exist".to_string()), Ok(Reply::Array(vec![])));
     }
 
     #[test]
     fn hvals_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
 
         // this is needed since hkeys will return the key-values array in an arbitrary element order
         // to see why read more here: https://doc.rust-lang.org/std/collections/struct.HashMap.html#method.iter
         let val = executor.exec("hvals hash1".to_string());
 
-        if val == Ok("[\"22\", \"Petar\"]".to_string()) ||
-            val == Ok("[\"Petar\", \"22\"]".to_string()) {
+        if val == Ok(Reply::Array(vec![Reply::bulk("22"), Reply::bulk("Petar")])) ||
+            val == Ok(Reply::Array(vec![Reply::bulk("Petar"), Reply::bulk("22")])) {
                 assert!(true);
             } else {
                 assert!(false);
             }
 
-        assert_eq!(executor.exec("hvals hash2".to_string()), Ok("[\"this is a new hash\"]".to_string()));
-        assert_eq!(executor.exec("hvals doesnt_exist".to_string()), Ok("[]".to_string()));
+        assert_eq!(executor.exec("hvals hash2".to_string()), Ok(Reply::Array(vec![Reply::bulk("this is a new hash")])));
+        assert_eq!(executor.exec("hvals doesnt_exist".to_string()), Ok(Reply::Array(vec![])));
     }
 
     #[test]
     fn hlen_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hlen hash1".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("hlen hash2".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hlen doesnt_exist".to_string()), Ok("0".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hlen hash1".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("hlen hash2".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hlen doesnt_exist".to_string()), Ok(Reply::Integer(0)));
     }
 
     #[test]
     fn hset_works_as_expected() {
         let executor = crate::redis_engine::setup_executor(true);
 
-        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hmset hash1 name \"John\" age 25".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("hset hash2 newfield 69".to_string()), Ok("1".to_string()));
-        assert_eq!(executor.exec("hlen hash2".to_string()), Ok("2".to_string()));
-        assert_eq!(executor.exec("hget hash2 newfield".to_string()), Ok("69".to_string()));
-        assert_eq!(executor.exec("hget hash1 name".to_string()), Ok("\"John\"".to_string()));
+        assert_eq!(executor.exec("hexists hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hexists hash1 age".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hmset hash1 name \"John\" age 25".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("hset hash2 newfield 69".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hlen hash2".to_string()), Ok(Reply::Integer(2)));
+        assert_eq!(executor.exec("hget hash2 newfield".to_string()), Ok(Reply::bulk("69")));
+        assert_eq!(executor.exec("hget hash1 name".to_string()), Ok(Reply::bulk("John")));
+    }
+
+    #[test]
+    fn resp_commands_are_parsed_properly() {
+        use crate::redis_engine::resp::parse_command;
+
+        let request = b"*3\r\n$3\r\nset\r\n$4\r\nkekw\r\n$11\r\nhello world\r\n";
+        assert_eq!(
+            parse_command(request),
+            Ok(Some((vec![b"set".to_vec(), b"kekw".to_vec(), b"hello world".to_vec()], request.len())))
+        );
+        assert_eq!(parse_command(b"get kekw\r\n"), Ok(Some((vec![b"get".to_vec(), b"kekw".to_vec()], 10))));
+        assert_eq!(parse_command(b"*2\r\n$3\r\nget\r\n$4\r\nke"), Ok(None));
+        assert!(parse_command(b"*1\r\n:12\r\n").is_err());
+    }
+
+    #[test]
+    fn resp_replies_are_encoded_properly() {
+        use crate::redis_engine::resp::{Protocol, RespValue};
+
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |command: &str| Reply::from_result(executor.exec(command.to_string())).to_resp().encode(Protocol::Resp2);
+
+        assert_eq!(exec("ping"), b"+PONG\r\n".to_vec());
+        assert_eq!(exec("set kekw hi"), b"+OK\r\n".to_vec());
+        assert_eq!(exec("get kekw"), b"$2\r\nhi\r\n".to_vec());
+        assert_eq!(exec("get doesnt_exist"), b"$-1\r\n".to_vec());
+        assert_eq!(exec("echo"), b"-ERR Too few arguments for echo\r\n".to_vec());
+        assert_eq!(exec("get"), b"-ERR get requires only one argument!\r\n".to_vec());
+        assert_eq!(
+            RespValue::Array(vec![RespValue::Integer(1), RespValue::Nil]).encode(Protocol::Resp2),
+            b"*2\r\n:1\r\n$-1\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn resp3_replies_are_downgraded_for_resp2() {
+        use crate::redis_engine::resp::{parse, Protocol, RespValue};
+
+        let executor = crate::redis_engine::setup_executor(true);
+        let reply = Reply::from_result(executor.exec("hgetall hash2".to_string())).to_resp();
+
+        assert_eq!(reply.encode(Protocol::Resp2), b"*2\r\n$4\r\nname\r\n$18\r\nthis is a new hash\r\n".to_vec());
+        assert_eq!(reply.encode(Protocol::Resp3), b"%1\r\n$4\r\nname\r\n$18\r\nthis is a new hash\r\n".to_vec());
+        assert_eq!(RespValue::Nil.encode(Protocol::Resp3), b"_\r\n".to_vec());
+        assert_eq!(RespValue::Boolean(true).encode(Protocol::Resp2), b":1\r\n".to_vec());
+        assert_eq!(RespValue::Double(1.5).encode(Protocol::Resp2), b"$3\r\n1.5\r\n".to_vec());
+
+        let values = vec![
+            RespValue::Double(f64::INFINITY),
+            RespValue::Boolean(false),
+            RespValue::BigNumber("3492890328409238509324850943850943825024385".to_string()),
+            RespValue::Verbatim { format: "txt".to_string(), text: b"Some string".to_vec() },
+            RespValue::Set(vec![RespValue::Integer(1), RespValue::bulk("a")]),
+            RespValue::Push(vec![RespValue::bulk("message"), RespValue::Null]),
+            RespValue::Attribute {
+                attributes: vec![(RespValue::SimpleString("ttl".to_string()), RespValue::Integer(100))],
+                value: Box::new(RespValue::Integer(1)),
+            },
+        ];
+        for value in values {
+            let encoded = value.encode(Protocol::Resp3);
+            assert_eq!(parse(&encoded), Ok(Some((value, encoded.len()))));
+        }
+    }
+
+    #[test]
+    fn replies_are_rendered_as_text_properly() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |command: &str| Reply::from_result(executor.exec(command.to_string())).to_string();
+
+        assert_eq!(exec("set kekw hi"), "OK");
+        assert_eq!(exec("get kekw"), "\"hi\"");
+        assert_eq!(exec("get doesnt_exist"), "(nil)");
+        assert_eq!(exec("llen list1"), "(integer) 2");
+        assert_eq!(exec("lpop list1 5"), "1) \"value1\"\n2) \"value2\"");
+        assert_eq!(exec("hkeys doesnt_exist"), "(empty array)");
+        assert_eq!(exec("hgetall hash2"), "1# \"name\" => \"this is a new hash\"");
+        assert_eq!(exec("get"), "(error) [ERROR]: get requires only one argument!");
+        assert_eq!(
+            Reply::Array(vec![Reply::Integer(1), Reply::Array(vec![Reply::bulk("a"), Reply::Nil])]).to_string(),
+            "1) (integer) 1\n2) 1) \"a\"\n   2) (nil)"
+        );
+    }
+
+    #[test]
+    fn executor_clones_share_the_keyspace() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let clone = executor.clone();
+
+        assert_eq!(executor.exec("set shared hello".to_string()), Ok(Reply::ok()));
+        assert_eq!(clone.exec("get shared".to_string()), Ok(Reply::bulk("hello")));
+
+        let handles = (0..4)
+            .map(|_| {
+                let clone = executor.clone();
+                std::thread::spawn(move || clone.exec("rpush shared_list x".to_string()))
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Ok(Reply::Integer(1)));
+        }
+
+        assert_eq!(executor.exec("llen shared_list".to_string()), Ok(Reply::Integer(4)));
+    }
+
+    #[test]
+    fn pipelined_commands_are_parsed_one_by_one() {
+        use crate::redis_engine::resp::parse_command;
+
+        let mut buf = b"*3\r\n$5\r\nrpush\r\n$4\r\nlist\r\n$1\r\na\r\nllen list\r\n\r\n*2\r\n$4\r\nllen\r\n$4\r\nli".to_vec();
+        let mut commands = vec![];
+
+        while let Ok(Some((args, used))) = parse_command(&buf) {
+            commands.push(args);
+            buf.drain(..used);
+        }
+
+        assert_eq!(
+            commands,
+            vec![
+                vec![b"rpush".to_vec(), b"list".to_vec(), b"a".to_vec()],
+                vec![b"llen".to_vec(), b"list".to_vec()],
+                vec![],
+            ]
+        );
+        // the last command has only been partially received
+        assert_eq!(buf, b"*2\r\n$4\r\nllen\r\n$4\r\nli".to_vec());
+
+        buf.extend_from_slice(b"st\r\n");
+        assert_eq!(
+            parse_command(&buf),
+            Ok(Some((vec![b"llen".to_vec(), b"list".to_vec()], buf.len())))
+        );
+    }
+
+    #[test]
+    fn inline_commands_are_tokenized_like_redis_cli() {
+        let executor = crate::redis_engine::setup_executor(true);
+
+        assert_eq!(executor.exec("set k \"hello world\"".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("get k".to_string()), Ok(Reply::bulk("hello world")));
+        assert_eq!(executor.exec("set   k    'it\\'s'  ".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("get k".to_string()), Ok(Reply::bulk("it's")));
+        assert_eq!(executor.exec("set k \"\\x41\\tB\\n\\\"\"".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("get k".to_string()), Ok(Reply::bulk("A\tB\n\"")));
+        assert_eq!(executor.exec("hset h field \"\"".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("hget h field".to_string()), Ok(Reply::bulk("")));
+        assert_eq!(
+            executor.exec("set k \"unbalanced".to_string()),
+            Err("Protocol error: unbalanced quotes in request".to_string())
+        );
+        assert_eq!(
+            executor.exec("set k \"closed\"too-early".to_string()),
+            Err("Protocol error: unbalanced quotes in request".to_string())
+        );
+    }
+
+    #[test]
+    fn binary_values_are_stored_as_is() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let blob = vec![0x00, 0xff, 0xfe, b'\r', b'\n', 0x80];
+
+        assert_eq!(executor.exec_args(vec![b"set".to_vec(), b"blob".to_vec(), blob.clone()]), Ok(Reply::ok()));
+        assert_eq!(executor.exec("get blob".to_string()), Ok(Reply::Bulk(blob.clone())));
+        assert_eq!(executor.exec_args(vec![b"rpush".to_vec(), b"blobs".to_vec(), blob.clone()]), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lindex blobs 0".to_string()), Ok(Reply::Bulk(blob.clone())));
+        assert_eq!(executor.exec("set number \"\\x31\\x32\"".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("get number".to_string()), Ok(Reply::bulk("12")));
+        assert_eq!(
+            Reply::Bulk(blob.clone()).to_string(),
+            "\"\\x00\\xff\\xfe\\r\\n\\x80\""
+        );
+
+        // the dump file can't hold raw bytes, so they get base64 encoded
+        let dump = executor.context.to_string();
+        assert!(dump.contains("\"base64\": \"AP/+DQqA\""));
+
+        let restored = crate::redis_engine::ExecutionContext::from_file_contents(dump);
+        let keyspace = restored.keyspace.lock().unwrap();
+        let value = keyspace.get_string("blob").unwrap().unwrap().to_bytes();
+        assert_eq!(value, blob);
+    }
+
+    #[test]
+    fn keys_of_every_type_share_one_keyspace() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let wrongtype = Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+
+        assert_eq!(executor.exec("type name".to_string()), Ok(Reply::Status("string".to_string())));
+        assert_eq!(executor.exec("type list1".to_string()), Ok(Reply::Status("list".to_string())));
+        assert_eq!(executor.exec("type hash1".to_string()), Ok(Reply::Status("hash".to_string())));
+        assert_eq!(executor.exec("type missing".to_string()), Ok(Reply::Status("none".to_string())));
+
+        assert_eq!(executor.exec("lpush hash1 value".to_string()), wrongtype);
+        assert_eq!(executor.exec("llen hash1".to_string()), wrongtype);
+        assert_eq!(executor.exec("hget list1 field".to_string()), wrongtype);
+        assert_eq!(executor.exec("get list1".to_string()), wrongtype);
+        assert_eq!(executor.exec("hlen hash1".to_string()), Ok(Reply::Integer(2)));
+
+        assert_eq!(executor.exec("rename list1 moved".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("type moved".to_string()), Ok(Reply::Status("list".to_string())));
+        assert_eq!(executor.exec("del moved hash1 name".to_string()), Ok(Reply::Integer(3)));
+        assert_eq!(executor.exec("type hash1".to_string()), Ok(Reply::Status("none".to_string())));
+
+        // a set replaces the value no matter what type it had before
+        assert_eq!(executor.exec("set list2 now-a-string".to_string()), Ok(Reply::ok()));
+        assert_eq!(executor.exec("type list2".to_string()), Ok(Reply::Status("string".to_string())));
+
+        // lists and hashes go away together with their last element
+        assert_eq!(executor.exec("rpush short one".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("lpop short".to_string()), Ok(Reply::bulk("one")));
+        assert_eq!(executor.exec("type short".to_string()), Ok(Reply::Status("none".to_string())));
+        assert_eq!(executor.exec("hdel hash2 name".to_string()), Ok(Reply::Integer(1)));
+        assert_eq!(executor.exec("key hash2".to_string()), Ok(Reply::Status("Nok".to_string())));
+    }
+
+    #[test]
+    fn sets_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        // sets have no order, so the members are sorted before comparing
+        let members = |cmd: &str| match exec(cmd) {
+            Ok(Reply::Set(members)) | Ok(Reply::Array(members)) => {
+                let mut members = members
+                    .into_iter()
+                    .map(|m| match m {
+                        Reply::Bulk(bytes) => String::from_utf8(bytes).unwrap(),
+                        other => panic!("{other:?}"),
+                    })
+                    .collect::<Vec<_>>();
+                members.sort();
+                members
+            }
+            other => panic!("{other:?}"),
+        };
+
+        assert_eq!(exec("sadd set1 a b c d"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("sadd set1 a e"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("sadd set2 c d e f"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("type set1"), Ok(Reply::Status("set".to_string())));
+        assert_eq!(exec("scard set1"), Ok(Reply::Integer(5)));
+        assert_eq!(exec("scard missing"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("sismember set1 a"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("sismember set1 z"), Ok(Reply::Integer(0)));
+        assert_eq!(
+            exec("smismember set1 a z e"),
+            Ok(Reply::Array(vec![Reply::Integer(1), Reply::Integer(0), Reply::Integer(1)]))
+        );
+        assert_eq!(exec("srem set1 a z"), Ok(Reply::Integer(1)));
+        assert_eq!(members("smembers set1"), vec!["b", "c", "d", "e"]);
+
+        assert_eq!(members("sinter set1 set2"), vec!["c", "d", "e"]);
+        assert_eq!(members("sunion set1 set2"), vec!["b", "c", "d", "e", "f"]);
+        assert_eq!(members("sdiff set1 set2"), vec!["b"]);
+        assert_eq!(members("sinter set1 missing"), Vec::<String>::new());
+        assert_eq!(exec("sintercard 2 set1 set2"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("sintercard 2 set1 set2 limit 2"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("sinterstore name set1 set2"), Ok(Reply::Integer(3)));
+        assert_eq!(members("smembers name"), vec!["c", "d", "e"]);
+        assert_eq!(exec("sdiffstore name set1 set1"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("key name"), Ok(Reply::Status("Nok".to_string())));
+        assert_eq!(exec("sunionstore both set1 set2"), Ok(Reply::Integer(5)));
+
+        assert_eq!(exec("smove set1 set3 b"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("smove set1 set3 b"), Ok(Reply::Integer(0)));
+        assert_eq!(members("smembers set3"), vec!["b"]);
+
+        assert_eq!(exec("srandmember set3"), Ok(Reply::Bulk(b"b".to_vec())));
+        assert_eq!(members("srandmember both 10"), vec!["b", "c", "d", "e", "f"]);
+        assert_eq!(members("srandmember set3 -3"), vec!["b", "b", "b"]);
+        assert_eq!(exec("spop set3"), Ok(Reply::Bulk(b"b".to_vec())));
+        assert_eq!(exec("key set3"), Ok(Reply::Status("Nok".to_string())));
+        assert_eq!(members("spop both 10"), vec!["b", "c", "d", "e", "f"]);
+        assert_eq!(exec("spop both"), Ok(Reply::Nil));
+
+        // members picked in one go are all different, and popped ones are gone from the set
+        let hundred = (0..100).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+        assert_eq!(exec(&format!("sadd many {hundred}")), Ok(Reply::Integer(100)));
+        let mut picked = members("srandmember many 60");
+        picked.dedup();
+        assert_eq!(picked.len(), 60);
+        let mut popped = members("spop many 60");
+        popped.dedup();
+        assert_eq!(popped.len(), 60);
+        assert_eq!(exec("scard many"), Ok(Reply::Integer(40)));
+        assert!(popped.iter().all(|member| exec(&format!("sismember many {member}")) == Ok(Reply::Integer(0))));
+        assert_eq!(members("srandmember many -50").len(), 50);
+
+        let wrongtype = Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+        assert_eq!(exec("sadd list1 a"), wrongtype);
+        assert_eq!(exec("sunion set1 hash1"), wrongtype);
+        assert_eq!(exec("smove set1 age c"), wrongtype);
+        assert_eq!(exec("spop set1 -1"), Err("[ERROR]: value is out of range, must be positive".to_string()));
+        assert_eq!(exec("sintercard 3 set1 set2"), Err("[ERROR]: Number of keys can't be greater than number of args".to_string()));
+    }
+
+    #[test]
+    fn sorted_sets_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let bulks = |members: &[&str]| Ok(Reply::Array(members.iter().map(|m| Reply::bulk(m)).collect()));
+
+        assert_eq!(exec("zadd board 10 alice 20 bob 30 carol"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("zadd board 40 dave 25 bob"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("type board"), Ok(Reply::Status("zset".to_string())));
+        assert_eq!(exec("zcard board"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("zscore board bob"), Ok(Reply::Double(25.0)));
+        assert_eq!(exec("zscore board nobody"), Ok(Reply::Nil));
+        assert_eq!(exec("zrange board 0 -1"), bulks(&["alice", "bob", "carol", "dave"]));
+        assert_eq!(exec("zrank board carol"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("zrevrank board carol withscore"), Ok(Reply::Array(vec![Reply::Integer(1), Reply::Double(30.0)])));
+
+        // NX/XX/GT/LT decide which members get touched, CH counts the updated ones too
+        assert_eq!(exec("zadd board nx 0 alice 50 erin"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("zadd board xx ch 11 alice 0 frank"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("zadd board gt ch 5 alice 60 erin"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("zadd board lt incr 1 alice"), Ok(Reply::Nil));
+        assert_eq!(exec("zadd board incr -1 alice"), Ok(Reply::Double(10.0)));
+        assert_eq!(exec("zincrby board 2.5 alice"), Ok(Reply::Double(12.5)));
+        assert_eq!(exec("zadd empty xx 1 a"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("key empty"), Ok(Reply::Status("Nok".to_string())));
+
+        assert_eq!(exec("zcount board (12.5 +inf"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("zrange board 0 1 withscores"), Ok(Reply::Array(vec![
+            Reply::bulk("alice"), Reply::Double(12.5), Reply::bulk("bob"), Reply::Double(25.0),
+        ])));
+        assert_eq!(exec("zrange board (25 40 byscore"), bulks(&["carol", "dave"]));
+        assert_eq!(exec("zrange board +inf 25 byscore rev limit 1 2"), bulks(&["dave", "carol"]));
+        assert_eq!(exec("zrange board 0 0 rev"), bulks(&["erin"]));
+
+        assert_eq!(exec("zadd letters 0 a 0 b 0 c 0 d"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("zrange letters [b (d bylex"), bulks(&["b", "c"]));
+        assert_eq!(exec("zrange letters + - bylex rev limit 0 2"), bulks(&["d", "c"]));
+        assert_eq!(exec("zrangestore top board 0 1 rev"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("zrange top 0 -1"), bulks(&["dave", "erin"]));
+
+        assert_eq!(exec("zpopmin board"), Ok(Reply::Array(vec![Reply::bulk("alice"), Reply::Double(12.5)])));
+        assert_eq!(exec("zpopmax board 2"), Ok(Reply::Array(vec![
+            Reply::bulk("erin"), Reply::Double(60.0), Reply::bulk("dave"), Reply::Double(40.0),
+        ])));
+        assert_eq!(exec("zrem board bob carol nobody"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("key board"), Ok(Reply::Status("Nok".to_string())));
+
+        assert_eq!(exec("zadd z1 1 a 2 b 3 c"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("zadd z2 10 b 20 c 30 d"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("zunionstore out 2 z1 z2 weights 2 1"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("zrange out 0 -1 withscores"), Ok(Reply::Array(vec![
+            Reply::bulk("a"), Reply::Double(2.0), Reply::bulk("b"), Reply::Double(14.0),
+            Reply::bulk("c"), Reply::Double(26.0), Reply::bulk("d"), Reply::Double(30.0),
+        ])));
+        assert_eq!(exec("zinterstore out 2 z1 z2 aggregate max"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("zrange out 0 -1 withscores"), Ok(Reply::Array(vec![
+            Reply::bulk("b"), Reply::Double(10.0), Reply::bulk("c"), Reply::Double(20.0),
+        ])));
+
+        let wrongtype = Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string());
+        assert_eq!(exec("zadd list1 1 a"), wrongtype);
+        assert_eq!(exec("zunionstore out 2 z1 hash1"), wrongtype);
+        assert_eq!(exec("zadd z1 nx xx 1 a"), Err("[ERROR]: XX and NX options at the same time are not compatible".to_string()));
+        assert_eq!(exec("zadd z1 nan a"), Err("[ERROR]: value is not a valid float".to_string()));
+        assert_eq!(exec("zadd z1 incr 1 a 2 b"), Err("[ERROR]: INCR option supports a single increment-element pair".to_string()));
+        assert_eq!(exec("zadd inf 1 a"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("zadd inf incr +inf a"), Ok(Reply::Double(f64::INFINITY)));
+        assert_eq!(exec("zincrby inf -inf a"), Err("[ERROR]: resulting score is not a number (NaN)".to_string()));
+        assert_eq!(
+            exec("zrange z1 0 1 limit 0 1"),
+            Err("[ERROR]: syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX".to_string())
+        );
+
+        // scores survive the dump, infinite ones included
+        let restored = crate::redis_engine::ExecutionContext::from_file_contents(executor.context.to_string());
+        let keyspace = restored.keyspace.lock().unwrap();
+        assert_eq!(keyspace.get_zset("inf").unwrap().unwrap().score(b"a"), Some(f64::INFINITY));
+        assert_eq!(keyspace.get_zset("out").unwrap().unwrap().score(b"c"), Some(20.0));
+    }
+
+    #[test]
+    fn sorted_set_ranks_follow_the_order_of_scores_and_members() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+
+        // members added in a shuffled order, with plenty of ties on the score
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut random = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize % n
+        };
+        let mut members: Vec<(i64, String)> = (0..300).map(|i| (i % 7, format!("m{i}"))).collect();
+        for i in (1..members.len()).rev() {
+            members.swap(i, random(i + 1));
+        }
+        for (score, member) in &members {
+            assert_eq!(exec(&format!("zadd board {score} {member}")), Ok(Reply::Integer(1)));
+        }
+
+        // moving and removing members has to keep the ranks of the rest right
+        for _ in 0..50 {
+            let i = random(members.len());
+            members[i].0 = random(10) as i64 - 3;
+            let (score, member) = &members[i];
+            assert_eq!(exec(&format!("zadd board {score} {member}")), Ok(Reply::Integer(0)));
+        }
+        for _ in 0..50 {
+            let (_, member) = members.swap_remove(random(members.len()));
+            assert_eq!(exec(&format!("zrem board {member}")), Ok(Reply::Integer(1)));
+        }
+
+        members.sort();
+        let len = members.len() as i64;
+        assert_eq!(exec("zcard board"), Ok(Reply::Integer(len)));
+        for (rank, (_, member)) in members.iter().enumerate() {
+            assert_eq!(exec(&format!("zrank board {member}")), Ok(Reply::Integer(rank as i64)));
+            assert_eq!(exec(&format!("zrevrank board {member}")), Ok(Reply::Integer(len - 1 - rank as i64)));
+        }
+
+        let ordered = |members: Vec<&(i64, String)>| {
+            Ok(Reply::Array(members.into_iter().map(|(_, member)| Reply::bulk(member)).collect()))
+        };
+        assert_eq!(exec("zrange board 0 -1"), ordered(members.iter().collect()));
+        assert_eq!(exec("zrange board 0 -1 rev"), ordered(members.iter().rev().collect()));
+        assert_eq!(exec("zrange board 2 4 byscore"), ordered(members.iter().filter(|(score, _)| (2..=4).contains(score)).collect()));
+        assert_eq!(exec("zrank board nobody"), Ok(Reply::Nil));
+
+        // popping from both ends keeps the ranks in between right
+        let popped = |(score, member): (i64, String)| Ok(Reply::Array(vec![Reply::bulk(&member), Reply::Double(score as f64)]));
+        while members.len() > 2 {
+            assert_eq!(exec("zpopmin board"), popped(members.remove(0)));
+            assert_eq!(exec("zpopmax board"), popped(members.pop().unwrap()));
+            let (_, member) = &members[members.len() / 2];
+            assert_eq!(exec(&format!("zrank board {member}")), Ok(Reply::Integer(members.len() as i64 / 2)));
+        }
+    }
+
+    #[test]
+    fn streams_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let entry = |id: &str, fields: &[&str]| {
+            Reply::Array(vec![Reply::bulk(id), Reply::Array(fields.iter().map(|f| Reply::bulk(f)).collect())])
+        };
+
+        assert_eq!(exec("xadd jobs 1-1 task build"), Ok(Reply::bulk("1-1")));
+        assert_eq!(exec("xadd jobs 1-* task test"), Ok(Reply::bulk("1-2")));
+        assert_eq!(exec("xadd jobs 5 task deploy env prod"), Ok(Reply::bulk("5-0")));
+        assert_eq!(
+            exec("xadd jobs 5-0 task again"),
+            Err("[ERROR]: The ID specified in XADD is equal or smaller than the target stream top item".to_string())
+        );
+        assert_eq!(exec("xadd fresh 0-0 a b"), Err("[ERROR]: The ID specified in XADD must be greater than 0-0".to_string()));
+        assert_eq!(exec("xadd missing nomkstream * a b"), Ok(Reply::Nil));
+        assert_eq!(exec("key missing"), Ok(Reply::Status("Nok".to_string())));
+        assert_eq!(exec("type jobs"), Ok(Reply::Status("stream".to_string())));
+        assert_eq!(exec("xlen jobs"), Ok(Reply::Integer(3)));
+
+        assert_eq!(exec("xrange jobs - + count 2"), Ok(Reply::Array(vec![
+            entry("1-1", &["task", "build"]),
+            entry("1-2", &["task", "test"]),
+        ])));
+        assert_eq!(exec("xrange jobs (1-1 1"), Ok(Reply::Array(vec![entry("1-2", &["task", "test"])])));
+        assert_eq!(exec("xrevrange jobs + - count 1"), Ok(Reply::Array(vec![entry("5-0", &["task", "deploy", "env", "prod"])])));
+        assert_eq!(exec("xread count 1 streams jobs nothing 1-1 0"), Ok(Reply::Array(vec![
+            Reply::Array(vec![Reply::bulk("jobs"), Reply::Array(vec![entry("1-2", &["task", "test"])])]),
+        ])));
+        assert_eq!(exec("xread streams jobs $"), Ok(Reply::Nil));
+
+        // consumer groups hand every entry out once and keep track of what hasn't been acknowledged
+        assert_eq!(exec("xgroup create jobs workers 0"), Ok(Reply::ok()));
+        assert_eq!(exec("xgroup create jobs workers $"), Err("BUSYGROUP Consumer Group name already exists".to_string()));
+        assert_eq!(exec("xreadgroup group workers alice count 2 streams jobs >"), Ok(Reply::Array(vec![
+            Reply::Array(vec![Reply::bulk("jobs"), Reply::Array(vec![
+                entry("1-1", &["task", "build"]),
+                entry("1-2", &["task", "test"]),
+            ])]),
+        ])));
+        assert_eq!(exec("xreadgroup group workers bob streams jobs >"), Ok(Reply::Array(vec![
+            Reply::Array(vec![Reply::bulk("jobs"), Reply::Array(vec![entry("5-0", &["task", "deploy", "env", "prod"])])]),
+        ])));
+        assert_eq!(exec("xreadgroup group workers bob streams jobs >"), Ok(Reply::Nil));
+        assert_eq!(exec("xack jobs workers 1-1 9-9"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("xpending jobs workers"), Ok(Reply::Array(vec![
+            Reply::Integer(2),
+            Reply::bulk("1-2"),
+            Reply::bulk("5-0"),
+            Reply::Array(vec![
+                Reply::Array(vec![Reply::bulk("alice"), Reply::bulk("1")]),
+                Reply::Array(vec![Reply::bulk("bob"), Reply::bulk("1")]),
+            ]),
+        ])));
+        assert_eq!(exec("xreadgroup group workers alice streams jobs 0"), Ok(Reply::Array(vec![
+            Reply::Array(vec![Reply::bulk("jobs"), Reply::Array(vec![entry("1-2", &["task", "test"])])]),
+        ])));
+
+        // alice's entry gets handed over to bob, then the deleted one gets dropped from the PEL
+        assert_eq!(exec("xclaim jobs workers bob 0 1-2 justid"), Ok(Reply::Array(vec![Reply::bulk("1-2")])));
+        assert_eq!(exec("xclaim jobs workers bob 3600000 5-0"), Ok(Reply::Array(vec![])));
+        assert_eq!(exec("xdel jobs 5-0 6-0"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("xautoclaim jobs workers alice 0 0"), Ok(Reply::Array(vec![
+            Reply::bulk("0-0"),
+            Reply::Array(vec![entry("1-2", &["task", "test"])]),
+            Reply::Array(vec![Reply::bulk("5-0")]),
+        ])));
+        assert_eq!(
+            exec("xautoclaim jobs workers alice 0 0 count 9223372036854775807"),
+            Err("[ERROR]: COUNT must be > 0".to_string())
+        );
+        match exec("xpending jobs workers - + 10") {
+            Ok(Reply::Array(entries)) => match &entries[..] {
+                [Reply::Array(entry)] => {
+                    assert_eq!(entry[0], Reply::bulk("1-2"));
+                    assert_eq!(entry[1], Reply::bulk("alice"));
+                    assert_eq!(entry[3], Reply::Integer(2));
+                }
+                other => panic!("{other:?}"),
+            },
+            other => panic!("{other:?}"),
+        }
+
+        // FORCE claims entries nobody got delivered yet, regardless of the min-idle-time
+        assert_eq!(exec("xadd forced 1-1 a b"), Ok(Reply::bulk("1-1")));
+        assert_eq!(exec("xgroup create forced workers 0"), Ok(Reply::ok()));
+        assert_eq!(exec("xclaim forced workers dave 3600000 1-1 force justid"), Ok(Reply::Array(vec![Reply::bulk("1-1")])));
+        assert_eq!(exec("xclaim forced workers erin 3600000 1-1 force justid"), Ok(Reply::Array(vec![])));
+        match exec("xpending forced workers - + 10") {
+            Ok(Reply::Array(entries)) => assert_eq!(entries.len(), 1),
+            other => panic!("{other:?}"),
+        }
+
+        assert!(matches!(exec("xadd jobs maxlen 2 * task lint"), Ok(Reply::Bulk(_))));
+        assert_eq!(exec("xlen jobs"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("xtrim jobs minid 2"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("xlen jobs"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("xgroup destroy jobs workers"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("xgroup create jobs again $"), Ok(Reply::ok()));
+        assert_eq!(exec("xreadgroup group workers alice streams jobs >"), Err(
+            "NOGROUP No such key 'jobs' or consumer group 'workers' in XREADGROUP with GROUP option".to_string()
+        ));
+        assert_eq!(exec("xadd list1 * a b"), Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()));
+
+        // entries, IDs and the pending entries lists survive the dump
+        assert_eq!(exec("xreadgroup group again carol streams jobs 0"), Ok(Reply::Array(vec![
+            Reply::Array(vec![Reply::bulk("jobs"), Reply::Array(vec![])]),
+        ])));
+        assert_eq!(exec("xgroup setid jobs again 0"), Ok(Reply::ok()));
+        assert!(matches!(exec("xreadgroup group again carol streams jobs >"), Ok(Reply::Array(_))));
+        let restored = crate::redis_engine::ExecutionContext::from_file_contents(executor.context.to_string());
+        let keyspace = restored.keyspace.lock().unwrap();
+        let stream = keyspace.get_stream("jobs").unwrap().unwrap();
+        let original = executor.context.keyspace.lock().unwrap();
+        let original = original.get_stream("jobs").unwrap().unwrap();
+        assert_eq!(stream.entries, original.entries);
+        assert_eq!(stream.last_id, original.last_id);
+        assert_eq!(stream.max_deleted_id, crate::redis_engine::StreamId { ms: 5, seq: 0 });
+        assert_eq!(stream.entries_added, 4);
+        assert_eq!(stream.groups["again"].pending.len(), 1);
+        assert_eq!(stream.groups["again"].pending.values().next().unwrap().consumer, "carol");
+    }
+
+    #[test]
+    fn hyperloglogs_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let count = |cmd: &str| match exec(cmd) {
+            Ok(Reply::Integer(n)) => n as f64,
+            other => panic!("{other:?}"),
+        };
+
+        assert_eq!(exec("pfadd empty"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("pfcount empty"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("pfadd visitors a b c"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("pfadd visitors a b"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("pfcount visitors"), Ok(Reply::Integer(3)));
+        assert_eq!(exec("pfcount missing"), Ok(Reply::Integer(0)));
+
+        // the values are plain strings in the same format Redis uses, starting out sparse
+        match exec("get visitors") {
+            Ok(Reply::Bulk(bytes)) => {
+                assert_eq!(&bytes[..5], b"HYLL\x01");
+                assert!(bytes.len() < 100);
+            }
+            other => panic!("{other:?}"),
+        }
+
+        // enough elements turn the value dense, the estimate stays within a few standard errors
+        for page in ["home", "docs"] {
+            for chunk in 0..50 {
+                let visitors = (0..200).map(|i| format!("{page}{}", chunk * 200 + i)).collect::<Vec<_>>();
+                exec(&format!("pfadd {page} {}", visitors.join(" "))).unwrap();
+            }
+        }
+        match exec("get home") {
+            Ok(Reply::Bulk(bytes)) => {
+                assert_eq!(&bytes[..5], b"HYLL\x00");
+                assert_eq!(bytes.len(), 12304);
+            }
+            other => panic!("{other:?}"),
+        }
+        assert!((count("pfcount home") - 10000.0).abs() < 300.0);
+        assert!((count("pfcount home docs") - 20000.0).abs() < 600.0);
+        assert_eq!(exec("pfmerge both home docs visitors"), Ok(Reply::ok()));
+        assert_eq!(count("pfcount both"), count("pfcount home docs visitors"));
+
+        assert_eq!(
+            exec("pfadd name x"),
+            Err("WRONGTYPE Key is not a valid HyperLogLog string value.".to_string())
+        );
+        assert_eq!(
+            exec("pfcount list1"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+    }
+
+    #[test]
+    fn geo_commands_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let bulks = |cmd: &str| match exec(cmd) {
+            Ok(Reply::Array(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    Reply::Bulk(bytes) => String::from_utf8(bytes).unwrap(),
+                    other => panic!("{other:?}"),
+                })
+                .collect::<Vec<_>>(),
+            other => panic!("{other:?}"),
+        };
+
+        assert_eq!(
+            exec("geoadd Sicily 13.361389 38.115556 Palermo 15.087269 37.502669 Catania"),
+            Ok(Reply::Integer(2))
+        );
+        assert_eq!(exec("geoadd Sicily NX 13.361389 38.115556 Palermo"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("type Sicily"), Ok(Reply::Status("zset".to_string())));
+        assert_eq!(exec("zscore Sicily Palermo"), Ok(Reply::Double(3479099956230698.0)));
+
+        assert_eq!(exec("geodist Sicily Palermo Catania"), Ok(Reply::Double(166274.1516)));
+        assert_eq!(exec("geodist Sicily Palermo Catania km"), Ok(Reply::Double(166.2742)));
+        assert_eq!(exec("geodist Sicily Palermo Rome"), Ok(Reply::Nil));
+        assert_eq!(bulks("geohash Sicily Palermo Catania"), vec!["sqc8b49rny0", "sqdtr74hyu0"]);
+
+        match exec("geopos Sicily Palermo Rome") {
+            Ok(Reply::Array(positions)) => match &positions[..] {
+                [Reply::Array(coordinates), Reply::Nil] => match coordinates[..] {
+                    [Reply::Double(longitude), Reply::Double(latitude)] => {
+                        assert!((longitude - 13.361389).abs() < 1e-5);
+                        assert!((latitude - 38.115556).abs() < 1e-5);
+                    }
+                    _ => panic!("{coordinates:?}"),
+                },
+                _ => panic!("{positions:?}"),
+            },
+            other => panic!("{other:?}"),
+        }
+
+        assert_eq!(bulks("geosearch Sicily FROMLONLAT 15 37 BYRADIUS 200 km ASC"), vec!["Catania", "Palermo"]);
+        assert_eq!(bulks("geosearch Sicily FROMLONLAT 15 37 BYRADIUS 100 km"), vec!["Catania"]);
+        assert_eq!(bulks("geosearch Sicily FROMMEMBER Palermo BYBOX 400 400 km DESC COUNT 1"), vec!["Catania"]);
+        assert_eq!(
+            exec("geosearch Sicily FROMLONLAT 15 37 BYBOX 400 400 km ASC WITHDIST"),
+            Ok(Reply::Array(vec![
+                Reply::Array(vec![Reply::bulk("Catania"), Reply::Double(56.4413)]),
+                Reply::Array(vec![Reply::bulk("Palermo"), Reply::Double(190.4424)]),
+            ]))
+        );
+
+        assert_eq!(exec("geosearchstore near Sicily FROMLONLAT 15 37 BYRADIUS 100 km STOREDIST"), Ok(Reply::Integer(1)));
+        assert_eq!(exec("zscore near Catania"), Ok(Reply::Double(56.4412578701582)));
+        assert_eq!(exec("geosearchstore near Sicily FROMLONLAT 0 0 BYRADIUS 1 m"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("zcard near"), Ok(Reply::Integer(0)));
+
+        assert_eq!(
+            exec("geoadd Sicily 13.361389 91 Nowhere"),
+            Err("[ERROR]: invalid longitude,latitude pair 13.361389,91.000000".to_string())
+        );
+        assert_eq!(
+            exec("geosearch Sicily BYRADIUS 10 km"),
+            Err("[ERROR]: exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH".to_string())
+        );
+        assert_eq!(
+            exec("geosearch Sicily FROMMEMBER Rome BYRADIUS 10 km"),
+            Err("[ERROR]: could not decode requested zset member".to_string())
+        );
+        assert!(exec("geosearch Sicily FROMMEMBER Palermo BYRADIUS 10 parsecs").is_err());
+        assert!(exec("geosearchstore near Sicily FROMMEMBER Palermo BYRADIUS 10 km WITHDIST").is_err());
+    }
+
+    #[test]
+    fn list_range_and_move_commands_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let bulks = |items: &[&str]| Ok(Reply::Array(items.iter().map(|item| Reply::bulk(item)).collect()));
+
+        exec("rpush list a b c d e").unwrap();
+        assert_eq!(exec("lrange list 0 -1"), bulks(&["a", "b", "c", "d", "e"]));
+        assert_eq!(exec("lrange list -2 100"), bulks(&["d", "e"]));
+        assert_eq!(exec("lrange list 3 1"), bulks(&[]));
+        assert_eq!(exec("lrange missing 0 -1"), bulks(&[]));
+
+        assert_eq!(exec("ltrim list 1 -2"), Ok(Reply::ok()));
+        assert_eq!(exec("lrange list 0 -1"), bulks(&["b", "c", "d"]));
+
+        assert_eq!(exec("linsert list before c x"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("linsert list after d c"), Ok(Reply::Integer(5)));
+        assert_eq!(exec("linsert list after nope y"), Ok(Reply::Integer(-1)));
+        assert_eq!(exec("linsert missing after b y"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("lrange list 0 -1"), bulks(&["b", "x", "c", "d", "c"]));
+
+        assert_eq!(exec("lpos list c"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("lpos list c rank -1"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("lpos list c rank 2"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("lpos list c count 0"), Ok(Reply::Array(vec![Reply::Integer(2), Reply::Integer(4)])));
+        assert_eq!(exec("lpos list c count 0 maxlen 3"), Ok(Reply::Array(vec![Reply::Integer(2)])));
+        assert_eq!(exec("lpos list nope"), Ok(Reply::Nil));
+        assert!(exec("lpos list c rank 0").is_err());
+        assert_eq!(exec("lpos list c foo bar"), Err("[ERROR]: syntax error".to_string()));
+        assert_eq!(exec("lpos list c foo"), Err("[ERROR]: syntax error".to_string()));
+        assert_eq!(exec("lpos list c rank"), Err("[ERROR]: syntax error".to_string()));
+        assert_eq!(exec("lpos list c rank first"), Err("[ERROR]: value is not an integer or out of range".to_string()));
+        // MAXLEN counts the compared elements from wherever the search starts
+        assert_eq!(exec("lpos list c rank 2 maxlen 3"), Ok(Reply::Nil));
+        assert_eq!(exec("lpos list c rank 2 maxlen 5"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("lpos list c rank -1 maxlen 2"), Ok(Reply::Integer(4)));
+        assert_eq!(exec("lpos list c rank -2 maxlen 2"), Ok(Reply::Nil));
+        assert_eq!(exec("lpos list c rank -2 maxlen 3"), Ok(Reply::Integer(2)));
+        assert_eq!(exec("lpos list c rank -1 count 0 maxlen 3"), Ok(Reply::Array(vec![Reply::Integer(4), Reply::Integer(2)])));
+
+        assert_eq!(exec("lmove list other left right"), Ok(Reply::bulk("b")));
+        assert_eq!(exec("rpoplpush list other"), Ok(Reply::bulk("c")));
+        assert_eq!(exec("lmove list list right left"), Ok(Reply::bulk("d")));
+        assert_eq!(exec("lrange list 0 -1"), bulks(&["d", "x", "c"]));
+        assert_eq!(exec("lrange other 0 -1"), bulks(&["c", "b"]));
+        assert_eq!(exec("lmove missing other left left"), Ok(Reply::Nil));
+        assert_eq!(
+            exec("lmove list name left left"),
+            Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        );
+        assert_eq!(exec("llen list"), Ok(Reply::Integer(3)));
+
+        assert_eq!(
+            exec("lmpop 2 missing other right count 5"),
+            Ok(Reply::Array(vec![Reply::bulk("other"), bulks(&["b", "c"]).unwrap()]))
+        );
+        assert_eq!(exec("llen other"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("lmpop 1 missing left"), Ok(Reply::NilArray));
+        assert!(exec("lmpop 0 list left").is_err());
+
+        assert_eq!(exec("lpushx missing a"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("llen missing"), Ok(Reply::Integer(0)));
+        assert_eq!(exec("lpushx list a b"), Ok(Reply::Integer(5)));
+        assert_eq!(exec("rpushx list z"), Ok(Reply::Integer(6)));
+        assert_eq!(exec("lrange list 0 -1"), bulks(&["b", "a", "d", "x", "c", "z"]));
+    }
+
+    #[test]
+    fn blocking_list_commands_work_as_expected() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+        let args = |cmd: &str| cmd.split(' ').map(|arg| arg.as_bytes().to_vec()).collect::<Vec<_>>();
+
+        // the engine only tries once, a nil tells the server to block the client
+        assert_eq!(exec("blpop queue other 0"), Ok(Reply::NilArray));
+        assert_eq!(exec("blmove queue dst left right 0.5"), Ok(Reply::Nil));
+        assert_eq!(exec("blmpop 0 2 queue other left"), Ok(Reply::NilArray));
+        assert_eq!(exec("blpop queue -1"), Err("[ERROR]: timeout is negative".to_string()));
+        assert_eq!(exec("brpop queue soon"), Err("[ERROR]: timeout is not a float or out of range".to_string()));
+
+        let blocking = executor.blocking(&args("BLPOP queue other 0")).unwrap();
+        assert_eq!(blocking.keys, vec!["queue", "other"]);
+        assert_eq!(blocking.timeout, None);
+        let blocking = executor.blocking(&args("blmpop 1.5 2 queue other left count 2")).unwrap();
+        assert_eq!(blocking.keys, vec!["queue", "other"]);
+        assert_eq!(blocking.timeout, Some(std::time::Duration::from_millis(1500)));
+        assert_eq!(executor.blocking(&args("blmove queue dst left right 0")).unwrap().keys, vec!["queue"]);
+        assert!(executor.blocking(&args("lpop queue")).is_none());
+        assert!(executor.blocking(&args("blpop queue -1")).is_none());
+        assert!(executor.blocking(&args("blmpop 0 18446744073709551615 queue left")).is_none());
+        assert!(exec("blmpop 0 18446744073709551615 queue left").is_err());
+
+        // newly created lists are the ones blocked clients get retried for, as long as somebody waits on them
+        let watched = vec!["queue".to_string(), "other".to_string()];
+        executor.block_on(&watched);
+        exec("rpush other a b c").unwrap();
+        exec("rpush other d").unwrap();
+        exec("rename list1 queue").unwrap();
+        exec("rpush unwatched a").unwrap();
+        let mut ready = executor.take_ready_keys().into_iter().collect::<Vec<_>>();
+        ready.sort();
+        assert_eq!(ready, vec!["other", "queue"]);
+        assert!(executor.take_ready_keys().is_empty());
+
+        executor.unblock_from(&watched);
+        exec("rpush fresh a").unwrap();
+        exec("del queue").unwrap();
+        exec("rpush queue a").unwrap();
+        assert!(executor.take_ready_keys().is_empty());
+
+        exec("del queue").unwrap();
+        assert_eq!(exec("blpop queue other 0"), Ok(Reply::Array(vec![Reply::bulk("other"), Reply::bulk("a")])));
+        assert_eq!(exec("brpop queue other 1"), Ok(Reply::Array(vec![Reply::bulk("other"), Reply::bulk("d")])));
+        assert_eq!(exec("blmove other dst left right 0"), Ok(Reply::bulk("b")));
+        assert_eq!(
+            exec("blmpop 0 2 queue other right count 3"),
+            Ok(Reply::Array(vec![Reply::bulk("other"), Reply::Array(vec![Reply::bulk("c")])]))
+        );
+        assert_eq!(exec("llen other"), Ok(Reply::Integer(0)));
+    }
+
+    #[test]
+    fn lists_work_as_queues() {
+        let executor = crate::redis_engine::setup_executor(true);
+        let exec = |cmd: &str| executor.exec(cmd.to_string());
+
+        // both ends are O(1), so a big queue drains quickly
+        for chunk in 0..50 {
+            let jobs = (0..1000).map(|i| format!("job{}", chunk * 1000 + i)).collect::<Vec<_>>();
+            exec(&format!("rpush queue {}", jobs.join(" "))).unwrap();
+        }
+        exec("lpush queue first").unwrap();
+        assert_eq!(exec("llen queue"), Ok(Reply::Integer(50001)));
+        assert_eq!(exec("lpop queue"), Ok(Reply::bulk("first")));
+        for chunk in 0..49 {
+            match exec("lpop queue 1000") {
+                Ok(Reply::Array(jobs)) => assert_eq!(jobs[0], Reply::bulk(&format!("job{}", chunk * 1000))),
+                other => panic!("{other:?}"),
+            }
+        }
+        assert_eq!(exec("rpop queue"), Ok(Reply::bulk("job49999")));
+        assert_eq!(exec("llen queue"), Ok(Reply::Integer(999)));
+
+        // the dump still holds lists as plain JSON arrays, in order
+        exec("rpush small a b c").unwrap();
+        exec("lpush small z").unwrap();
+        let dump = executor.context.to_string();
+        let json: serde_json::Value = serde_json::from_str(&dump).unwrap();
+        assert_eq!(json["lists"]["small"], serde_json::json!(["z", "a", "b", "c"]));
+
+        let restored = crate::redis_engine::ExecutionContext::from_file_contents(dump);
+        let keyspace = restored.keyspace.lock().unwrap();
+        let small = keyspace.get_list("small").unwrap().unwrap();
+        assert_eq!(small.iter().map(|v| v.to_bytes()).collect::<Vec<_>>(), vec![b"z", b"a", b"b", b"c"]);
+        assert_eq!(keyspace.get_list("queue").unwrap().unwrap().len(), 999);
+    }
+
+    #[test]
+    fn server_serves_blocked_clients() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        // the connections save after every batch of commands, which must not touch the debug dump
+        let dump_file = std::env::temp_dir().join(format!("blocking_test_{}.my_rdb", std::process::id()));
+        let dump_file: &'static str = Box::leak(dump_file.to_string_lossy().into_owned().into_boxed_str());
+        let executor = crate::redis_engine::setup_executor(true).with_dump_file(dump_file);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server = {
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::spawn(move || {
+                let mut server = crate::server::Server::new(listener, executor).unwrap();
+                server.run(shutdown).unwrap();
+            })
+        };
+
+        let connect = || {
+            let stream = TcpStream::connect(address).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            stream
+        };
+        let send = |mut stream: &TcpStream, commands: &[&str]| {
+            let mut request = vec![];
+            for command in commands {
+                let args: Vec<&str> = command.split(' ').collect();
+                request.extend(format!("*{}\r\n", args.len()).into_bytes());
+                for arg in args {
+                    request.extend(format!("${}\r\n{arg}\r\n", arg.len()).into_bytes());
+                }
+            }
+            stream.write_all(&request).unwrap();
+        };
+        let expect = |mut stream: &TcpStream, reply: &str| {
+            let mut received = vec![0; reply.len()];
+            stream.read_exact(&mut received).unwrap();
+            assert_eq!(String::from_utf8_lossy(&received), reply);
+        };
+        // waits a bit to make sure the server has seen the command and parked the client
+        let expect_blocked = |mut stream: &TcpStream| {
+            stream.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+            let mut byte = [0];
+            let result = stream.read(&mut byte);
+            assert!(result.is_err(), "expected the client to be blocked, got {result:?} {byte:?}");
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        };
+
+        let (a, b, c) = (connect(), connect(), connect());
+
+        // a client gets parked and woken up by a push from another connection
+        send(&a, &["blpop queue 0"]);
+        expect_blocked(&a);
+        send(&c, &["lpush queue job"]);
+        expect(&c, ":1\r\n");
+        expect(&a, "*2\r\n$5\r\nqueue\r\n$3\r\njob\r\n");
+
+        // the client that has been waiting the longest gets served first
+        send(&a, &["brpop fifo 0"]);
+        expect_blocked(&a);
+        send(&b, &["brpop fifo 0"]);
+        expect_blocked(&b);
+        send(&c, &["rpush fifo first"]);
+        expect(&c, ":1\r\n");
+        expect(&a, "*2\r\n$4\r\nfifo\r\n$5\r\nfirst\r\n");
+        expect_blocked(&b);
+        send(&c, &["rpush fifo second"]);
+        expect(&c, ":1\r\n");
+        expect(&b, "*2\r\n$4\r\nfifo\r\n$6\r\nsecond\r\n");
+
+        // whatever a blocked client pipelines waits until it is served, and it gets served
+        // right after the push, before the rest of the pipeline of the pushing client
+        send(&a, &["blpop pipe 0", "echo after"]);
+        expect_blocked(&a);
+        send(&c, &["rpush pipe x", "lpop pipe"]);
+        expect(&c, ":1\r\n$-1\r\n");
+        expect(&a, "*2\r\n$4\r\npipe\r\n$1\r\nx\r\n$5\r\nafter\r\n");
+
+        // on timeout BLMOVE replies with a nil bulk string, the rest with a nil array
+        send(&a, &["blpop missing 0.05"]);
+        expect(&a, "*-1\r\n");
+        send(&a, &["brpop missing 0.05"]);
+        expect(&a, "*-1\r\n");
+        send(&a, &["blmpop 0.05 1 missing left"]);
+        expect(&a, "*-1\r\n");
+        send(&a, &["blmove missing dst left right 0.05"]);
+        expect(&a, "$-1\r\n");
+        send(&a, &["llen missing"]);
+        expect(&a, ":0\r\n");
+
+        shutdown.store(true, Ordering::Relaxed);
+        server.join().unwrap();
+        let _ = std::fs::remove_file(dump_file);
     }
 }
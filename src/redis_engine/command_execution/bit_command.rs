@@ -0,0 +1,495 @@
+#![allow(clippy::comparison_chain)]
+
+pub mod bit {
+    use crate::redis_engine::{CommandArg, Keyspace, RedisObject, RedisValue};
+    use super::super::Reply;
+    use std::borrow::Cow;
+    type Result = super::super::Result;
+
+    // Bitmaps are plain strings, bit 0 is the most significant bit of the first byte.
+    // Same as the limit of Redis, which is 512MB worth of bits.
+    const MAX_BIT_OFFSET: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+    const NOT_AN_INTEGER: &str = "[ERROR]: value is not an integer or out of range";
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
+
+    // Borrows the bytes of the string, so reading a large bitmap doesn't copy it
+    fn string_bytes<'a>(keyspace: &'a Keyspace, key: &str) -> core::result::Result<Option<Cow<'a, [u8]>>, String> {
+        Ok(keyspace.get_string(key)?.map(|value| value.as_bytes()))
+    }
+
+    fn parse_int(arg: &[u8]) -> core::result::Result<i64, String> {
+        arg.parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())
+    }
+
+    fn parse_bit_offset(arg: &[u8]) -> core::result::Result<u64, String> {
+        match arg.parse::<u64>() {
+            Ok(offset) if offset <= MAX_BIT_OFFSET => Ok(offset),
+            _ => Err("[ERROR]: bit offset is not an integer or out of range".to_string()),
+        }
+    }
+
+    fn get_bit(bytes: &[u8], offset: u64) -> u8 {
+        match bytes.get((offset / 8) as usize) {
+            Some(byte) => (byte >> (7 - offset % 8)) & 1,
+            None => 0,
+        }
+    }
+
+    fn set_bit(bytes: &mut Vec<u8>, offset: u64, bit: u8) {
+        let idx = (offset / 8) as usize;
+        if bytes.len() <= idx {
+            bytes.resize(idx + 1, 0);
+        }
+
+        let mask = 1 << (7 - offset % 8);
+        if bit == 1 {
+            bytes[idx] |= mask;
+        } else {
+            bytes[idx] &= !mask;
+        }
+    }
+
+    // Turns a start/end pair, where negative values count from the end, into an inclusive
+    // range within `len`. Gives None if the range is empty.
+    fn clamp_range(mut start: i64, mut end: i64, len: i64) -> Option<(u64, u64)> {
+        if start < 0 {
+            start += len;
+        }
+        if end < 0 {
+            end += len;
+        }
+        start = start.max(0);
+        end = end.max(0).min(len - 1);
+
+        if len == 0 || start > end {
+            None
+        } else {
+            Some((start as u64, end as u64))
+        }
+    }
+
+    // Parses the optional BYTE|BIT unit of BITCOUNT and BITPOS. Returns true for BIT.
+    fn parse_unit(arg: Option<&&[u8]>) -> core::result::Result<bool, String> {
+        match arg.map(|x| x.as_text().to_lowercase()) {
+            None => Ok(false),
+            Some(unit) if unit == "byte" => Ok(false),
+            Some(unit) if unit == "bit" => Ok(true),
+            Some(_) => Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    pub fn setbit(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/setbit/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: setbit requires exactly three arguments!".to_string());
+        }
+
+        let offset = parse_bit_offset(args[1])?;
+        let bit = match args[2] {
+            b"0" => 0,
+            b"1" => 1,
+            _ => return Err("[ERROR]: bit is not an integer or out of range".to_string()),
+        };
+
+        let key: &str = &args[0].as_text();
+        // changed in place, a large bitmap must not be copied for every bit
+        let bytes = keyspace.get_or_create_string_bytes_mut(key)?;
+        let old = get_bit(bytes, offset);
+
+        set_bit(bytes, offset, bit);
+
+        Ok(Reply::Integer(old as i64))
+    }
+
+    pub fn getbit(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/getbit/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: getbit requires exactly two arguments!".to_string());
+        }
+
+        let offset = parse_bit_offset(args[1])?;
+        let bytes = string_bytes(keyspace, &args[0].as_text())?.unwrap_or_default();
+
+        Ok(Reply::Integer(get_bit(&bytes, offset) as i64))
+    }
+
+    pub fn bitcount(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/bitcount/
+
+        if args.is_empty() || args.len() == 2 || args.len() > 4 {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let bytes = string_bytes(keyspace, &args[0].as_text())?.unwrap_or_default();
+
+        if args.len() == 1 {
+            let count: u32 = bytes.iter().map(|b| b.count_ones()).sum();
+            return Ok(Reply::Integer(count as i64));
+        }
+
+        let (start, end) = (parse_int(args[1])?, parse_int(args[2])?);
+        let count = if parse_unit(args.get(3))? {
+            match clamp_range(start, end, bytes.len() as i64 * 8) {
+                Some((start, end)) => (start..=end).filter(|&i| get_bit(&bytes, i) == 1).count(),
+                None => 0,
+            }
+        } else {
+            match clamp_range(start, end, bytes.len() as i64) {
+                Some((start, end)) => bytes[start as usize..=end as usize]
+                    .iter()
+                    .map(|b| b.count_ones() as usize)
+                    .sum(),
+                None => 0,
+            }
+        };
+
+        Ok(Reply::Integer(count as i64))
+    }
+
+    pub fn bitpos(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/bitpos/
+
+        if args.len() < 2 || args.len() > 5 {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let bit = match args[1] {
+            b"0" => 0,
+            b"1" => 1,
+            _ => return Err("[ERROR]: The bit argument must be 1 or 0.".to_string()),
+        };
+
+        let bytes = match string_bytes(keyspace, &args[0].as_text())? {
+            Some(bytes) => bytes,
+            // a missing key is an empty string, which is all zeroes
+            None => return Ok(Reply::Integer(if bit == 1 { -1 } else { 0 })),
+        };
+
+        let start = match args.get(2) {
+            Some(start) => parse_int(start)?,
+            None => 0,
+        };
+        let end_given = args.len() > 3;
+        let end = match args.get(3) {
+            Some(end) => parse_int(end)?,
+            None => -1,
+        };
+
+        let range = if parse_unit(args.get(4))? {
+            clamp_range(start, end, bytes.len() as i64 * 8)
+        } else {
+            clamp_range(start, end, bytes.len() as i64).map(|(start, end)| (start * 8, end * 8 + 7))
+        };
+
+        let (start, end) = match range {
+            Some(range) => range,
+            None => return Ok(Reply::Integer(-1)),
+        };
+
+        if let Some(pos) = (start..=end).find(|&i| get_bit(&bytes, i) == bit) {
+            return Ok(Reply::Integer(pos as i64));
+        }
+
+        // without an explicit end, the string counts as padded with zeroes on the right
+        if bit == 0 && !end_given {
+            return Ok(Reply::Integer(end as i64 + 1));
+        }
+
+        Ok(Reply::Integer(-1))
+    }
+
+    pub fn bitop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/bitop/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for bitop!".to_string());
+        }
+
+        let operation = args[0].as_text().to_lowercase();
+        if !["and", "or", "xor", "not"].contains(&operation.as_str()) {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+        if operation == "not" && args.len() != 3 {
+            return Err("[ERROR]: BITOP NOT must be called with a single source key.".to_string());
+        }
+
+        let mut sources = vec![];
+        for key in &args[2..] {
+            sources.push(string_bytes(keyspace, &key.as_text())?.unwrap_or_default());
+        }
+
+        // shorter strings are treated as if they were padded with zeroes
+        let len = sources.iter().map(|x| x.len()).max().unwrap_or(0);
+        let mut result = vec![0_u8; len];
+
+        for (i, byte) in result.iter_mut().enumerate() {
+            let mut bytes = sources.iter().map(|x| x.get(i).copied().unwrap_or(0));
+            let first = bytes.next().unwrap_or(0);
+
+            *byte = match operation.as_str() {
+                "and" => bytes.fold(first, |acc, b| acc & b),
+                "or" => bytes.fold(first, |acc, b| acc | b),
+                "xor" => bytes.fold(first, |acc, b| acc ^ b),
+                _ => !first,
+            };
+        }
+
+        let destination: &str = &args[1].as_text();
+        if result.is_empty() {
+            // the destination goes away, same as any other empty value
+            keyspace.remove(destination);
+        } else {
+            keyspace.insert(destination.to_string(), RedisObject::String(RedisValue::from_bytes(&result)));
+        }
+
+        Ok(Reply::Integer(len as i64))
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Overflow {
+        Wrap,
+        Sat,
+        Fail,
+    }
+
+    // An integer type like i8 or u16
+    #[derive(Clone, Copy)]
+    struct Encoding {
+        signed: bool,
+        bits: u32,
+    }
+
+    impl Encoding {
+        fn parse(arg: &[u8]) -> core::result::Result<Self, String> {
+            let text = arg.as_text().to_lowercase();
+            let invalid = || {
+                "[ERROR]: Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                    .to_string()
+            };
+
+            let (signed, bits) = match text.split_at_checked(1) {
+                Some(("i", bits)) => (true, bits),
+                Some(("u", bits)) => (false, bits),
+                _ => return Err(invalid()),
+            };
+
+            match bits.parse::<u32>() {
+                Ok(bits) if bits >= 1 && (bits <= 63 || (signed && bits == 64)) => Ok(Self { signed, bits }),
+                _ => Err(invalid()),
+            }
+        }
+
+        fn min(&self) -> i128 {
+            if self.signed {
+                -(1_i128 << (self.bits - 1))
+            } else {
+                0
+            }
+        }
+
+        fn max(&self) -> i128 {
+            if self.signed {
+                (1_i128 << (self.bits - 1)) - 1
+            } else {
+                (1_i128 << self.bits) - 1
+            }
+        }
+
+        // Reads the raw bits at `offset` as a number of this type
+        fn read(&self, bytes: &[u8], offset: u64) -> i128 {
+            let mut raw: u64 = 0;
+            for i in 0..self.bits as u64 {
+                raw = (raw << 1) | get_bit(bytes, offset + i) as u64;
+            }
+
+            if self.signed && (raw >> (self.bits - 1)) & 1 == 1 {
+                // sign extend
+                raw as i128 - (1_i128 << self.bits)
+            } else {
+                raw as i128
+            }
+        }
+
+        fn write(&self, bytes: &mut Vec<u8>, offset: u64, value: i128) {
+            // two's complement, cut down to the size of the type
+            let raw = value as u64;
+            for i in 0..self.bits as u64 {
+                let bit = (raw >> (self.bits as u64 - 1 - i)) & 1;
+                set_bit(bytes, offset + i, bit as u8);
+            }
+        }
+
+        // Applies the overflow mode to a value that may be out of range.
+        // Gives None if the operation has to fail.
+        fn fit(&self, value: i128, overflow: Overflow) -> Option<i128> {
+            if value >= self.min() && value <= self.max() {
+                return Some(value);
+            }
+
+            match overflow {
+                Overflow::Fail => None,
+                Overflow::Sat => Some(value.clamp(self.min(), self.max())),
+                Overflow::Wrap => {
+                    let modulus = 1_i128 << self.bits;
+                    let wrapped = value.rem_euclid(modulus);
+
+                    if self.signed && wrapped > self.max() {
+                        Some(wrapped - modulus)
+                    } else {
+                        Some(wrapped)
+                    }
+                }
+            }
+        }
+    }
+
+    enum Operation {
+        Get,
+        Set(i64),
+        IncrBy(i64),
+    }
+
+    struct FieldOperation {
+        operation: Operation,
+        encoding: Encoding,
+        offset: u64,
+        overflow: Overflow,
+    }
+
+    // Offsets are either in bits, or prefixed with # to be multiplied by the size of the type
+    fn parse_field_offset(arg: &[u8], encoding: Encoding) -> core::result::Result<u64, String> {
+        let offset = match arg.strip_prefix(b"#") {
+            Some(index) => parse_bit_offset(index)?.checked_mul(encoding.bits as u64),
+            None => Some(parse_bit_offset(arg)?),
+        };
+
+        match offset {
+            Some(offset) if offset + encoding.bits as u64 - 1 <= MAX_BIT_OFFSET => Ok(offset),
+            _ => Err("[ERROR]: bit offset is not an integer or out of range".to_string()),
+        }
+    }
+
+    fn parse_field_operations(args: &[&[u8]], read_only: bool) -> core::result::Result<Vec<FieldOperation>, String> {
+        let mut operations = vec![];
+        let mut overflow = Overflow::Wrap;
+        let mut idx = 0;
+
+        while idx < args.len() {
+            let subcommand = args[idx].as_text().to_lowercase();
+            let arity = match subcommand.as_str() {
+                "overflow" => 2,
+                "get" => 3,
+                "set" | "incrby" => 4,
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            };
+            if idx + arity > args.len() {
+                return Err(SYNTAX_ERROR.to_string());
+            }
+
+            if subcommand == "overflow" {
+                overflow = match args[idx + 1].as_text().to_lowercase().as_str() {
+                    "wrap" => Overflow::Wrap,
+                    "sat" => Overflow::Sat,
+                    "fail" => Overflow::Fail,
+                    _ => return Err("[ERROR]: Invalid OVERFLOW type specified".to_string()),
+                };
+                idx += arity;
+                continue;
+            }
+
+            if read_only && subcommand != "get" {
+                return Err("[ERROR]: BITFIELD_RO only supports the GET subcommand".to_string());
+            }
+
+            let encoding = Encoding::parse(args[idx + 1])?;
+            let offset = parse_field_offset(args[idx + 2], encoding)?;
+            let operation = match subcommand.as_str() {
+                "get" => Operation::Get,
+                "set" => Operation::Set(parse_int(args[idx + 3])?),
+                _ => Operation::IncrBy(parse_int(args[idx + 3])?),
+            };
+
+            operations.push(FieldOperation {
+                operation,
+                encoding,
+                offset,
+                overflow,
+            });
+            idx += arity;
+        }
+
+        Ok(operations)
+    }
+
+    fn bitfield_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, read_only: bool) -> Result {
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for bitfield!".to_string());
+        }
+
+        let operations = parse_field_operations(&args[1..], read_only)?;
+        let key: &str = &args[0].as_text();
+        let writes = operations.iter().any(|x| !matches!(x.operation, Operation::Get));
+        if !writes {
+            let data = string_bytes(keyspace, key)?.unwrap_or_default();
+            let replies = operations
+                .iter()
+                .map(|field| Reply::Integer(field.encoding.read(&data, field.offset) as i64))
+                .collect();
+
+            return Ok(Reply::Array(replies));
+        }
+
+        // the writes go straight into the stored string
+        let data = keyspace.get_or_create_string_bytes_mut(key)?;
+        let mut replies = vec![];
+
+        for field in &operations {
+            let encoding = field.encoding;
+            let old = encoding.read(data, field.offset);
+
+            // the string grows to fit every field that gets written to, even if the write fails
+            if !matches!(field.operation, Operation::Get) {
+                let needed = ((field.offset + encoding.bits as u64).div_ceil(8)) as usize;
+                if data.len() < needed {
+                    data.resize(needed, 0);
+                }
+            }
+
+            let reply = match field.operation {
+                Operation::Get => Reply::Integer(old as i64),
+                Operation::Set(value) => match encoding.fit(value as i128, field.overflow) {
+                    Some(value) => {
+                        encoding.write(data, field.offset, value);
+                        Reply::Integer(old as i64)
+                    }
+                    None => Reply::Nil,
+                },
+                Operation::IncrBy(increment) => match encoding.fit(old + increment as i128, field.overflow) {
+                    Some(value) => {
+                        encoding.write(data, field.offset, value);
+                        Reply::Integer(value as i64)
+                    }
+                    None => Reply::Nil,
+                },
+            };
+            replies.push(reply);
+        }
+
+        Ok(Reply::Array(replies))
+    }
+
+    pub fn bitfield(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/bitfield/
+
+        bitfield_generic(keyspace, args, false)
+    }
+
+    pub fn bitfield_ro(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/bitfield_ro/
+
+        bitfield_generic(keyspace, args, true)
+    }
+}
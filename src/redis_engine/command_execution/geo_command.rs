@@ -0,0 +1,548 @@
+pub mod geo {
+    use crate::redis_engine::{CommandArg, Keyspace, RedisObject, SortedSet};
+    use super::super::sorted_set_command::sorted_set;
+    use super::super::Reply;
+    use std::ops::Bound;
+    type Result = super::super::Result;
+
+    // Locations are stored in a sorted set, with a 52 bit geohash of the coordinates as the score.
+    // Like Redis, latitudes are limited to the range of the Web Mercator projection.
+    const STEP_MAX: u32 = 26;
+    const LONGITUDE_MIN: f64 = -180.0;
+    const LONGITUDE_MAX: f64 = 180.0;
+    const LATITUDE_MIN: f64 = -85.05112878;
+    const LATITUDE_MAX: f64 = 85.05112878;
+    const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+    const MERCATOR_MAX: f64 = 20037726.37;
+    const BASE32_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
+    const NOT_A_FLOAT: &str = "[ERROR]: value is not a valid float";
+
+    // Spreads the bits of `x` over the even positions and the ones of `y` over the odd ones
+    fn interleave(x: u32, y: u32) -> u64 {
+        let mut bits = 0;
+        for i in 0..32 {
+            bits |= (((x >> i) & 1) as u64) << (2 * i);
+            bits |= (((y >> i) & 1) as u64) << (2 * i + 1);
+        }
+
+        bits
+    }
+
+    fn deinterleave(bits: u64) -> (u32, u32) {
+        let (mut x, mut y) = (0, 0);
+        for i in 0..32 {
+            x |= (((bits >> (2 * i)) & 1) as u32) << i;
+            y |= (((bits >> (2 * i + 1)) & 1) as u32) << i;
+        }
+
+        (x, y)
+    }
+
+    fn encode(longitude: f64, latitude: f64, lat_range: (f64, f64)) -> u64 {
+        let cells = (1_u64 << STEP_MAX) as f64;
+        let lat_offset = (latitude - lat_range.0) / (lat_range.1 - lat_range.0);
+        let long_offset = (longitude - LONGITUDE_MIN) / (LONGITUDE_MAX - LONGITUDE_MIN);
+
+        interleave((lat_offset * cells) as u32, (long_offset * cells) as u32)
+    }
+
+    // The center of the cell the score stands for
+    fn decode(score: f64) -> (f64, f64) {
+        let (lat_bits, long_bits) = deinterleave(score as u64);
+        let cells = (1_u64 << STEP_MAX) as f64;
+        let lat_scale = LATITUDE_MAX - LATITUDE_MIN;
+        let long_scale = LONGITUDE_MAX - LONGITUDE_MIN;
+
+        let lat_min = LATITUDE_MIN + (lat_bits as f64 / cells) * lat_scale;
+        let lat_max = LATITUDE_MIN + ((lat_bits as f64 + 1.0) / cells) * lat_scale;
+        let long_min = LONGITUDE_MIN + (long_bits as f64 / cells) * long_scale;
+        let long_max = LONGITUDE_MIN + ((long_bits as f64 + 1.0) / cells) * long_scale;
+
+        (
+            ((long_min + long_max) / 2.0).clamp(LONGITUDE_MIN, LONGITUDE_MAX),
+            ((lat_min + lat_max) / 2.0).clamp(LATITUDE_MIN, LATITUDE_MAX),
+        )
+    }
+
+    // Great-circle distance in meters, using the haversine formula
+    fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+        let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+        let u = ((lat2 - lat1) / 2.0).sin();
+        let v = ((lon2.to_radians() - lon1.to_radians()) / 2.0).sin();
+
+        2.0 * EARTH_RADIUS_IN_METERS * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+    }
+
+    fn parse_coordinates(longitude: &[u8], latitude: &[u8]) -> core::result::Result<(f64, f64), String> {
+        let parse = |arg: &[u8]| match arg.parse::<f64>() {
+            Ok(value) if !value.is_nan() => Ok(value),
+            _ => Err(NOT_A_FLOAT.to_string()),
+        };
+        let (longitude, latitude) = (parse(longitude)?, parse(latitude)?);
+
+        if !(LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude) || !(LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude) {
+            return Err(format!("[ERROR]: invalid longitude,latitude pair {longitude:.6},{latitude:.6}"));
+        }
+
+        Ok((longitude, latitude))
+    }
+
+    // How many meters one of the units is
+    fn parse_unit(arg: &[u8]) -> core::result::Result<f64, String> {
+        match arg.as_text().to_lowercase().as_str() {
+            "m" => Ok(1.0),
+            "km" => Ok(1000.0),
+            "ft" => Ok(0.3048),
+            "mi" => Ok(1609.34),
+            _ => Err("[ERROR]: unsupported unit provided. please use M, KM, FT, MI".to_string()),
+        }
+    }
+
+    // Distances are rounded to 4 decimals, like Redis does
+    fn distance_reply(meters: f64, unit: f64) -> Reply {
+        Reply::Double((meters / unit * 10000.0).round() / 10000.0)
+    }
+
+    pub fn geoadd(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geoadd/
+
+        if args.len() < 4 {
+            return Err("[ERROR]: At least four arguments required for geoadd!".to_string());
+        }
+
+        let mut idx = 1;
+        while idx < args.len() && ["nx", "xx", "ch"].contains(&args[idx].as_text().to_lowercase().as_str()) {
+            idx += 1;
+        }
+
+        let triples = &args[idx..];
+        if triples.is_empty() || !triples.len().is_multiple_of(3) {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        // the same as ZADD with the geohashes as scores
+        let mut zadd_args: Vec<Vec<u8>> = args[..idx].iter().map(|arg| arg.to_vec()).collect();
+        for triple in triples.chunks(3) {
+            let (longitude, latitude) = parse_coordinates(triple[0], triple[1])?;
+            let hash = encode(longitude, latitude, (LATITUDE_MIN, LATITUDE_MAX));
+
+            zadd_args.push(hash.to_string().into_bytes());
+            zadd_args.push(triple[2].to_vec());
+        }
+
+        sorted_set::zadd(keyspace, zadd_args.iter().map(|arg| arg.as_slice()).collect())
+    }
+
+    pub fn geopos(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geopos/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for geopos!".to_string());
+        }
+
+        let zset = keyspace.get_zset(&args[0].as_text())?;
+        let positions = args[1..]
+            .iter()
+            .map(|member| match zset.and_then(|zset| zset.score(member)) {
+                Some(score) => {
+                    let (longitude, latitude) = decode(score);
+                    Reply::Array(vec![Reply::Double(longitude), Reply::Double(latitude)])
+                }
+                None => Reply::Nil,
+            })
+            .collect();
+
+        Ok(Reply::Array(positions))
+    }
+
+    pub fn geodist(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geodist/
+
+        if args.len() < 3 || args.len() > 4 {
+            return Err("[ERROR]: Usage: geodist KEY MEMBER1 MEMBER2 [M|KM|FT|MI]".to_string());
+        }
+
+        let unit = match args.get(3) {
+            Some(unit) => parse_unit(unit)?,
+            None => 1.0,
+        };
+
+        let zset = match keyspace.get_zset(&args[0].as_text())? {
+            Some(zset) => zset,
+            None => return Ok(Reply::Nil),
+        };
+
+        match (zset.score(args[1]), zset.score(args[2])) {
+            (Some(first), Some(second)) => {
+                let (lon1, lat1) = decode(first);
+                let (lon2, lat2) = decode(second);
+                Ok(distance_reply(distance(lon1, lat1, lon2, lat2), unit))
+            }
+            _ => Ok(Reply::Nil),
+        }
+    }
+
+    pub fn geohash(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geohash/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for geohash!".to_string());
+        }
+
+        let zset = keyspace.get_zset(&args[0].as_text())?;
+        let hashes = args[1..]
+            .iter()
+            .map(|member| match zset.and_then(|zset| zset.score(member)) {
+                Some(score) => {
+                    // the standard geohash covers every latitude, unlike the scores
+                    let (longitude, latitude) = decode(score);
+                    let bits = encode(longitude, latitude, (-90.0, 90.0));
+
+                    let hash: Vec<u8> = (0..11)
+                        .map(|i| match i {
+                            // there are only 52 bits, so the last character is always '0'
+                            10 => BASE32_ALPHABET[0],
+                            i => BASE32_ALPHABET[((bits >> (52 - (i + 1) * 5)) & 0x1f) as usize],
+                        })
+                        .collect();
+                    Reply::Bulk(hash)
+                }
+                None => Reply::Nil,
+            })
+            .collect();
+
+        Ok(Reply::Array(hashes))
+    }
+
+    enum Shape {
+        // radius in meters
+        Radius(f64),
+        // width and height in meters
+        Box(f64, f64),
+    }
+
+    // The score ranges of the 3x3 geohash cells around the center, picked big enough to cover the whole shape
+    fn search_ranges(longitude: f64, latitude: f64, shape: &Shape) -> Vec<(f64, f64)> {
+        let (half_width, half_height) = match shape {
+            Shape::Radius(radius) => (*radius, *radius),
+            Shape::Box(width, height) => (width / 2.0, height / 2.0),
+        };
+
+        // the bounding box of the shape, every longitude is in it once the shape reaches a pole
+        let lat_delta = (half_height / EARTH_RADIUS_IN_METERS).to_degrees();
+        let long_delta = match shape {
+            Shape::Radius(radius) => {
+                let angle = radius / EARTH_RADIUS_IN_METERS;
+                let ratio = angle.sin() / latitude.to_radians().cos();
+                if latitude.abs() + lat_delta >= 90.0 || ratio >= 1.0 {
+                    180.0
+                } else {
+                    ratio.asin().to_degrees()
+                }
+            }
+            Shape::Box(..) => {
+                // widths are measured along the latitude of each point, the one furthest from the equator is the widest
+                let furthest = (latitude.abs() + lat_delta).min(90.0);
+                let ratio = (half_width / EARTH_RADIUS_IN_METERS / 2.0).sin() / furthest.to_radians().cos();
+                if ratio >= 1.0 {
+                    180.0
+                } else {
+                    2.0 * ratio.asin().to_degrees()
+                }
+            }
+        };
+        let (lat_min, lat_max) = ((latitude - lat_delta).max(LATITUDE_MIN), (latitude + lat_delta).min(LATITUDE_MAX));
+
+        // the biggest step whose cells are about as big as the shape, cells get wider towards the poles
+        let mut range = half_width.hypot(half_height).max(half_width.max(half_height));
+        let mut step: i32 = 1;
+        if range > 0.0 {
+            while range < MERCATOR_MAX {
+                range *= 2.0;
+                step += 1;
+            }
+            step -= 2;
+            if latitude.abs() > 66.0 {
+                step -= 1;
+                if latitude.abs() > 80.0 {
+                    step -= 1;
+                }
+            }
+        } else {
+            step = STEP_MAX as i32;
+        }
+        let mut step = step.clamp(1, STEP_MAX as i32) as u32;
+
+        // make the cells bigger until the neighbours of the center one are enough to cover the shape
+        let (cells, long_idx, lat_idx) = loop {
+            let cells = 1_i64 << step;
+            let cell_width = (LONGITUDE_MAX - LONGITUDE_MIN) / cells as f64;
+            let cell_height = (LATITUDE_MAX - LATITUDE_MIN) / cells as f64;
+            let long_idx = ((longitude - LONGITUDE_MIN) / cell_width).floor() as i64;
+            let lat_idx = ((latitude - LATITUDE_MIN) / cell_height).floor() as i64;
+
+            let covered = longitude - long_delta >= LONGITUDE_MIN + (long_idx - 1) as f64 * cell_width
+                && longitude + long_delta <= LONGITUDE_MIN + (long_idx + 2) as f64 * cell_width
+                && lat_min >= LATITUDE_MIN + (lat_idx - 1) as f64 * cell_height
+                && lat_max <= LATITUDE_MIN + (lat_idx + 2) as f64 * cell_height;
+            if covered || step == 1 {
+                break (cells, long_idx, lat_idx);
+            }
+            step -= 1;
+        };
+
+        let shift = 2 * (STEP_MAX - step);
+        let mut ranges = vec![];
+        for lat in lat_idx - 1..=lat_idx + 1 {
+            if lat < 0 || lat >= cells {
+                continue;
+            }
+
+            // longitudes wrap around at the antimeridian
+            for long in long_idx - 1..=long_idx + 1 {
+                let hash = interleave(lat as u32, long.rem_euclid(cells) as u32);
+                let range = ((hash << shift) as f64, ((hash + 1) << shift) as f64);
+                if !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+        }
+
+        ranges
+    }
+
+    struct Found {
+        member: Vec<u8>,
+        score: f64,
+        distance: f64,
+    }
+
+    // Everything GEOSEARCH and GEOSEARCHSTORE share, `args` start right after the key(s)
+    struct Search {
+        from_member: Option<Vec<u8>>,
+        from_coordinates: Option<(f64, f64)>,
+        shape: Option<(Shape, f64)>,
+        ascending: Option<bool>,
+        count: Option<(usize, bool)>,
+        with_coord: bool,
+        with_dist: bool,
+        with_hash: bool,
+        store_dist: bool,
+    }
+
+    impl Search {
+        fn parse(args: &[&[u8]], store: bool) -> core::result::Result<Self, String> {
+            let mut search = Search {
+                from_member: None,
+                from_coordinates: None,
+                shape: None,
+                ascending: None,
+                count: None,
+                with_coord: false,
+                with_dist: false,
+                with_hash: false,
+                store_dist: false,
+            };
+
+            let arg = |idx: usize| args.get(idx).copied().ok_or_else(|| SYNTAX_ERROR.to_string());
+            let number = |idx: usize| match arg(idx)?.parse::<f64>() {
+                Ok(value) if value >= 0.0 => Ok(value),
+                Ok(_) => Err("[ERROR]: radius cannot be negative".to_string()),
+                Err(_) => Err("[ERROR]: need numeric radius".to_string()),
+            };
+
+            let mut idx = 0;
+            while idx < args.len() {
+                match args[idx].as_text().to_lowercase().as_str() {
+                    "frommember" if search.from_member.is_none() => {
+                        search.from_member = Some(arg(idx + 1)?.to_vec());
+                        idx += 2;
+                    }
+                    "fromlonlat" if search.from_coordinates.is_none() => {
+                        search.from_coordinates = Some(parse_coordinates(arg(idx + 1)?, arg(idx + 2)?)?);
+                        idx += 3;
+                    }
+                    "byradius" if search.shape.is_none() => {
+                        let unit = parse_unit(arg(idx + 2)?)?;
+                        search.shape = Some((Shape::Radius(number(idx + 1)? * unit), unit));
+                        idx += 3;
+                    }
+                    "bybox" if search.shape.is_none() => {
+                        let unit = parse_unit(arg(idx + 3)?)?;
+                        search.shape = Some((Shape::Box(number(idx + 1)? * unit, number(idx + 2)? * unit), unit));
+                        idx += 4;
+                    }
+                    "asc" => {
+                        search.ascending = Some(true);
+                        idx += 1;
+                    }
+                    "desc" => {
+                        search.ascending = Some(false);
+                        idx += 1;
+                    }
+                    "count" => {
+                        let count = match arg(idx + 1)?.parse::<i64>() {
+                            Ok(count) if count > 0 => count as usize,
+                            Ok(_) => return Err("[ERROR]: COUNT must be > 0".to_string()),
+                            Err(_) => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+                        };
+                        let any = args.get(idx + 2).is_some_and(|arg| arg.as_text().eq_ignore_ascii_case("any"));
+                        search.count = Some((count, any));
+                        idx += if any { 3 } else { 2 };
+                    }
+                    "withcoord" if !store => {
+                        search.with_coord = true;
+                        idx += 1;
+                    }
+                    "withdist" if !store => {
+                        search.with_dist = true;
+                        idx += 1;
+                    }
+                    "withhash" if !store => {
+                        search.with_hash = true;
+                        idx += 1;
+                    }
+                    "storedist" if store => {
+                        search.store_dist = true;
+                        idx += 1;
+                    }
+                    _ => return Err(SYNTAX_ERROR.to_string()),
+                }
+            }
+
+            if search.from_member.is_some() == search.from_coordinates.is_some() {
+                return Err("[ERROR]: exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH".to_string());
+            }
+            if search.shape.is_none() {
+                return Err("[ERROR]: exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH".to_string());
+            }
+
+            Ok(search)
+        }
+
+        // Members within the shape, sorted and limited the way the options asked for
+        fn run(&self, zset: &SortedSet) -> core::result::Result<Vec<Found>, String> {
+            let (longitude, latitude) = match (&self.from_member, self.from_coordinates) {
+                (Some(member), _) => match zset.score(member) {
+                    Some(score) => decode(score),
+                    None => return Err("[ERROR]: could not decode requested zset member".to_string()),
+                },
+                (None, Some(coordinates)) => coordinates,
+                (None, None) => unreachable!(),
+            };
+            let shape = &self.shape.as_ref().unwrap().0;
+
+            // without ANY, COUNT returns the closest members
+            let (limit, any) = self.count.unwrap_or((usize::MAX, false));
+            let ascending = match self.ascending {
+                None if self.count.is_some() && !any => Some(true),
+                ascending => ascending,
+            };
+
+            let mut found = vec![];
+            'ranges: for (min, max) in search_ranges(longitude, latitude, shape) {
+                for (member, score) in zset.range_by_score(Bound::Included(min), Bound::Excluded(max)) {
+                    let (lon, lat) = decode(score);
+                    let dist = distance(longitude, latitude, lon, lat);
+
+                    let inside = match shape {
+                        Shape::Radius(radius) => dist <= *radius,
+                        Shape::Box(width, height) => {
+                            let lat_distance = EARTH_RADIUS_IN_METERS * (lat.to_radians() - latitude.to_radians()).abs();
+                            lat_distance <= height / 2.0 && distance(longitude, lat, lon, lat) <= width / 2.0
+                        }
+                    };
+                    if inside {
+                        found.push(Found { member: member.to_vec(), score, distance: dist });
+                        if any && found.len() == limit {
+                            break 'ranges;
+                        }
+                    }
+                }
+            }
+
+            match ascending {
+                Some(true) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+                Some(false) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+                None => {}
+            }
+            found.truncate(limit);
+
+            Ok(found)
+        }
+    }
+
+    pub fn geosearch(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geosearch/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for geosearch!".to_string());
+        }
+
+        let search = Search::parse(&args[1..], false)?;
+        let zset = match keyspace.get_zset(&args[0].as_text())? {
+            Some(zset) => zset,
+            None => return Ok(Reply::Array(vec![])),
+        };
+
+        let unit = search.shape.as_ref().unwrap().1;
+        let replies = search
+            .run(zset)?
+            .into_iter()
+            .map(|found| {
+                if !search.with_dist && !search.with_hash && !search.with_coord {
+                    return Reply::Bulk(found.member);
+                }
+
+                let mut reply = vec![Reply::Bulk(found.member)];
+                if search.with_dist {
+                    reply.push(distance_reply(found.distance, unit));
+                }
+                if search.with_hash {
+                    reply.push(Reply::Integer(found.score as i64));
+                }
+                if search.with_coord {
+                    let (longitude, latitude) = decode(found.score);
+                    reply.push(Reply::Array(vec![Reply::Double(longitude), Reply::Double(latitude)]));
+                }
+                Reply::Array(reply)
+            })
+            .collect();
+
+        Ok(Reply::Array(replies))
+    }
+
+    pub fn geosearchstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/geosearchstore/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for geosearchstore!".to_string());
+        }
+
+        let search = Search::parse(&args[2..], true)?;
+        let found = match keyspace.get_zset(&args[1].as_text())? {
+            Some(zset) => search.run(zset)?,
+            None => vec![],
+        };
+
+        // STOREDIST keeps the distances in place of the geohashes
+        let unit = search.shape.as_ref().unwrap().1;
+        let mut zset = SortedSet::new();
+        for found in found {
+            let score = if search.store_dist { found.distance / unit } else { found.score };
+            zset.insert(found.member, score);
+        }
+
+        let len = zset.len();
+        let destination: &str = &args[0].as_text();
+        if zset.is_empty() {
+            keyspace.remove(destination);
+        } else {
+            keyspace.insert(destination.to_string(), RedisObject::SortedSet(zset));
+        }
+
+        Ok(Reply::Integer(len as i64))
+    }
+}
@@ -1,49 +1,47 @@
-#![allow(clippy::if_same_then_else)] // for lines 18 and 20
-
 pub mod hash {
-    use crate::redis_engine::RedisValue;
+    use crate::redis_engine::{CommandArg, Keyspace, RedisValue};
+    use super::super::Reply;
     type Result = super::super::Result;
-    type Hash = std::collections::HashMap<String, std::collections::HashMap<String, RedisValue>>;
 
-    pub fn hget(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hget(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hget/
 
         if args.len() < 2 {
             return Err("[ERROR]: At least two arguments required for hget!".to_string());
         }
 
-        let key = args[0];
-        let field = args[1];
+        let key: &str = &args[0].as_text();
+        let field: &str = &args[1].as_text();
+
+        let hash = match keyspace.get_hash(key)? {
+            Some(hash) => hash,
+            None => return Ok(Reply::Nil),
+        };
 
-        if !hash.contains_key(key) {
-            return Ok("(nil)".to_string());
-        } else if !hash[key].contains_key(field) {
-            return Ok("(nil)".to_string());
+        if !hash.contains_key(field) {
+            return Ok(Reply::Nil);
         }
 
-        Ok(hash[key][field].to_string())
+        Ok(Reply::Bulk(hash[field].to_bytes()))
     }
 
-    pub fn hexists(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hexists(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hexists/
 
         if args.len() < 2 {
             return Err("[ERROR]: At least two arguments required for hexists!".to_string());
         }
 
-        let key = args[0];
-        let field = args[1];
+        let key: &str = &args[0].as_text();
+        let field: &str = &args[1].as_text();
 
-        if !hash.contains_key(key) {
-            return Ok("0".to_string());
-        } else if !hash[key].contains_key(field) {
-            return Ok("0".to_string());
+        match keyspace.get_hash(key)? {
+            Some(hash) if hash.contains_key(field) => Ok(Reply::Integer(1)),
+            _ => Ok(Reply::Integer(0)),
         }
-
-        Ok("1".to_string())
     }
 
-    pub fn hdel(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hdel(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hdel/
 
         if args.len() < 2 {
@@ -51,44 +49,44 @@ pub mod hash {
         }
 
         let mut affected = 0;
-        let key = args[0];
+        let key: &str = &args[0].as_text();
 
-        if !hash.contains_key(key) {
-            return Ok(format!("{}", affected));
-        }
+        let hash = match keyspace.get_hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(Reply::Integer(affected)),
+        };
 
         for f in &args[1..] {
-            if hash.get_mut(key).unwrap().remove(&f.to_string()).is_some() {
+            if hash.remove(f.as_text().as_ref()).is_some() {
                 affected+=1;
             }
         }
+        keyspace.remove_if_empty(key);
 
-        Ok(format!("{}", affected))
+        Ok(Reply::Integer(affected))
     }
 
-    pub fn hgetall(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hgetall(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hgetall/
 
         if args.is_empty() {
             return Err("[ERROR]: At least two arguments required for hgetall!".to_string());
         }
 
-        let mut vec = vec![];
-        let key = args[0];
+        let mut pairs = vec![];
+        let key: &str = &args[0].as_text();
 
-        if hash.contains_key(key) {
-            for k in hash[key].keys() {
-                vec.push(k.to_string());
-                let clone = hash[key][k].to_string().clone();
-                vec.push(clone);
+        if let Some(hash) = keyspace.get_hash(key)? {
+            for (k, v) in hash {
+                pairs.push((Reply::bulk(k), Reply::Bulk(v.to_bytes())));
             }
         }
 
-        Ok(format!("{:?}", vec))
+        Ok(Reply::Map(pairs))
     }
 
 
-    pub fn hkeys(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hkeys(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hkeys/
 
         if args.is_empty() {
@@ -96,11 +94,11 @@ pub mod hash {
         }
 
         let mut vec = vec![];
-        let key = args[0];
+        let key: &str = &args[0].as_text();
 
-        if hash.contains_key(key) {
-            for k in hash[key].keys() {
-                vec.push(k.to_string())
+        if let Some(hash) = keyspace.get_hash(key)? {
+            for k in hash.keys() {
+                vec.push(Reply::bulk(k))
             }
 
             // this can be done like so as well:
@@ -109,11 +107,11 @@ pub mod hash {
             // for more info see: https://doc.rust-lang.org/std/collections/struct.HashMap.html#method.into_keys
         }
 
-        Ok(format!("{:?}", vec))
+        Ok(Reply::Array(vec))
     }
 
 
-    pub fn hvals(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hvals(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hvals/
 
         if args.is_empty() {
@@ -121,11 +119,11 @@ pub mod hash {
         }
 
         let mut vec = vec![];
-        let key = args[0];
+        let key: &str = &args[0].as_text();
 
-        if hash.contains_key(key) {
-            for k in hash[key].values() {
-                vec.push(k.to_string())
+        if let Some(hash) = keyspace.get_hash(key)? {
+            for k in hash.values() {
+                vec.push(Reply::Bulk(k.to_bytes()))
             }
 
             // this can be done like so as well:
@@ -134,46 +132,43 @@ pub mod hash {
             // for more info see: https://doc.rust-lang.org/std/collections/struct.HashMap.html#method.into_values
         }
 
-        Ok(format!("{:?}", vec))
+        Ok(Reply::Array(vec))
     }
 
-    pub fn hlen(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hlen(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hlen/
 
         if args.is_empty() {
             return Err("[ERROR]: At least one argument required for hlen!".to_string());
         }
 
-        let key = args[0];
+        let key: &str = &args[0].as_text();
 
-        if !hash.contains_key(key) {
-            return Ok("0".to_string())
+        match keyspace.get_hash(key)? {
+            Some(hash) => Ok(Reply::Integer(hash.keys().len() as i64)),
+            None => Ok(Reply::Integer(0)),
         }
-
-        return Ok(format!("{}", hash[key].keys().len()))
     }
 
-    pub fn hset(hash: &mut Hash, args: Vec<&str>) -> Result {
+    pub fn hset(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/hset/
 
         if args.len() < 3 {
             return Err("[ERROR]: At least three arguments required for hset!".to_string());
         }
 
-        let key = args[0];
+        let key: &str = &args[0].as_text();
         let mut fields = vec![];
         let mut values = vec![];
         let mut affected = 0;
 
         // get all fields and values
-        let mut idx = 1;
-        for item in &args[1..] {
-            if idx % 2 != 0 {
+        for (idx, item) in args[1..].iter().enumerate() {
+            if idx % 2 == 0 {
                 fields.push(item);
             } else {
                 values.push(item);
             }
-            idx+=1;
         }
 
         if fields.len() != values.len() {
@@ -183,25 +178,18 @@ pub mod hash {
         let mut sub_hash = std::collections::HashMap::<String, RedisValue>::new();
 
         for i in 0..fields.len() {
-            sub_hash.insert(fields[i].to_string(), RedisValue::from_str(values[i]));
-        }
-
-        if !hash.contains_key(key) {
-            affected = sub_hash.len();
-            hash.insert(key.to_string(), sub_hash);
-        } else {
-            for (k,v) in sub_hash {
-                hash
-                    .get_mut(key)
-                    .unwrap()
-                    .insert(k.to_string(), v);
-                affected+=1;
-            }
+            sub_hash.insert(fields[i].as_text().to_string(), RedisValue::from_bytes(values[i]));
+        }
+
+        let hash = keyspace.get_or_create_hash(key)?;
+        for (k,v) in sub_hash {
+            hash.insert(k.to_string(), v);
+            affected+=1;
         }
-        return Ok(format!("{}", affected))
+        Ok(Reply::Integer(affected as i64))
     }
 
-     pub fn hmset(hash: &mut Hash, args: Vec<&str>) -> Result {
+     pub fn hmset(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
          // https://redis.io/commands/hmset/
 
          // See the link above.
@@ -209,6 +197,6 @@ pub mod hash {
           * As of Redis version 4.0.0, this command is regarded as deprecated.
           * It can be replaced by HSET with multiple field-value pairs when migrating or writing new code.
           */
-         self::hset(hash, args)
+         self::hset(keyspace, args)
      }
 }
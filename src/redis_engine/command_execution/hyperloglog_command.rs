@@ -0,0 +1,407 @@
+pub mod hyperloglog {
+    use crate::redis_engine::{CommandArg, Keyspace};
+    use super::super::Reply;
+    type Result = super::super::Result;
+
+    // Same parameters as Redis: 2^14 registers of 6 bits each, which gives a standard error of
+    // 1.04 / sqrt(2^14) = 0.81%. The string layout is the one Redis uses as well, so the values
+    // can be moved between the two with a plain GET and SET.
+    const P: u32 = 14;
+    const Q: u32 = 64 - P;
+    const REGISTERS: usize = 1 << P;
+    const BITS: usize = 6;
+    const REGISTER_MASK: u8 = (1 << BITS) - 1;
+    const HASH_SEED: u64 = 0xadc83b19;
+    const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+    // "HYLL", the encoding, 3 unused bytes and the cached cardinality as a little endian u64.
+    // The most significant bit of the cardinality marks the cache as out of date.
+    const HEADER_SIZE: usize = 16;
+    const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * BITS).div_ceil(8);
+    const DENSE: u8 = 0;
+    const SPARSE: u8 = 1;
+
+    // Sparse opcodes: ZERO is 00xxxxxx, a run of up to 64 empty registers,
+    // XZERO is 01xxxxxx yyyyyyyy, a run of up to 16384 empty registers and
+    // VAL is 1vvvvvxx, a run of up to 4 registers set to a value of up to 32
+    const ZERO_MAX_LEN: usize = 64;
+    const XZERO_MAX_LEN: usize = 16384;
+    const VAL_MAX_VALUE: u8 = 32;
+    const VAL_MAX_LEN: usize = 4;
+    // "hll-sparse-max-bytes" of the default Redis config, bigger ones are converted to dense
+    const SPARSE_MAX_BYTES: usize = 3000;
+
+    const INVALID_HLL: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+    const CORRUPTED_HLL: &str = "INVALIDOBJ Corrupted HLL object detected";
+
+    // MurmurHash64A, the hash function Redis uses for HyperLogLogs
+    fn murmur_hash_64a(key: &[u8], seed: u64) -> u64 {
+        const M: u64 = 0xc6a4a7935bd1e995;
+        const R: u32 = 47;
+
+        let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+        let chunks = key.chunks_exact(8);
+        let tail = chunks.remainder();
+
+        for chunk in chunks {
+            let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+            k = k.wrapping_mul(M);
+            k ^= k >> R;
+            k = k.wrapping_mul(M);
+
+            h ^= k;
+            h = h.wrapping_mul(M);
+        }
+
+        if !tail.is_empty() {
+            for (i, b) in tail.iter().enumerate() {
+                h ^= (*b as u64) << (8 * i);
+            }
+            h = h.wrapping_mul(M);
+        }
+
+        h ^= h >> R;
+        h = h.wrapping_mul(M);
+        h ^= h >> R;
+
+        h
+    }
+
+    // The register the element goes to and the length of the 000..1 pattern of the rest of its hash
+    fn pattern_len(element: &[u8]) -> (usize, u8) {
+        let hash = murmur_hash_64a(element, HASH_SEED);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+
+        // the extra bit makes sure the pattern is never longer than Q + 1
+        let hash = (hash >> P) | (1 << Q);
+
+        (index, hash.trailing_zeros() as u8 + 1)
+    }
+
+    // Registers are packed 6 bits at a time, starting from the least significant bit of each byte
+    fn get_dense_register(data: &[u8], index: usize) -> u8 {
+        let byte = index * BITS / 8;
+        let fb = (index * BITS % 8) as u32;
+        let b0 = data[byte] as u16;
+        let b1 = data.get(byte + 1).copied().unwrap_or(0) as u16;
+
+        (((b0 >> fb) | (b1 << (8 - fb))) as u8) & REGISTER_MASK
+    }
+
+    fn set_dense_register(data: &mut [u8], index: usize, value: u8) {
+        let byte = index * BITS / 8;
+        let fb = (index * BITS % 8) as u32;
+        let value = value as u16;
+
+        data[byte] &= !((REGISTER_MASK as u16) << fb) as u8;
+        data[byte] |= (value << fb) as u8;
+        if let Some(next) = data.get_mut(byte + 1) {
+            *next &= !((REGISTER_MASK as u16) >> (8 - fb)) as u8;
+            *next |= (value >> (8 - fb)) as u8;
+        }
+    }
+
+    // Helper functions of the estimator by Otmar Ertl, the one Redis uses since 5.0
+    fn sigma(mut x: f64) -> f64 {
+        if x == 1.0 {
+            return f64::INFINITY;
+        }
+
+        let mut y = 1.0;
+        let mut z = x;
+        loop {
+            x *= x;
+            let z_prime = z;
+            z += x * y;
+            y += y;
+            if z_prime == z {
+                return z;
+            }
+        }
+    }
+
+    fn tau(mut x: f64) -> f64 {
+        if x == 0.0 || x == 1.0 {
+            return 0.0;
+        }
+
+        let mut y = 1.0;
+        let mut z = 1.0 - x;
+        loop {
+            x = x.sqrt();
+            let z_prime = z;
+            y *= 0.5;
+            z -= (1.0 - x).powi(2) * y;
+            if z_prime == z {
+                return z / 3.0;
+            }
+        }
+    }
+
+    // The registers are always worked on unpacked, the encoding only matters when reading and writing the string
+    struct HyperLogLog {
+        registers: Vec<u8>,
+        sparse: bool,
+        cached_cardinality: Option<u64>,
+    }
+
+    impl HyperLogLog {
+        fn new() -> Self {
+            Self {
+                registers: vec![0; REGISTERS],
+                sparse: true,
+                cached_cardinality: Some(0),
+            }
+        }
+
+        fn from_bytes(bytes: &[u8]) -> core::result::Result<Self, String> {
+            if bytes.len() < HEADER_SIZE || &bytes[..4] != b"HYLL" || bytes[4] > SPARSE {
+                return Err(INVALID_HLL.to_string());
+            }
+
+            let sparse = bytes[4] == SPARSE;
+            if !sparse && bytes.len() != DENSE_SIZE {
+                return Err(INVALID_HLL.to_string());
+            }
+
+            let cached_cardinality = if bytes[15] & 0x80 == 0 {
+                Some(u64::from_le_bytes(bytes[8..16].try_into().unwrap()))
+            } else {
+                None
+            };
+
+            let data = &bytes[HEADER_SIZE..];
+            let mut registers = vec![0; REGISTERS];
+            if sparse {
+                let mut index = 0;
+                let mut i = 0;
+                while i < data.len() {
+                    let op = data[i];
+                    let (value, len) = match op >> 6 {
+                        0b00 => (0, (op & 0x3f) as usize + 1),
+                        0b01 => {
+                            i += 1;
+                            let next = *data.get(i).ok_or_else(|| CORRUPTED_HLL.to_string())?;
+                            (0, ((((op & 0x3f) as usize) << 8) | next as usize) + 1)
+                        }
+                        _ => (((op >> 2) & 0x1f) + 1, (op & 0x03) as usize + 1),
+                    };
+                    i += 1;
+
+                    if index + len > REGISTERS {
+                        return Err(CORRUPTED_HLL.to_string());
+                    }
+                    registers[index..index + len].fill(value);
+                    index += len;
+                }
+
+                if index != REGISTERS {
+                    return Err(CORRUPTED_HLL.to_string());
+                }
+            } else {
+                for (index, register) in registers.iter_mut().enumerate() {
+                    *register = get_dense_register(data, index);
+                }
+            }
+
+            Ok(Self { registers, sparse, cached_cardinality })
+        }
+
+        // None when some register doesn't fit into a VAL opcode, or the result gets too big
+        fn encode_sparse(&self) -> Option<Vec<u8>> {
+            let mut data = vec![];
+            let mut index = 0;
+
+            while index < REGISTERS {
+                let value = self.registers[index];
+                let run = self.registers[index..].iter().take_while(|r| **r == value).count();
+                index += run;
+
+                let mut left = run;
+                while left > 0 {
+                    if value == 0 {
+                        let len = left.min(XZERO_MAX_LEN);
+                        if len > ZERO_MAX_LEN {
+                            data.push(0x40 | ((len - 1) >> 8) as u8);
+                            data.push(((len - 1) & 0xff) as u8);
+                        } else {
+                            data.push((len - 1) as u8);
+                        }
+                        left -= len;
+                    } else {
+                        if value > VAL_MAX_VALUE {
+                            return None;
+                        }
+
+                        let len = left.min(VAL_MAX_LEN);
+                        data.push(0x80 | ((value - 1) << 2) | (len - 1) as u8);
+                        left -= len;
+                    }
+                }
+            }
+
+            if HEADER_SIZE + data.len() > SPARSE_MAX_BYTES {
+                return None;
+            }
+
+            Some(data)
+        }
+
+        fn encode(&mut self) -> Vec<u8> {
+            // once a HyperLogLog is dense, it stays dense
+            let sparse_data = if self.sparse { self.encode_sparse() } else { None };
+            self.sparse = sparse_data.is_some();
+
+            let mut bytes = b"HYLL".to_vec();
+            bytes.push(if self.sparse { SPARSE } else { DENSE });
+            bytes.extend([0; 3]);
+            match self.cached_cardinality {
+                Some(cardinality) => bytes.extend(cardinality.to_le_bytes()),
+                None => bytes.extend([0, 0, 0, 0, 0, 0, 0, 0x80]),
+            }
+
+            match sparse_data {
+                Some(data) => bytes.extend(data),
+                None => {
+                    let mut data = vec![0; DENSE_SIZE - HEADER_SIZE];
+                    for (index, register) in self.registers.iter().enumerate() {
+                        set_dense_register(&mut data, index, *register);
+                    }
+                    bytes.extend(data);
+                }
+            }
+
+            bytes
+        }
+
+        // Returns true if a register got updated, i.e. the cardinality might have changed
+        fn add(&mut self, element: &[u8]) -> bool {
+            let (index, count) = pattern_len(element);
+            if self.registers[index] >= count {
+                return false;
+            }
+
+            self.registers[index] = count;
+            self.cached_cardinality = None;
+            true
+        }
+
+        fn merge(&mut self, other: &HyperLogLog) {
+            for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+                *register = (*register).max(*other);
+            }
+            self.cached_cardinality = None;
+        }
+
+        fn count(&self) -> u64 {
+            let m = REGISTERS as f64;
+            let mut histogram = [0_u32; 64];
+            for register in &self.registers {
+                histogram[*register as usize] += 1;
+            }
+
+            let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+            for j in (1..=Q as usize).rev() {
+                z += histogram[j] as f64;
+                z *= 0.5;
+            }
+            z += m * sigma(histogram[0] as f64 / m);
+
+            (ALPHA_INF * m * m / z).round() as u64
+        }
+    }
+
+    fn load(keyspace: &Keyspace, key: &str) -> core::result::Result<Option<HyperLogLog>, String> {
+        match keyspace.get_string(key)? {
+            Some(value) => Ok(Some(HyperLogLog::from_bytes(&value.to_bytes())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn pfadd(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pfadd/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for pfadd!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let (mut hll, mut updated) = match load(keyspace, key)? {
+            Some(hll) => (hll, false),
+            None => (HyperLogLog::new(), true),
+        };
+
+        for element in &args[1..] {
+            if hll.add(element) {
+                updated = true;
+            }
+        }
+
+        if updated {
+            keyspace.update_string(key, &hll.encode())?;
+        }
+
+        Ok(Reply::Integer(updated as i64))
+    }
+
+    pub fn pfcount(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pfcount/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for pfcount!".to_string());
+        }
+
+        // the union of several keys is counted on the fly, without touching any of them
+        if args.len() > 1 {
+            let mut union = HyperLogLog::new();
+            for key in &args {
+                if let Some(hll) = load(keyspace, &key.as_text())? {
+                    union.merge(&hll);
+                }
+            }
+
+            return Ok(Reply::Integer(union.count() as i64));
+        }
+
+        let key: &str = &args[0].as_text();
+        let mut hll = match load(keyspace, key)? {
+            Some(hll) => hll,
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        // a single key caches its cardinality until the next update
+        let cardinality = match hll.cached_cardinality {
+            Some(cardinality) => cardinality,
+            None => {
+                let cardinality = hll.count();
+                hll.cached_cardinality = Some(cardinality);
+                keyspace.update_string(key, &hll.encode())?;
+                cardinality
+            }
+        };
+
+        Ok(Reply::Integer(cardinality as i64))
+    }
+
+    pub fn pfmerge(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pfmerge/
+
+        if args.is_empty() {
+            return Err("[ERROR]: At least one argument required for pfmerge!".to_string());
+        }
+
+        // the destination is a part of the union as well
+        let mut union = HyperLogLog::new();
+        for key in &args {
+            if let Some(hll) = load(keyspace, &key.as_text())? {
+                union.merge(&hll);
+            }
+        }
+
+        // like Redis, the result of a merge is always dense
+        union.sparse = false;
+        keyspace.update_string(&args[0].as_text(), &union.encode())?;
+
+        Ok(Reply::ok())
+    }
+}
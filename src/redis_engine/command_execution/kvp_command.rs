@@ -1,54 +1,644 @@
 #![allow(clippy::comparison_chain)]
 
 pub mod kvp {
-    use crate::redis_engine::RedisValue;
+    use crate::redis_engine::{unix_time_ms, CommandArg, Keyspace, RedisObject, RedisValue};
+    use super::super::Reply;
 
-    type KVPHash = std::collections::HashMap<String, RedisValue>;
     type Result = super::super::Result;
 
-    fn set_from_redis_value(kvp: &mut KVPHash, name: &str, value: RedisValue) -> Result {
-        kvp.insert(name.to_string(), value);
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
 
-        Ok("Ok".to_string())
+    // Turns the argument of EX, PX, EXAT or PXAT into an absolute deadline in milliseconds
+    fn parse_deadline(option: &str, value: Option<&&[u8]>, command: &str) -> core::result::Result<u64, String> {
+        let value = value.ok_or_else(|| SYNTAX_ERROR.to_string())?;
+        let time = value
+            .parse::<i64>()
+            .map_err(|_| "[ERROR]: value is not an integer or out of range".to_string())?;
+
+        let invalid = || format!("[ERROR]: invalid expire time in '{command}' command");
+        if time <= 0 {
+            return Err(invalid());
+        }
+
+        let now = unix_time_ms() as i64;
+        let deadline = match option {
+            "ex" => time.checked_mul(1000).and_then(|t| t.checked_add(now)),
+            "px" => time.checked_add(now),
+            "exat" => time.checked_mul(1000),
+            _ => Some(time),
+        };
+
+        deadline.map(|d| d as u64).ok_or_else(invalid)
     }
 
-    pub fn set(kvp: &mut KVPHash, args: Vec<&str>) -> Result {
-        // https://redis.io/commands/set/
+    fn set_generic(
+        keyspace: &mut Keyspace,
+        key: &str,
+        value: &[u8],
+        deadline: Option<u64>,
+        keep_ttl: bool,
+    ) {
+        let ttl = keyspace.expiry(key);
+
+        // SET overwrites whatever the key was holding, regardless of its type
+        keyspace.insert(key.to_string(), RedisObject::String(RedisValue::from_bytes(value)));
+
+        if let Some(at) = deadline.or(if keep_ttl { ttl } else { None }) {
+            keyspace.set_expiry(key, at);
+        }
+    }
+
+    fn old_value(keyspace: &Keyspace, key: &str) -> Result {
+        match keyspace.get_string(key)? {
+            Some(value) => value.to_result_value(),
+            None => Ok(Reply::Nil),
+        }
+    }
 
-        let mut text = String::new();
+    pub fn set(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/set/
 
         if args.len() < 2 {
             return Err("[ERROR]: Too few arguments for set".to_string());
-        } else if args.len() > 2 {
-            for i in &args[1..] {
-                text.push_str(i);
-                text.push(' ');
+        }
+
+        let (mut nx, mut xx, mut get, mut keep_ttl) = (false, false, false, false);
+        let mut deadline = None;
+        let mut idx = 2;
+
+        while idx < args.len() {
+            let option = args[idx].as_text().to_lowercase();
+
+            match option.as_str() {
+                "nx" if !xx => nx = true,
+                "xx" if !nx => xx = true,
+                "get" => get = true,
+                "keepttl" if deadline.is_none() => keep_ttl = true,
+                "ex" | "px" | "exat" | "pxat" if deadline.is_none() && !keep_ttl => {
+                    deadline = Some(parse_deadline(&option, args.get(idx + 1), "set")?);
+                    idx += 1;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
             }
-            //Err("[ERROR]: Too many arguments for set".to_string())
-        } else {
-            text.push_str(args[1]);
+            idx += 1;
+        }
+
+        let key: &str = &args[0].as_text();
+        // GET fails on keys that aren't strings, in which case nothing gets set either
+        let reply = if get { old_value(keyspace, key)? } else { Reply::ok() };
+
+        let exists = keyspace.contains_key(key);
+        if (nx && exists) || (xx && !exists) {
+            return Ok(if get { reply } else { Reply::Nil });
+        }
+
+        set_generic(keyspace, key, args[1], deadline, keep_ttl);
+
+        Ok(reply)
+    }
+
+    pub fn setnx(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/setnx/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: setnx requires exactly two arguments!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        if keyspace.contains_key(key) {
+            return Ok(Reply::Integer(0));
+        }
+
+        set_generic(keyspace, key, args[1], None, false);
+
+        Ok(Reply::Integer(1))
+    }
+
+    pub fn setex(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/setex/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: setex requires exactly three arguments!".to_string());
+        }
+
+        let deadline = parse_deadline("ex", args.get(1), "setex")?;
+        set_generic(keyspace, &args[0].as_text(), args[2], Some(deadline), false);
+
+        Ok(Reply::ok())
+    }
+
+    pub fn psetex(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/psetex/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: psetex requires exactly three arguments!".to_string());
         }
 
-        kvp.insert(args[0].to_string(), RedisValue::from_str(&text));
+        let deadline = parse_deadline("px", args.get(1), "psetex")?;
+        set_generic(keyspace, &args[0].as_text(), args[2], Some(deadline), false);
 
-        Ok("Ok".to_string())
+        Ok(Reply::ok())
     }
 
-    pub fn get(kvp: &mut KVPHash, args: Vec<&str>) -> Result {
+    pub fn get(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/get/
 
         if args.len() != 1 {
             return Err("[ERROR]: get requires only one argument!".to_string());
         }
 
-        if kvp.contains_key(args[0]) {
-            kvp[args[0]].to_result_value()
-        } else {
-            Ok("(nil)".to_string())
+        old_value(keyspace, &args[0].as_text())
+    }
+
+    pub fn getset(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/getset/
+
+        // As of Redis version 6.2.0, this command is regarded as deprecated.
+        // It can be replaced by SET with the GET argument when migrating or writing new code.
+        if args.len() != 2 {
+            return Err("[ERROR]: getset requires exactly two arguments!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let reply = old_value(keyspace, key)?;
+        set_generic(keyspace, key, args[1], None, false);
+
+        Ok(reply)
+    }
+
+    pub fn getdel(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/getdel/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: getdel requires only one argument!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let reply = old_value(keyspace, key)?;
+        keyspace.remove(key);
+
+        Ok(reply)
+    }
+
+    pub fn getex(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/getex/
+
+        if args.is_empty() {
+            return Err("[ERROR]: getex requires at least one argument!".to_string());
+        }
+
+        let mut deadline = None;
+        let mut persist = false;
+        let mut idx = 1;
+
+        while idx < args.len() {
+            let option = args[idx].as_text().to_lowercase();
+
+            match option.as_str() {
+                "persist" if deadline.is_none() => persist = true,
+                "ex" | "px" | "exat" | "pxat" if deadline.is_none() && !persist => {
+                    deadline = Some(parse_deadline(&option, args.get(idx + 1), "getex")?);
+                    idx += 1;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+            idx += 1;
+        }
+
+        let key: &str = &args[0].as_text();
+        let reply = old_value(keyspace, key)?;
+
+        if persist {
+            keyspace.persist(key);
+        } else if let Some(at) = deadline {
+            if at <= unix_time_ms() {
+                keyspace.remove(key);
+            } else {
+                keyspace.set_expiry(key, at);
+            }
+        }
+
+        Ok(reply)
+    }
+
+    // Same as the default "proto-max-bulk-len" of Redis
+    const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+
+    // Shared by all of the integer counter commands. The value keeps its TTL, same as in Redis.
+    fn incr_generic(keyspace: &mut Keyspace, key: &str, delta: i64) -> Result {
+        let current = match keyspace.get_string(key)? {
+            Some(value) => match value.as_int() {
+                Some(n) => n,
+                None => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+            },
+            None => 0,
+        };
+
+        let new_value = match current.checked_add(delta) {
+            Some(n) => n,
+            None => return Err("[ERROR]: increment or decrement would overflow".to_string()),
+        };
+
+        keyspace.update_string(key, new_value.to_string().as_bytes())?;
+
+        Ok(Reply::Integer(new_value))
+    }
+
+    fn parse_increment(arg: &[u8]) -> core::result::Result<i64, String> {
+        arg.parse::<i64>()
+            .map_err(|_| "[ERROR]: value is not an integer or out of range".to_string())
+    }
+
+    pub fn incr(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/incr/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: incr requires only one argument!".to_string());
+        }
+
+        incr_generic(keyspace, &args[0].as_text(), 1)
+    }
+
+    pub fn decr(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/decr/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: decr requires only one argument!".to_string());
+        }
+
+        incr_generic(keyspace, &args[0].as_text(), -1)
+    }
+
+    pub fn incrby(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/incrby/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: incrby requires exactly two arguments!".to_string());
+        }
+
+        incr_generic(keyspace, &args[0].as_text(), parse_increment(args[1])?)
+    }
+
+    pub fn decrby(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/decrby/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: decrby requires exactly two arguments!".to_string());
+        }
+
+        let decrement = parse_increment(args[1])?;
+        if decrement == i64::MIN {
+            // can't be negated
+            return Err("[ERROR]: decrement would overflow".to_string());
+        }
+
+        incr_generic(keyspace, &args[0].as_text(), -decrement)
+    }
+
+    pub fn incrbyfloat(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/incrbyfloat/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: incrbyfloat requires exactly two arguments!".to_string());
+        }
+
+        let not_a_float = || "[ERROR]: value is not a valid float".to_string();
+        let parse_float = |bytes: &[u8]| match bytes.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err(not_a_float()),
+        };
+
+        let key: &str = &args[0].as_text();
+        let increment = parse_float(args[1])?;
+        let current = match keyspace.get_string(key)? {
+            Some(value) => parse_float(&value.to_bytes())?,
+            None => 0.0,
+        };
+
+        let new_value = current + increment;
+        if !new_value.is_finite() {
+            return Err("[ERROR]: increment would produce NaN or Infinity".to_string());
+        }
+
+        // Redis prints its long double result with 17 digits and trims the trailing zeroes, which
+        // hides the error of the addition, e.g. 10.1 + 0.2 gives 10.3. An f64 has no digits to spare,
+        // so it gets rounded to the 15 significant digits it holds exactly before being printed
+        // without an exponent and trailing zeroes, e.g. 10.3 or 5000
+        let rounded = format!("{:.14e}", new_value)
+            .parse::<f64>()
+            .ok()
+            .filter(|rounded| rounded.is_finite())
+            .unwrap_or(new_value);
+        let text = format!("{}", rounded).into_bytes();
+        keyspace.update_string(key, &text)?;
+
+        Ok(Reply::Bulk(text))
+    }
+
+    pub fn append(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/append/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: append requires exactly two arguments!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        // integers are appended to as their decimal string
+        let mut bytes = match keyspace.get_string(key)? {
+            Some(value) => value.to_bytes(),
+            None => vec![],
+        };
+
+        if bytes.len() + args[1].len() > MAX_STRING_LENGTH {
+            return Err("[ERROR]: string exceeds maximum allowed size (proto-max-bulk-len)".to_string());
+        }
+
+        bytes.extend_from_slice(args[1]);
+        keyspace.update_string(key, &bytes)?;
+
+        Ok(Reply::Integer(bytes.len() as i64))
+    }
+
+    pub fn strlen(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/strlen/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: strlen requires only one argument!".to_string());
+        }
+
+        match keyspace.get_string(&args[0].as_text())? {
+            Some(value) => Ok(Reply::Integer(value.to_bytes().len() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn getrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/getrange/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: getrange requires exactly three arguments!".to_string());
+        }
+
+        let (mut start, mut end) = match (args[1].parse::<i64>(), args[2].parse::<i64>()) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+        };
+
+        let bytes = match keyspace.get_string(&args[0].as_text())? {
+            Some(value) => value.to_bytes(),
+            None => vec![],
+        };
+        let len = bytes.len() as i64;
+
+        // negative offsets count from the end of the string, the range is inclusive
+        if start < 0 {
+            start += len;
+        }
+        if end < 0 {
+            end += len;
+        }
+        start = start.max(0);
+        end = end.max(0).min(len - 1);
+
+        if len == 0 || start > end {
+            return Ok(Reply::Bulk(vec![]));
+        }
+
+        Ok(Reply::Bulk(bytes[start as usize..=end as usize].to_vec()))
+    }
+
+    pub fn setrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/setrange/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: setrange requires exactly three arguments!".to_string());
+        }
+
+        let offset = match args[1].parse::<i64>() {
+            Ok(offset) if offset >= 0 => offset as usize,
+            Ok(_) => return Err("[ERROR]: offset is out of range".to_string()),
+            Err(_) => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+        };
+
+        let key: &str = &args[0].as_text();
+        let value = args[2];
+        let mut bytes = match keyspace.get_string(key)? {
+            Some(current) => current.to_bytes(),
+            // nothing to write, so the key doesn't get created either
+            None if value.is_empty() => return Ok(Reply::Integer(0)),
+            None => vec![],
+        };
+
+        if value.is_empty() {
+            return Ok(Reply::Integer(bytes.len() as i64));
+        }
+
+        if offset.saturating_add(value.len()) > MAX_STRING_LENGTH {
+            return Err("[ERROR]: string exceeds maximum allowed size (proto-max-bulk-len)".to_string());
+        }
+
+        // writing past the end pads the string with zero bytes
+        if bytes.len() < offset + value.len() {
+            bytes.resize(offset + value.len(), 0);
+        }
+        bytes[offset..offset + value.len()].copy_from_slice(value);
+        keyspace.update_string(key, &bytes)?;
+
+        Ok(Reply::Integer(bytes.len() as i64))
+    }
+
+    pub fn lcs(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lcs/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: lcs requires at least two arguments!".to_string());
+        }
+
+        let (mut get_len, mut get_idx, mut with_match_len) = (false, false, false);
+        let mut min_match_len = 0;
+        let mut idx = 2;
+
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "len" => get_len = true,
+                "idx" => get_idx = true,
+                "withmatchlen" => with_match_len = true,
+                "minmatchlen" if idx + 1 < args.len() => {
+                    min_match_len = match args[idx + 1].parse::<i64>() {
+                        Ok(n) => n.max(0) as usize,
+                        Err(_) => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+                    };
+                    idx += 1;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+            idx += 1;
+        }
+
+        if get_len && get_idx {
+            return Err("[ERROR]: If you want both the length and indexes, please just use IDX.".to_string());
+        }
+
+        // missing keys count as empty strings
+        let mut strings = vec![];
+        for key in &args[..2] {
+            match keyspace.get(&key.as_text()) {
+                Some(RedisObject::String(value)) => strings.push(value.to_bytes()),
+                Some(_) => return Err("[ERROR]: The specified keys must contain string values".to_string()),
+                None => strings.push(vec![]),
+            }
+        }
+        let (a, b) = (&strings[0], &strings[1]);
+
+        // the whole table is kept in memory, so refuse to build one that is too big
+        let width = b.len() + 1;
+        let cells = (a.len() + 1).saturating_mul(width);
+        if cells.saturating_mul(std::mem::size_of::<u32>()) > MAX_STRING_LENGTH {
+            return Err("[ERROR]: Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len".to_string());
+        }
+
+        // table[i * width + j] holds the length of the LCS of a[..i] and b[..j]
+        let mut table = vec![0_u32; cells];
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                table[i * width + j] = if a[i - 1] == b[j - 1] {
+                    table[(i - 1) * width + j - 1] + 1
+                } else {
+                    table[(i - 1) * width + j].max(table[i * width + j - 1])
+                };
+            }
+        }
+
+        let lcs_len = table[a.len() * width + b.len()] as usize;
+        if get_len {
+            return Ok(Reply::Integer(lcs_len as i64));
+        }
+
+        // walk the table back from the end, collecting the common subsequence and,
+        // if IDX was given, the ranges of the matches in both strings
+        let mut result = vec![0_u8; lcs_len];
+        let mut matches = vec![];
+        let (mut i, mut j, mut remaining) = (a.len(), b.len(), lcs_len);
+        let mut range: Option<(usize, usize, usize, usize)> = None;
+
+        while i > 0 && j > 0 {
+            let mut emit_range = false;
+
+            if a[i - 1] == b[j - 1] {
+                result[remaining - 1] = a[i - 1];
+                range = match range {
+                    None => Some((i - 1, i - 1, j - 1, j - 1)),
+                    // the match continues backwards
+                    Some((a_start, a_end, b_start, b_end)) if a_start == i && b_start == j => {
+                        Some((a_start - 1, a_end, b_start - 1, b_end))
+                    }
+                    other => {
+                        emit_range = true;
+                        other
+                    }
+                };
+
+                if let Some((0, _, _, _)) | Some((_, _, 0, _)) = range {
+                    emit_range = true;
+                }
+                remaining -= 1;
+                i -= 1;
+                j -= 1;
+            } else {
+                if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                    i -= 1;
+                } else {
+                    j -= 1;
+                }
+                emit_range = range.is_some();
+            }
+
+            if emit_range {
+                if let Some((a_start, a_end, b_start, b_end)) = range.take() {
+                    let match_len = a_end - a_start + 1;
+                    if match_len >= min_match_len {
+                        let mut entry = vec![
+                            Reply::Array(vec![Reply::Integer(a_start as i64), Reply::Integer(a_end as i64)]),
+                            Reply::Array(vec![Reply::Integer(b_start as i64), Reply::Integer(b_end as i64)]),
+                        ];
+                        if with_match_len {
+                            entry.push(Reply::Integer(match_len as i64));
+                        }
+                        matches.push(Reply::Array(entry));
+                    }
+                }
+            }
+        }
+
+        if get_idx {
+            return Ok(Reply::Map(vec![
+                (Reply::bulk("matches"), Reply::Array(matches)),
+                (Reply::bulk("len"), Reply::Integer(lcs_len as i64)),
+            ]));
+        }
+
+        Ok(Reply::Bulk(result))
+    }
+
+    // MSET and MSETNX take the keys and values interleaved
+    fn key_value_pairs<'a>(args: &[&'a [u8]], command: &str) -> core::result::Result<Vec<(String, &'a [u8])>, String> {
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(format!("[ERROR]: wrong number of arguments for {command}"));
+        }
+
+        Ok(args
+            .chunks(2)
+            .map(|pair| (pair[0].as_text().to_string(), pair[1]))
+            .collect())
+    }
+
+    pub fn mget(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/mget/
+
+        if args.is_empty() {
+            return Err("[ERROR]: mget requires at least one argument!".to_string());
+        }
+
+        // keys that don't hold a string are reported as missing instead of failing the whole command
+        let values = args
+            .iter()
+            .map(|key| match keyspace.get(&key.as_text()) {
+                Some(RedisObject::String(value)) => Reply::Bulk(value.to_bytes()),
+                _ => Reply::Nil,
+            })
+            .collect();
+
+        Ok(Reply::Array(values))
+    }
+
+    pub fn mset(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/mset/
+
+        for (key, value) in key_value_pairs(&args, "mset")? {
+            set_generic(keyspace, &key, value, None, false);
+        }
+
+        Ok(Reply::ok())
+    }
+
+    pub fn msetnx(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/msetnx/
+
+        // either all of the keys get set or none of them, if any of them exists already
+        let pairs = key_value_pairs(&args, "msetnx")?;
+        if pairs.iter().any(|(key, _)| keyspace.contains_key(key)) {
+            return Ok(Reply::Integer(0));
         }
+
+        for (key, value) in pairs {
+            set_generic(keyspace, &key, value, None, false);
+        }
+
+        Ok(Reply::Integer(1))
     }
 
-    pub fn key(kvps: &mut KVPHash, args: Vec<&str>) -> Result {
+    pub fn key(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         /*
          * There's no command "key" in Redis. I assume "key" will return "Ok" if a key exists and "Nok" if a key
          * does not exist.
@@ -59,35 +649,31 @@ pub mod kvp {
             return Err("[ERROR]: key requires only one argument!".to_string());
         }
 
-        if kvps.contains_key(args[0]) {
-            Ok("Ok".to_string())
+        if keyspace.contains_key(args[0].as_text().as_ref()) {
+            Ok(Reply::Status("Ok".to_string()))
         } else {
-            Ok("Nok".to_string())
+            Ok(Reply::Status("Nok".to_string()))
         }
     }
 
-    pub fn r#type(kvps: &mut KVPHash, args: Vec<&str>) -> Result {
+    pub fn r#type(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/type/
 
         if args.len() != 1 {
             return Err("[ERROR]: type requires only one argument!".to_string());
         }
 
-        return if kvps.contains_key(args[0]) {
-            let mut txt = "string".to_string();
-
-            if kvps[args[0]].is_a_number() {
-                txt = format!("(integer) {}", kvps[args[0]].int_value());
-            }
+        // integers are stored as strings in Redis as well
+        let name = match keyspace.get(args[0].as_text().as_ref()) {
+            Some(value) => value.type_name(),
+            None => "none",
+        };
 
-            Ok(txt)
-        } else {
-            Err("(nil)".to_string())
-        }
+        Ok(Reply::Status(name.to_string()))
     }
 
 
-    pub fn del(kvps: &mut KVPHash, args: Vec<&str>) -> Result {
+    pub fn del(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/del/
 
         if args.is_empty() {
@@ -97,15 +683,16 @@ pub mod kvp {
         let mut affected = 0;
 
         for k in args {
-            if kvps.contains_key(k) && kvps.remove(k).is_some() {
+            let k: &str = &k.as_text();
+            if keyspace.remove(k).is_some() {
                     affected+=1;
             }
         }
 
-        Ok(format!("{}", affected))
+        Ok(Reply::Integer(affected))
     }
 
-    pub fn unlink(kvps_arc: &std::sync::Arc<std::sync::Mutex<KVPHash>>, args: Vec<String>) -> Result {
+    pub fn unlink(keyspace_arc: &std::sync::Arc<std::sync::Mutex<Keyspace>>, args: Vec<String>) -> Result {
         // https://redis.io/commands/unlink/
 
         // This command is very similar to DEL: it removes the specified keys.
@@ -117,16 +704,15 @@ pub mod kvp {
         }
 
         let affected = std::sync::Arc::new(std::sync::Mutex::new(0));
-        let sub_arc = std::sync::Arc::clone(kvps_arc);
+        let sub_arc = std::sync::Arc::clone(keyspace_arc);
         let sub_aff = std::sync::Arc::clone(&affected);
 
         std::thread::spawn(move || {
-            let mut kvps = sub_arc.lock().unwrap();
+            let mut keyspace = sub_arc.lock().unwrap();
             let mut aff = sub_aff.lock().unwrap();
 
             for i in args {
-                if kvps.contains_key(&i) {
-                    kvps.remove(&i);
+                if keyspace.remove(&i).is_some() {
                     *aff += 1;
 
                     println!("Affected: {}", aff);
@@ -134,53 +720,164 @@ pub mod kvp {
             }
         }).join().unwrap();
 
-        Ok(format!("{:?}", affected.lock().unwrap()))
+        let affected = *affected.lock().unwrap();
+        Ok(Reply::Integer(affected))
+    }
+
+    // Shared by all of the expire commands, `base` and `unit` turn the given time into
+    // an absolute deadline in milliseconds.
+    fn expire_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, name: &str, base: u64, unit: i64) -> Result {
+        if args.len() < 2 {
+            return Err(format!("[ERROR]: {name} requires at least two arguments!"));
+        }
+
+        let time = match args[1].parse::<i64>() {
+            Ok(time) => time,
+            Err(_) => return Err(format!("[ERROR]: Expiry timeout must be a number! Got {}", args[1].as_text())),
+        };
+
+        let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+        for option in &args[2..] {
+            match option.as_text().to_lowercase().as_str() {
+                "nx" => nx = true,
+                "xx" => xx = true,
+                "gt" => gt = true,
+                "lt" => lt = true,
+                other => return Err(format!("[ERROR]: Unsupported option {other}")),
+            }
+        }
+
+        if nx && (xx || gt || lt) {
+            return Err("[ERROR]: NX and XX, GT or LT options at the same time are not compatible".to_string());
+        } else if gt && lt {
+            return Err("[ERROR]: GT and LT options at the same time are not compatible".to_string());
+        }
+
+        let deadline = match time.checked_mul(unit).and_then(|t| t.checked_add(base as i64)) {
+            Some(deadline) => deadline,
+            None => return Err(format!("[ERROR]: invalid expire time in '{name}' command")),
+        };
+
+        let key: &str = &args[0].as_text();
+        if !keyspace.contains_key(key) {
+            return Ok(Reply::Integer(0));
+        }
+
+        // keys without a TTL count as living forever when comparing
+        let current = keyspace.expiry(key);
+        let allowed = match current {
+            _ if nx => current.is_none(),
+            _ if xx && current.is_none() => false,
+            Some(at) if gt => deadline > at as i64,
+            None if gt => false,
+            Some(at) if lt => deadline < at as i64,
+            _ => true,
+        };
+
+        if !allowed {
+            return Ok(Reply::Integer(0));
+        }
+
+        if deadline <= unix_time_ms() as i64 {
+            // a deadline in the past deletes the key right away
+            keyspace.remove(key);
+        } else {
+            keyspace.set_expiry(key, deadline as u64);
+        }
+
+        Ok(Reply::Integer(1))
+    }
+
+    pub fn expire(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/expire/
+
+        // Set a timeout on key. After the timeout has expired, the key will automatically be deleted.
+        expire_generic(keyspace, args, "expire", unix_time_ms(), 1000)
+    }
+
+    pub fn pexpire(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pexpire/
+
+        expire_generic(keyspace, args, "pexpire", unix_time_ms(), 1)
+    }
+
+    pub fn expireat(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/expireat/
+
+        expire_generic(keyspace, args, "expireat", 0, 1000)
+    }
+
+    pub fn pexpireat(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pexpireat/
+
+        expire_generic(keyspace, args, "pexpireat", 0, 1)
+    }
+
+    // Shared by TTL, PTTL, EXPIRETIME and PEXPIRETIME. Gives -2 for missing keys,
+    // -1 for keys without a TTL, otherwise `convert` turns the deadline into the reply.
+    fn ttl_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, name: &str, convert: fn(u64) -> i64) -> Result {
+        if args.len() != 1 {
+            return Err(format!("[ERROR]: {name} requires only one argument!"));
+        }
+
+        let key: &str = &args[0].as_text();
+        if !keyspace.contains_key(key) {
+            return Ok(Reply::Integer(-2));
+        }
+
+        match keyspace.expiry(key) {
+            Some(at) => Ok(Reply::Integer(convert(at))),
+            None => Ok(Reply::Integer(-1)),
+        }
+    }
+
+    pub fn ttl(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/ttl/
+
+        // rounded the same way Redis does it
+        ttl_generic(keyspace, args, "ttl", |at| (at.saturating_sub(unix_time_ms()) as i64 + 500) / 1000)
     }
 
-     pub fn expire(_: &std::sync::Arc<std::sync::Mutex<KVPHash>>, args: Vec<String>) -> Result {
-         // https://redis.io/commands/expire/
+    pub fn pttl(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pttl/
 
-         // Set a timeout on key. After the timeout has expired, the key will automatically be deleted.
+        ttl_generic(keyspace, args, "pttl", |at| at.saturating_sub(unix_time_ms()) as i64)
+    }
 
-         if args.len() < 2 {
-             return Err("[ERROR]: expire requires at least two arguments!".to_string());
-         }
+    pub fn expiretime(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/expiretime/
 
-         let cl = args[1].clone();
-         if cl.parse::<u64>().is_err() {
-             return Err(format!("[ERROR]: Expiry timeout must be a number! Got {}", cl));
-         }
+        ttl_generic(keyspace, args, "expiretime", |at| (at / 1000) as i64)
+    }
 
-         crate::redis_engine::ScheduledExpiry::create(
-             std::time::SystemTime::now(),
-             cl.parse::<u64>().unwrap(),
-             args[0].clone()
-         );
+    pub fn pexpiretime(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/pexpiretime/
 
+        ttl_generic(keyspace, args, "pexpiretime", |at| at as i64)
+    }
 
-         // let kvps_arc = std::sync::Arc::clone(kvps);
-         // let cl = args[1].clone();
+    pub fn persist(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/persist/
 
-         // tokio::spawn(async move {
-         //     tokio::time::sleep(tokio::time::Duration::from_secs(cl.parse::<u64>().unwrap())).await;
-         //     self::del(&mut kvps_arc.lock().unwrap(), vec![args[0].as_str()]).unwrap();
-         // });
+        if args.len() != 1 {
+            return Err("[ERROR]: persist requires only one argument!".to_string());
+        }
 
-         Ok(format!("{}", 1))
-     }
+        Ok(Reply::Integer(keyspace.persist(&args[0].as_text()) as i64))
+    }
 
-    pub fn rename(kvps: &mut KVPHash, args: Vec<&str>) -> Result {
+    pub fn rename(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/rename/
 
         if args.len() < 2 {
             return Err("[ERROR]: rename requires at least two arguments!".to_string());
         }
 
-        let clone = kvps[args[0]].clone();
-        self::del(kvps, vec![args[0]]).unwrap();
-
-        //println!("\n\nClone: {clone:?}\n\n");
+        // the value is moved over as is along with its TTL, so this works for keys of any type
+        if !keyspace.rename(&args[0].as_text(), &args[1].as_text()) {
+            return Err("[ERROR]: no such key".to_string());
+        }
 
-        self::set_from_redis_value(kvps, args[1], clone)
+        Ok(Reply::ok())
     }
 }
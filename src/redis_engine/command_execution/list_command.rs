@@ -1,133 +1,114 @@
 #![allow(clippy::comparison_chain)]
 
 pub mod list {
-    use crate::redis_engine::RedisValue;
+    use crate::redis_engine::{CommandArg, Keyspace, RedisValue};
+    use std::collections::VecDeque;
+    use super::super::Reply;
     type Result = super::super::Result;
-    type List = std::collections::HashMap<String, Vec<RedisValue>>;
 
-    pub fn llen(list: &mut List, args: Vec<&str>) -> Result {
+    const NOT_AN_INTEGER: &str = "[ERROR]: value is not an integer or out of range";
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
+
+    pub fn llen(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/llen/
 
         if args.len() != 1 {
             return Err("[ERROR]: llen accepts only one argument".to_string());
         }
 
-        if !list.contains_key(args[0]) {
+        match keyspace.get_list(&args[0].as_text())? {
+            Some(list) => Ok(Reply::Integer(list.len() as i64)),
             // from docs:
             // If key does not exist, it is interpreted as an empty list and 0 is returned.
-            Ok("0".to_string())
-        } else {
-            Ok(list[args[0]].len().to_string())
+            None => Ok(Reply::Integer(0)),
         }
     }
 
-    pub fn lpush(list: &mut List, args: Vec<&str>) -> Result {
+    pub fn lpush(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/lpush/
 
         if args.len() < 2 {
             return Err("[ERROR]: At least two arguments required for lpush!".to_string());
         }
 
-        let mut vec = vec![];
+        let key: &str = &args[0].as_text();
+        let l = keyspace.get_or_create_list(key)?;
         for v in &args[1..] {
-            vec.push(RedisValue::from_str(v));
+            l.push_front(RedisValue::from_bytes(v));
         }
 
-        if !list.contains_key(args[0]) {
-            list.insert(args[0].to_string(), vec);
-        } else {
-            let mut deq = std::collections::VecDeque::from(list[args[0]].clone());
-            for v in &args[1..] {
-                deq.push_front(RedisValue::from_str(v));
-            }
-
-            let l = list.get_mut(args[0]).unwrap();
-            l.clear();
-            *l = Vec::from(deq);
-        }
-
-        Ok(format!("{}", args.len() - 1))
+        Ok(Reply::Integer(args.len() as i64 - 1))
     }
 
-    pub fn rpush(list: &mut List, args: Vec<&str>) -> Result {
+    pub fn rpush(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/rpush/
 
         if args.len() < 2 {
             return Err("[ERROR]: At least two arguments required for rpush!".to_string());
         }
 
-        let mut vec = vec![];
+        let key: &str = &args[0].as_text();
+        let l = keyspace.get_or_create_list(key)?;
         for v in &args[1..] {
-            vec.push(RedisValue::from_str(v));
-        }
-
-        if !list.contains_key(args[0]) {
-            list.insert(args[0].to_string(), vec);
-        } else {
-            for v in &args[1..] {
-                list.get_mut(args[0]).unwrap().push(RedisValue::from_str(v));
-            }
+            l.push_back(RedisValue::from_bytes(v));
         }
 
-        Ok(format!("{}", args.len() - 1))
+        Ok(Reply::Integer(args.len() as i64 - 1))
     }
 
-    pub fn lrem(list: &mut List, args: Vec<&str>) -> Result {
+    pub fn lrem(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/lrem/
 
         if args.len() < 3 {
             return Err("[ERROR]: At least three arguments required for lrem!".to_string());
         }
 
-        let name = args[0];
+        let name: &str = &args[0].as_text();
         let mut count = 0;
         let key = args[2];
 
         if args[1].parse::<i32>().is_err() {
-            return Err(format!("Second argument of lrem MUST be a whole number! Got {}", args[1]));
+            return Err(format!("Second argument of lrem MUST be a whole number! Got {}", args[1].as_text()));
         } else {
             count = args[1].parse::<i32>().unwrap();
         }
 
-        if !list.contains_key(name) {
+        let mutable_list = match keyspace.get_list_mut(name)? {
+            Some(list) => list,
             // Note that non-existing keys are treated like empty lists, so when key does not exist, the command will always return 0.
-            return Ok("0".to_string());
-        }
-
-        let old_len = list[name].len();
+            None => return Ok(Reply::Integer(0)),
+        };
 
-        let mutable_list = list.get_mut(name).unwrap();
-        let mut vec_cpy = vec![];
+        let old_len = mutable_list.len();
+        let mut vec_cpy = VecDeque::new();
 
         if count == 0 {
             mutable_list
                 .retain(|x| {
-                    x.to_string() != key
+                    x.to_bytes() != key
                 });
         } else if count < 0{
             let mut to_remove = -count;
 
             for item in mutable_list.iter().rev() {
-                if to_remove > 0  && item.to_string() == key {
+                if to_remove > 0  && item.to_bytes() == key {
                     to_remove-=1;
                     continue;
                 } else {
-                    vec_cpy.push(item.clone());
+                    // going backwards, so the kept items get prepended to stay in order
+                    vec_cpy.push_front(item.clone());
                 }
             }
 
             mutable_list.clear();
-            // doing this:
             *mutable_list = vec_cpy.clone();
-            // will save the list in reverse, so we need to reverse it back:
-            mutable_list.reverse();
         } else {
             for item in mutable_list.iter() {
-                if count > 0  && item.to_string() == key {
+                if count > 0  && item.to_bytes() == key {
                     count-=1;
                     continue;
                 } else {
-                    vec_cpy.push(item.clone());
+                    vec_cpy.push_back(item.clone());
                 }
             }
 
@@ -135,17 +116,20 @@ pub mod list {
             *mutable_list = vec_cpy.clone();
         }
 
-        Ok(format!("{}", old_len - list[name].len()))
+        let removed = old_len - mutable_list.len();
+        keyspace.remove_if_empty(name);
+
+        Ok(Reply::Integer(removed as i64))
     }
 
-    pub fn lindex(list: &mut List, args: Vec<&str>) -> Result {
+    pub fn lindex(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/lindex/
 
         if args.len() < 2 {
             return Err("[ERROR]: At least two arguments required for lindex!".to_string());
         }
 
-        let name = args[0];
+        let name: &str = &args[0].as_text();
         let mut index = 0;
 
         if args[1].parse::<i32>().is_err() {
@@ -154,100 +138,107 @@ pub mod list {
             index = args[1].parse::<i32>().unwrap();
         }
 
-        if !list.contains_key(name) {
-            return Ok("(nil)".to_string());
-        }
-
-        let vec = &list[name];
+        let vec = match keyspace.get_list(name)? {
+            Some(list) => list,
+            None => return Ok(Reply::Nil),
+        };
 
-        if index > vec.len().try_into().unwrap() {
-            return Ok("(nil)".to_string());
+        if index < 0 { // -1, -2, etc..
+            index += vec.len() as i32;
         }
 
-        if index < 0 { // -1, -2, etc..
-            index = (vec.len() as i32 + index) as i32;
+        if index < 0 || index >= vec.len() as i32 {
+            return Ok(Reply::Nil);
         }
 
-        Ok(format!("{:?}", vec[index as usize].to_string()))
+        Ok(Reply::Bulk(vec[index as usize].to_bytes()))
     }
 
 
-    pub fn lpop(list: &mut List, args: Vec<&str>) -> Result {
+    pub fn lpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/lpop/
 
         if args.is_empty() {
             return Err("[ERROR]: At least one argument required for lpop!".to_string());
         }
 
-        let v = list.get_mut(args[0]).unwrap();
-
-        if v.is_empty() {
-            return Err(format!("[ERROR]: List {} is empty!", args[0]));
-        }
+        let key: &str = &args[0].as_text();
+        let v = match keyspace.get_list_mut(key)? {
+            Some(list) => list,
+            // popping a count of elements replies with an array, even when there is nothing
+            None => return Ok(if args.len() > 1 { Reply::NilArray } else { Reply::Nil }),
+        };
 
         if args.len() > 1 {
             let mut count = 0;
 
-            if args[1].parse::<i32>().is_err() {
+            if args[1].parse::<usize>().is_err() {
                 return Err("[ERROR]: count value is not an integer. Usage: lpop LISTNAME [COUNT]".to_string());
             } else {
-                count = args[1].parse::<i32>().unwrap();
+                count = args[1].parse::<usize>().unwrap().min(v.len());
             }
 
             let mut r_v = vec![];
             for _ in 0..count {
-                v.rotate_left(1);
-                r_v.push(v.pop().unwrap().to_string());
+                r_v.push(Reply::Bulk(v.pop_front().unwrap().to_bytes()));
             }
 
-            return Ok(format!("{:?}", r_v));
+            keyspace.remove_if_empty(key);
+            return Ok(Reply::Array(r_v));
         }
 
-        v.rotate_left(1);
-        Ok(v.pop().unwrap().to_string())
+        let popped = v.pop_front().unwrap();
+        keyspace.remove_if_empty(key);
+
+        Ok(Reply::Bulk(popped.to_bytes()))
     }
 
-     pub fn rpop(list: &mut List, args: Vec<&str>) -> Result {
+     pub fn rpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
         // https://redis.io/commands/rpop/
 
         if args.is_empty() {
             return Err("[ERROR]: At least one argument required for rpop!".to_string());
         }
 
-        let v = list.get_mut(args[0]).unwrap();
-
-        if v.is_empty() {
-            return Err(format!("[ERROR]: List {} is empty!", args[0]));
-        }
+        let key: &str = &args[0].as_text();
+        let v = match keyspace.get_list_mut(key)? {
+            Some(list) => list,
+            // popping a count of elements replies with an array, even when there is nothing
+            None => return Ok(if args.len() > 1 { Reply::NilArray } else { Reply::Nil }),
+        };
 
         if args.len() > 1 {
             let mut count = 0;
 
-            if args[1].parse::<i32>().is_err() {
+            if args[1].parse::<usize>().is_err() {
                 return Err("[ERROR]: count value is not an integer. Usage: rpop LISTNAME [COUNT]".to_string());
             } else {
-                count = args[1].parse::<i32>().unwrap();
+                count = args[1].parse::<usize>().unwrap().min(v.len());
             }
 
             let mut r_v = vec![];
             for _ in 0..count {
-                r_v.push(v.pop().unwrap().to_string());
+                r_v.push(Reply::Bulk(v.pop_back().unwrap().to_bytes()));
             }
 
-            return Ok(format!("{:?}", r_v));
+            keyspace.remove_if_empty(key);
+            return Ok(Reply::Array(r_v));
         }
 
-        Ok(v.pop().unwrap().to_string())
+        let popped = v.pop_back().unwrap();
+        keyspace.remove_if_empty(key);
+
+        Ok(Reply::Bulk(popped.to_bytes()))
      }
 
-     pub fn lset(list: &mut List, args: Vec<&str>) -> Result {
+     pub fn lset(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
          // https://redis.io/commands/lset/
 
          if args.len() < 3 {
              return Err("[ERROR]: At least three arguments required for lset!".to_string());
          }
 
-         let key     =args[0];
+         let key: &str = &args[0].as_text();
          let mut index   = 0;
          let element = args[2];
 
@@ -257,22 +248,440 @@ pub mod list {
              index = args[1].parse::<i32>().unwrap();
          }
 
-         if !list.contains_key(key) {
-             return Err("[ERROR]: no such key".to_string());
-         }
-
-         let vec = list.get_mut(key).unwrap();
-
-         if index > vec.len().try_into().unwrap() {
-             return Err("[ERROR]: Index is out of list range".to_string());
-         }
+         let vec = match keyspace.get_list_mut(key)? {
+             Some(list) => list,
+             None => return Err("[ERROR]: no such key".to_string()),
+         };
 
          // clamp the index into the bounds of the vec
          if index < 0 { // -1, -2, etc..
-             index = (vec.len() as i32 + index) as i32;
+             index += vec.len() as i32;
          }
 
-         vec[index as usize] = RedisValue::from_str(element);
-         Ok("Ok".to_string())
+         if index < 0 || index >= vec.len() as i32 {
+             return Err("[ERROR]: Index is out of list range".to_string());
+         }
+
+         vec[index as usize] = RedisValue::from_bytes(element);
+         Ok(Reply::ok())
      }
+
+    // Turns LRANGE style indexes, which can be negative, into an inclusive range of the list.
+    // None when the range is empty.
+    fn resolve_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+        let len = len as i64;
+        let start = if start < 0 { (start + len).max(0) } else { start };
+        let stop = if stop < 0 { stop + len } else { stop.min(len - 1) };
+
+        if start > stop || start >= len {
+            return None;
+        }
+
+        Some((start as usize, stop as usize))
+    }
+
+    #[derive(Clone, Copy)]
+    enum End {
+        Left,
+        Right,
+    }
+
+    fn parse_end(arg: &[u8]) -> core::result::Result<End, String> {
+        match arg.as_text().to_lowercase().as_str() {
+            "left" => Ok(End::Left),
+            "right" => Ok(End::Right),
+            _ => Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    fn pop_end(list: &mut VecDeque<RedisValue>, end: End) -> Option<RedisValue> {
+        match end {
+            End::Left => list.pop_front(),
+            End::Right => list.pop_back(),
+        }
+    }
+
+    fn push_end(list: &mut VecDeque<RedisValue>, end: End, value: RedisValue) {
+        match end {
+            End::Left => list.push_front(value),
+            End::Right => list.push_back(value),
+        }
+    }
+
+    pub fn lrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lrange/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: Usage: lrange LISTNAME START STOP".to_string());
+        }
+
+        let (start, stop) = match (args[1].parse::<i64>(), args[2].parse::<i64>()) {
+            (Ok(start), Ok(stop)) => (start, stop),
+            _ => return Err(NOT_AN_INTEGER.to_string()),
+        };
+
+        let list = match keyspace.get_list(&args[0].as_text())? {
+            Some(list) => list,
+            None => return Ok(Reply::Array(vec![])),
+        };
+
+        let elements = match resolve_range(list.len(), start, stop) {
+            Some((start, stop)) => list.range(start..=stop).map(|v| Reply::Bulk(v.to_bytes())).collect(),
+            None => vec![],
+        };
+
+        Ok(Reply::Array(elements))
+    }
+
+    pub fn ltrim(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/ltrim/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: Usage: ltrim LISTNAME START STOP".to_string());
+        }
+
+        let (start, stop) = match (args[1].parse::<i64>(), args[2].parse::<i64>()) {
+            (Ok(start), Ok(stop)) => (start, stop),
+            _ => return Err(NOT_AN_INTEGER.to_string()),
+        };
+
+        let key: &str = &args[0].as_text();
+        let list = match keyspace.get_list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(Reply::ok()),
+        };
+
+        match resolve_range(list.len(), start, stop) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+
+        keyspace.remove_if_empty(key);
+        Ok(Reply::ok())
+    }
+
+    pub fn linsert(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/linsert/
+
+        if args.len() != 4 {
+            return Err("[ERROR]: Usage: linsert LISTNAME BEFORE|AFTER PIVOT ELEMENT".to_string());
+        }
+
+        let after = match args[1].as_text().to_lowercase().as_str() {
+            "before" => false,
+            "after" => true,
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        };
+
+        let list = match keyspace.get_list_mut(&args[0].as_text())? {
+            Some(list) => list,
+            // from docs:
+            // When key does not exist, it is considered an empty list and no operation is performed.
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        match list.iter().position(|v| v.to_bytes() == args[2]) {
+            Some(idx) => {
+                list.insert(if after { idx + 1 } else { idx }, RedisValue::from_bytes(args[3]));
+                Ok(Reply::Integer(list.len() as i64))
+            }
+            None => Ok(Reply::Integer(-1)),
+        }
+    }
+
+    pub fn lpos(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lpos/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for lpos!".to_string());
+        }
+
+        let mut rank: i64 = 1;
+        let mut count: Option<usize> = None;
+        let mut max_len: usize = 0;
+
+        let mut idx = 2;
+        while idx < args.len() {
+            // an unknown option is a syntax error, whatever comes after it
+            let option = args[idx].as_text().to_lowercase();
+            if !matches!(option.as_str(), "rank" | "count" | "maxlen") {
+                return Err(SYNTAX_ERROR.to_string());
+            }
+
+            let value = match args.get(idx + 1) {
+                Some(value) => value.parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())?,
+                None => return Err(SYNTAX_ERROR.to_string()),
+            };
+
+            match option.as_str() {
+                "rank" if value == 0 => {
+                    return Err("[ERROR]: RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list".to_string());
+                }
+                "rank" => rank = value,
+                "count" if value < 0 => return Err("[ERROR]: COUNT can't be negative".to_string()),
+                "count" => count = Some(value as usize),
+                "maxlen" if value < 0 => return Err("[ERROR]: MAXLEN can't be negative".to_string()),
+                "maxlen" => max_len = value as usize,
+                _ => unreachable!(),
+            }
+            idx += 2;
+        }
+
+        let positions: Vec<usize> = match keyspace.get_list(&args[0].as_text())? {
+            Some(list) => {
+                // a negative rank searches from the tail, skipping the first |rank| - 1 matches
+                let indexes: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+                    Box::new(0..list.len())
+                } else {
+                    Box::new((0..list.len()).rev())
+                };
+                let compared = if max_len == 0 { list.len() } else { max_len };
+                // COUNT 0 means all of the matches
+                let wanted = match count {
+                    Some(0) => usize::MAX,
+                    Some(count) => count,
+                    None => 1,
+                };
+
+                indexes
+                    .take(compared)
+                    .filter(|&i| *list[i].as_bytes() == *args[1])
+                    .skip(rank.unsigned_abs() as usize - 1)
+                    .take(wanted)
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        match count {
+            Some(_) => Ok(Reply::Array(positions.into_iter().map(|i| Reply::Integer(i as i64)).collect())),
+            None => Ok(positions.first().map_or(Reply::Nil, |&i| Reply::Integer(i as i64))),
+        }
+    }
+
+    // Pops an element from one end of `source` and pushes it to an end of `destination`.
+    // The destination is checked before anything is popped, so a WRONGTYPE leaves the source untouched.
+    fn move_element(keyspace: &mut Keyspace, source: &str, destination: &str, from: End, to: End) -> Result {
+        keyspace.get_list(destination)?;
+
+        let element = match keyspace.get_list_mut(source)? {
+            Some(list) => pop_end(list, from),
+            None => None,
+        };
+        keyspace.remove_if_empty(source);
+
+        match element {
+            Some(element) => {
+                let bytes = element.to_bytes();
+                push_end(keyspace.get_or_create_list(destination)?, to, element);
+                Ok(Reply::Bulk(bytes))
+            }
+            None => Ok(Reply::Nil),
+        }
+    }
+
+    pub fn lmove(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lmove/
+
+        if args.len() != 4 {
+            return Err("[ERROR]: Usage: lmove SOURCE DESTINATION LEFT|RIGHT LEFT|RIGHT".to_string());
+        }
+
+        let (from, to) = (parse_end(args[2])?, parse_end(args[3])?);
+        move_element(keyspace, &args[0].as_text(), &args[1].as_text(), from, to)
+    }
+
+    pub fn rpoplpush(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/rpoplpush/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: Usage: rpoplpush SOURCE DESTINATION".to_string());
+        }
+
+        move_element(keyspace, &args[0].as_text(), &args[1].as_text(), End::Right, End::Left)
+    }
+
+    pub fn lmpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lmpop/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for lmpop!".to_string());
+        }
+
+        let num_keys = match args[0].parse::<i64>() {
+            Ok(num_keys) if num_keys > 0 => num_keys as usize,
+            Ok(_) => return Err("[ERROR]: numkeys should be greater than 0".to_string()),
+            Err(_) => return Err(NOT_AN_INTEGER.to_string()),
+        };
+        if args.len() < num_keys + 2 {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let end = parse_end(args[num_keys + 1])?;
+        let count = match &args[num_keys + 2..] {
+            [] => 1,
+            [option, count] if option.as_text().eq_ignore_ascii_case("count") => match count.parse::<i64>() {
+                Ok(count) if count > 0 => count as usize,
+                _ => return Err("[ERROR]: count should be greater than 0".to_string()),
+            },
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        };
+
+        // pops from the first of the keys that holds a non-empty list
+        for key in &args[1..=num_keys] {
+            let key: &str = &key.as_text();
+            let list = match keyspace.get_list_mut(key)? {
+                Some(list) if !list.is_empty() => list,
+                _ => continue,
+            };
+
+            let elements = (0..count)
+                .map_while(|_| pop_end(list, end))
+                .map(|v| Reply::Bulk(v.to_bytes()))
+                .collect();
+            keyspace.remove_if_empty(key);
+
+            return Ok(Reply::Array(vec![Reply::bulk(key), Reply::Array(elements)]));
+        }
+
+        Ok(Reply::NilArray)
+    }
+
+    fn push_if_exists(keyspace: &mut Keyspace, args: Vec<&[u8]>, end: End) -> Result {
+        match keyspace.get_list_mut(&args[0].as_text())? {
+            Some(list) => {
+                for v in &args[1..] {
+                    push_end(list, end, RedisValue::from_bytes(v));
+                }
+
+                Ok(Reply::Integer(list.len() as i64))
+            }
+            // from docs:
+            // no operation will be performed when key does not yet exist.
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn lpushx(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/lpushx/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for lpushx!".to_string());
+        }
+
+        push_if_exists(keyspace, args, End::Left)
+    }
+
+    pub fn rpushx(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/rpushx/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for rpushx!".to_string());
+        }
+
+        push_if_exists(keyspace, args, End::Right)
+    }
+
+    // What a blocked client is waiting for
+    pub struct Blocking {
+        pub keys: Vec<String>,
+        // None blocks forever
+        pub timeout: Option<std::time::Duration>,
+    }
+
+    // Timeouts are in seconds, with 0 meaning forever
+    fn parse_timeout(arg: &[u8]) -> core::result::Result<Option<std::time::Duration>, String> {
+        match arg.parse::<f64>() {
+            Ok(timeout) if timeout < 0.0 => Err("[ERROR]: timeout is negative".to_string()),
+            Ok(0.0) => Ok(None),
+            Ok(timeout) if timeout.is_finite() => Ok(Some(std::time::Duration::from_secs_f64(timeout))),
+            _ => Err("[ERROR]: timeout is not a float or out of range".to_string()),
+        }
+    }
+
+    // The keys and the timeout of a blocking command, None for any other command or malformed arguments
+    pub fn blocking(command: &str, args: &[&[u8]]) -> Option<Blocking> {
+        let (keys, timeout) = match command {
+            "blpop" | "brpop" if args.len() >= 2 => (&args[..args.len() - 1], args[args.len() - 1]),
+            "blmove" if args.len() == 5 => (&args[..1], args[4]),
+            "blmpop" if args.len() >= 2 => {
+                let num_keys = args[1].parse::<usize>().ok()?;
+                (args.get(2..2_usize.checked_add(num_keys)?)?, args[0])
+            }
+            _ => return None,
+        };
+
+        Some(Blocking {
+            keys: keys.iter().map(|key| key.as_text().to_string()).collect(),
+            timeout: parse_timeout(timeout).ok()?,
+        })
+    }
+
+    // The blocking commands below only try once, just like their non-blocking versions.
+    // When there is nothing to pop they reply with a nil, and it's up to the server to park
+    // the client and run the command again once one of its keys gets a list.
+    fn blocking_pop(keyspace: &mut Keyspace, args: Vec<&[u8]>, end: End) -> Result {
+        parse_timeout(args[args.len() - 1])?;
+
+        // pops from the first of the keys that holds a non-empty list
+        for key in &args[..args.len() - 1] {
+            let key: &str = &key.as_text();
+            let element = match keyspace.get_list_mut(key)? {
+                Some(list) => pop_end(list, end),
+                None => None,
+            };
+
+            if let Some(element) = element {
+                keyspace.remove_if_empty(key);
+                return Ok(Reply::Array(vec![Reply::bulk(key), Reply::Bulk(element.to_bytes())]));
+            }
+        }
+
+        Ok(Reply::NilArray)
+    }
+
+    pub fn blpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/blpop/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for blpop!".to_string());
+        }
+
+        blocking_pop(keyspace, args, End::Left)
+    }
+
+    pub fn brpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/brpop/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for brpop!".to_string());
+        }
+
+        blocking_pop(keyspace, args, End::Right)
+    }
+
+    pub fn blmove(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/blmove/
+
+        if args.len() != 5 {
+            return Err("[ERROR]: Usage: blmove SOURCE DESTINATION LEFT|RIGHT LEFT|RIGHT TIMEOUT".to_string());
+        }
+
+        parse_timeout(args[4])?;
+        lmove(keyspace, args[..4].to_vec())
+    }
+
+    pub fn blmpop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/blmpop/
+
+        if args.len() < 4 {
+            return Err("[ERROR]: At least four arguments required for blmpop!".to_string());
+        }
+
+        parse_timeout(args[0])?;
+        lmpop(keyspace, args[1..].to_vec())
+    }
 }
@@ -1,6 +1,15 @@
-pub type Result = core::result::Result<String, String>;
+pub type Result = core::result::Result<Reply, String>;
+
+pub mod reply;
+pub use reply::Reply;
 
 pub mod one_off_command;
 pub mod kvp_command;
+pub mod hyperloglog_command;
 pub mod list_command;
 pub mod hash_command;
+pub mod bit_command;
+pub mod set_command;
+pub mod sorted_set_command;
+pub mod geo_command;
+pub mod stream_command;
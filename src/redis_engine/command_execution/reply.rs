@@ -0,0 +1,123 @@
+use crate::redis_engine::resp::{format_double, RespValue};
+
+// Structured result of a command. It gets rendered either as text, the way
+// redis-cli prints replies, or as RESP when sent over the wire.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Status(String),
+    Error(String),
+    Integer(i64),
+    // sent as a bulk string to RESP2 clients
+    Double(f64),
+    Bulk(Vec<u8>),
+    Nil,
+    // what array replies send when there is nothing, e.g. popping a count of elements from a missing list
+    NilArray,
+    Array(Vec<Reply>),
+    Map(Vec<(Reply, Reply)>),
+    // unordered and without duplicates, sent as a set to RESP3 clients
+    Set(Vec<Reply>),
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Status("OK".to_string())
+    }
+
+    pub fn bulk(value: &str) -> Self {
+        Reply::Bulk(value.as_bytes().to_vec())
+    }
+
+    pub fn from_result(result: super::Result) -> Self {
+        match result {
+            Ok(reply) => reply,
+            Err(e) => Reply::Error(e),
+        }
+    }
+
+    pub fn to_resp(&self) -> RespValue {
+        match self {
+            Reply::Status(s) => RespValue::SimpleString(s.clone()),
+            Reply::Error(e) => RespValue::error(e),
+            Reply::Integer(n) => RespValue::Integer(*n),
+            Reply::Double(d) => RespValue::Double(*d),
+            Reply::Bulk(bytes) => RespValue::BulkString(bytes.clone()),
+            Reply::Nil => RespValue::Nil,
+            Reply::NilArray => RespValue::NilArray,
+            Reply::Array(items) => RespValue::Array(items.iter().map(|x| x.to_resp()).collect()),
+            Reply::Set(items) => RespValue::Set(items.iter().map(|x| x.to_resp()).collect()),
+            Reply::Map(pairs) => RespValue::Map(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_resp(), v.to_resp()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+        match self {
+            Reply::Status(s) => write!(f, "{s}"),
+            Reply::Error(e) => write!(f, "(error) {e}"),
+            Reply::Integer(n) => write!(f, "(integer) {n}"),
+            Reply::Double(d) => write!(f, "(double) {}", format_double(*d)),
+            Reply::Bulk(bytes) => write!(f, "{}", quote(bytes)),
+            Reply::Nil | Reply::NilArray => write!(f, "(nil)"),
+            Reply::Array(items) | Reply::Set(items) if items.is_empty() => write!(f, "(empty array)"),
+            Reply::Map(pairs) if pairs.is_empty() => write!(f, "(empty hash)"),
+            Reply::Array(items) | Reply::Set(items) => {
+                let width = items.len().to_string().len();
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "\n{:indent$}", "")?;
+                    }
+                    write!(f, "{:>width$}) ", i + 1)?;
+                    item.fmt_indented(f, indent + width + 2)?;
+                }
+
+                Ok(())
+            }
+            Reply::Map(pairs) => {
+                let width = pairs.len().to_string().len();
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "\n{:indent$}", "")?;
+                    }
+                    write!(f, "{:>width$}# ", i + 1)?;
+                    k.fmt_indented(f, indent + width + 2)?;
+                    write!(f, " => ")?;
+                    v.fmt_indented(f, indent + width + 2)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+// Quotes binary data the same way redis-cli does, escaping anything that isn't printable
+fn quote(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'\\' => text.push_str("\\\\"),
+            b'"' => text.push_str("\\\""),
+            b'\n' => text.push_str("\\n"),
+            b'\r' => text.push_str("\\r"),
+            b'\t' => text.push_str("\\t"),
+            0x07 => text.push_str("\\a"),
+            0x08 => text.push_str("\\b"),
+            b if b.is_ascii_graphic() || b == b' ' => text.push(b as char),
+            b => text.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    text.push('"');
+
+    text
+}
@@ -0,0 +1,364 @@
+pub mod set {
+    use crate::redis_engine::{CommandArg, Keyspace, RedisObject};
+    use super::super::Reply;
+    use std::collections::HashSet;
+    type Result = super::super::Result;
+
+    fn members_reply<'a>(members: impl Iterator<Item = &'a Vec<u8>>) -> Reply {
+        Reply::Set(members.map(|m| Reply::Bulk(m.clone())).collect())
+    }
+
+    // Picks a random member, the set must not be empty
+    fn random_member(keyspace: &mut Keyspace, key: &str) -> Vec<u8> {
+        let random = keyspace.next_random() as usize;
+        let set = keyspace.get_set(key).unwrap().unwrap();
+
+        set.iter().nth(random % set.len()).unwrap().clone()
+    }
+
+    // Picks up to `count` different random members of an existing set. The set is only walked once,
+    // the members get picked with a partial Fisher-Yates shuffle of the references to them.
+    fn random_members(keyspace: &mut Keyspace, key: &str, count: usize) -> Vec<Vec<u8>> {
+        let count = count.min(keyspace.get_set(key).unwrap().unwrap().len());
+        let randoms: Vec<usize> = (0..count).map(|_| keyspace.next_random() as usize).collect();
+        let mut members: Vec<&Vec<u8>> = keyspace.get_set(key).unwrap().unwrap().iter().collect();
+
+        for (i, random) in randoms.into_iter().enumerate() {
+            let j = i + random % (members.len() - i);
+            members.swap(i, j);
+        }
+
+        members[..count].iter().map(|member| member.to_vec()).collect()
+    }
+
+    fn parse_count(arg: &[u8]) -> core::result::Result<i64, String> {
+        arg.parse::<i64>()
+            .map_err(|_| "[ERROR]: value is not an integer or out of range".to_string())
+    }
+
+    pub fn sadd(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sadd/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for sadd!".to_string());
+        }
+
+        let set = keyspace.get_or_create_set(&args[0].as_text())?;
+        let added = args[1..].iter().filter(|m| set.insert(m.to_vec())).count();
+
+        Ok(Reply::Integer(added as i64))
+    }
+
+    pub fn srem(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/srem/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for srem!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let set = match keyspace.get_set_mut(key)? {
+            Some(set) => set,
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        let removed = args[1..].iter().filter(|m| set.remove(**m)).count();
+        keyspace.remove_if_empty(key);
+
+        Ok(Reply::Integer(removed as i64))
+    }
+
+    pub fn smembers(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/smembers/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: smembers requires only one argument!".to_string());
+        }
+
+        match keyspace.get_set(&args[0].as_text())? {
+            Some(set) => Ok(members_reply(set.iter())),
+            None => Ok(Reply::Set(vec![])),
+        }
+    }
+
+    pub fn sismember(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sismember/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: sismember requires exactly two arguments!".to_string());
+        }
+
+        match keyspace.get_set(&args[0].as_text())? {
+            Some(set) if set.contains(args[1]) => Ok(Reply::Integer(1)),
+            _ => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn smismember(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/smismember/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for smismember!".to_string());
+        }
+
+        let set = keyspace.get_set(&args[0].as_text())?;
+        let replies = args[1..]
+            .iter()
+            .map(|m| Reply::Integer(set.is_some_and(|set| set.contains(*m)) as i64))
+            .collect();
+
+        Ok(Reply::Array(replies))
+    }
+
+    pub fn scard(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/scard/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: scard requires only one argument!".to_string());
+        }
+
+        match keyspace.get_set(&args[0].as_text())? {
+            Some(set) => Ok(Reply::Integer(set.len() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn spop(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/spop/
+
+        if args.is_empty() || args.len() > 2 {
+            return Err("[ERROR]: Usage: spop KEY [COUNT]".to_string());
+        }
+
+        let count = match args.get(1) {
+            Some(count) => match parse_count(count)? {
+                count if count < 0 => return Err("[ERROR]: value is out of range, must be positive".to_string()),
+                count => Some(count as usize),
+            },
+            None => None,
+        };
+
+        let key: &str = &args[0].as_text();
+        match keyspace.get_set(key)? {
+            Some(_) => {}
+            None if count.is_some() => return Ok(Reply::Set(vec![])),
+            None => return Ok(Reply::Nil),
+        }
+
+        let mut popped = random_members(keyspace, key, count.unwrap_or(1));
+        let set = keyspace.get_set_mut(key)?.unwrap();
+        for member in &popped {
+            set.remove(member);
+        }
+        keyspace.remove_if_empty(key);
+
+        match count {
+            Some(_) => Ok(members_reply(popped.iter())),
+            None => Ok(Reply::Bulk(popped.pop().unwrap())),
+        }
+    }
+
+    pub fn srandmember(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/srandmember/
+
+        if args.is_empty() || args.len() > 2 {
+            return Err("[ERROR]: Usage: srandmember KEY [COUNT]".to_string());
+        }
+
+        let count = match args.get(1) {
+            Some(count) => Some(parse_count(count)?),
+            None => None,
+        };
+
+        let key: &str = &args[0].as_text();
+        match keyspace.get_set(key)? {
+            Some(_) => {}
+            None if count.is_some() => return Ok(Reply::Array(vec![])),
+            None => return Ok(Reply::Nil),
+        }
+
+        let count = match count {
+            Some(count) => count,
+            None => return Ok(Reply::Bulk(random_member(keyspace, key))),
+        };
+
+        if count >= 0 {
+            let picked = random_members(keyspace, key, count as usize);
+            return Ok(Reply::Array(picked.into_iter().map(Reply::Bulk).collect()));
+        }
+
+        // a negative count may return the same member more than once
+        let randoms: Vec<usize> = (0..count.unsigned_abs()).map(|_| keyspace.next_random() as usize).collect();
+        let members: Vec<&Vec<u8>> = keyspace.get_set(key)?.unwrap().iter().collect();
+        let picked = randoms
+            .into_iter()
+            .map(|random| Reply::Bulk(members[random % members.len()].clone()))
+            .collect();
+
+        Ok(Reply::Array(picked))
+    }
+
+    pub fn smove(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/smove/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: smove requires exactly three arguments!".to_string());
+        }
+
+        let source: &str = &args[0].as_text();
+        let destination: &str = &args[1].as_text();
+        let member = args[2];
+
+        // both keys have to be sets, even if nothing gets moved
+        keyspace.get_set(destination)?;
+        let present = match keyspace.get_set(source)? {
+            Some(set) => set.contains(member),
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        if !present {
+            return Ok(Reply::Integer(0));
+        }
+        if source == destination {
+            return Ok(Reply::Integer(1));
+        }
+
+        keyspace.get_set_mut(source)?.unwrap().remove(member);
+        keyspace.remove_if_empty(source);
+        keyspace.get_or_create_set(destination)?.insert(member.to_vec());
+
+        Ok(Reply::Integer(1))
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Algebra {
+        Inter,
+        Union,
+        Diff,
+    }
+
+    // Runs the operation over the sets stored at `keys`, missing keys count as empty sets
+    fn combine(keyspace: &Keyspace, keys: &[&[u8]], operation: Algebra) -> core::result::Result<HashSet<Vec<u8>>, String> {
+        let empty = HashSet::new();
+        let mut sets = vec![];
+        for key in keys {
+            sets.push(keyspace.get_set(&key.as_text())?.unwrap_or(&empty));
+        }
+
+        let mut result = sets[0].clone();
+        for set in &sets[1..] {
+            match operation {
+                Algebra::Inter => result.retain(|m| set.contains(m)),
+                Algebra::Union => result.extend(set.iter().cloned()),
+                Algebra::Diff => result.retain(|m| !set.contains(m)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn algebra(keyspace: &mut Keyspace, args: Vec<&[u8]>, operation: Algebra, name: &str) -> Result {
+        if args.is_empty() {
+            return Err(format!("[ERROR]: At least one argument required for {name}!"));
+        }
+
+        let result = combine(keyspace, &args, operation)?;
+
+        Ok(members_reply(result.iter()))
+    }
+
+    fn algebra_store(keyspace: &mut Keyspace, args: Vec<&[u8]>, operation: Algebra, name: &str) -> Result {
+        if args.len() < 2 {
+            return Err(format!("[ERROR]: At least two arguments required for {name}!"));
+        }
+
+        let result = combine(keyspace, &args[1..], operation)?;
+        let len = result.len();
+
+        // the destination is overwritten no matter its type, an empty result deletes it
+        let destination: &str = &args[0].as_text();
+        if result.is_empty() {
+            keyspace.remove(destination);
+        } else {
+            keyspace.insert(destination.to_string(), RedisObject::Set(result));
+        }
+
+        Ok(Reply::Integer(len as i64))
+    }
+
+    pub fn sinter(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sinter/
+
+        algebra(keyspace, args, Algebra::Inter, "sinter")
+    }
+
+    pub fn sunion(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sunion/
+
+        algebra(keyspace, args, Algebra::Union, "sunion")
+    }
+
+    pub fn sdiff(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sdiff/
+
+        algebra(keyspace, args, Algebra::Diff, "sdiff")
+    }
+
+    pub fn sinterstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sinterstore/
+
+        algebra_store(keyspace, args, Algebra::Inter, "sinterstore")
+    }
+
+    pub fn sunionstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sunionstore/
+
+        algebra_store(keyspace, args, Algebra::Union, "sunionstore")
+    }
+
+    pub fn sdiffstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sdiffstore/
+
+        algebra_store(keyspace, args, Algebra::Diff, "sdiffstore")
+    }
+
+    pub fn sintercard(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/sintercard/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for sintercard!".to_string());
+        }
+
+        let numkeys = match args[0].parse::<i64>() {
+            Ok(n) if n > 0 => n as usize,
+            Ok(_) => return Err("[ERROR]: numkeys should be greater than 0".to_string()),
+            Err(_) => return Err("[ERROR]: numkeys should be greater than 0".to_string()),
+        };
+        if numkeys > args.len() - 1 {
+            return Err("[ERROR]: Number of keys can't be greater than number of args".to_string());
+        }
+
+        let mut limit = 0;
+        let options = &args[1 + numkeys..];
+        match options {
+            [] => {}
+            [option, value] if option.as_text().eq_ignore_ascii_case("limit") => {
+                limit = match value.parse::<i64>() {
+                    Ok(n) if n >= 0 => n as usize,
+                    Ok(_) => return Err("[ERROR]: LIMIT can't be negative".to_string()),
+                    Err(_) => return Err("[ERROR]: value is not an integer or out of range".to_string()),
+                };
+            }
+            _ => return Err("[ERROR]: syntax error".to_string()),
+        }
+
+        let cardinality = combine(keyspace, &args[1..1 + numkeys], Algebra::Inter)?.len();
+
+        // a limit of 0 means there is no limit
+        if limit > 0 {
+            return Ok(Reply::Integer(cardinality.min(limit) as i64));
+        }
+
+        Ok(Reply::Integer(cardinality as i64))
+    }
+}
@@ -0,0 +1,609 @@
+pub mod sorted_set {
+    use crate::redis_engine::{CommandArg, Keyspace, RedisObject, SortedSet};
+    use super::super::Reply;
+    use std::collections::HashMap;
+    use std::ops::Bound;
+    type Result = super::super::Result;
+
+    const NOT_AN_INTEGER: &str = "[ERROR]: value is not an integer or out of range";
+    const NOT_A_FLOAT: &str = "[ERROR]: value is not a valid float";
+    const NAN_SCORE: &str = "[ERROR]: resulting score is not a number (NaN)";
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
+
+    type ScoredMembers = Vec<(Vec<u8>, f64)>;
+
+    fn parse_float(arg: &[u8], error: &str) -> core::result::Result<f64, String> {
+        match arg.parse::<f64>() {
+            Ok(value) if !value.is_nan() => Ok(value),
+            _ => Err(error.to_string()),
+        }
+    }
+
+    // "(1.5" excludes the score itself, anything else includes it
+    fn parse_score_bound(arg: &[u8]) -> core::result::Result<Bound<f64>, String> {
+        const ERROR: &str = "[ERROR]: min or max is not a float";
+
+        match arg.strip_prefix(b"(") {
+            Some(score) => Ok(Bound::Excluded(parse_float(score, ERROR)?)),
+            None => Ok(Bound::Included(parse_float(arg, ERROR)?)),
+        }
+    }
+
+    // "-" and "+" stand for the smallest and the largest possible member
+    #[derive(Clone, Copy)]
+    enum LexBound<'a> {
+        Min,
+        Max,
+        Included(&'a [u8]),
+        Excluded(&'a [u8]),
+    }
+
+    impl LexBound<'_> {
+        fn parse(arg: &[u8]) -> core::result::Result<LexBound<'_>, String> {
+            match arg {
+                b"-" => Ok(LexBound::Min),
+                b"+" => Ok(LexBound::Max),
+                [b'[', member @ ..] => Ok(LexBound::Included(member)),
+                [b'(', member @ ..] => Ok(LexBound::Excluded(member)),
+                _ => Err("[ERROR]: min or max not valid string range item".to_string()),
+            }
+        }
+
+        fn is_below(&self, member: &[u8]) -> bool {
+            match self {
+                LexBound::Min => true,
+                LexBound::Max => false,
+                LexBound::Included(min) => member >= *min,
+                LexBound::Excluded(min) => member > *min,
+            }
+        }
+
+        fn is_above(&self, member: &[u8]) -> bool {
+            match self {
+                LexBound::Min => false,
+                LexBound::Max => true,
+                LexBound::Included(max) => member <= *max,
+                LexBound::Excluded(max) => member < *max,
+            }
+        }
+    }
+
+    // Members followed by their scores when asked for, like WITHSCORES does
+    fn scored_reply(members: ScoredMembers, with_scores: bool) -> Reply {
+        let mut replies = vec![];
+        for (member, score) in members {
+            replies.push(Reply::Bulk(member));
+            if with_scores {
+                replies.push(Reply::Double(score));
+            }
+        }
+
+        Reply::Array(replies)
+    }
+
+    pub fn zadd(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zadd/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for zadd!".to_string());
+        }
+
+        let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) = (false, false, false, false, false, false);
+        let mut idx = 1;
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "nx" => nx = true,
+                "xx" => xx = true,
+                "gt" => gt = true,
+                "lt" => lt = true,
+                "ch" => ch = true,
+                "incr" => incr = true,
+                _ => break,
+            }
+            idx += 1;
+        }
+
+        let pairs = &args[idx..];
+        if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+        if nx && xx {
+            return Err("[ERROR]: XX and NX options at the same time are not compatible".to_string());
+        }
+        if (gt && lt) || (nx && (gt || lt)) {
+            return Err("[ERROR]: GT, LT, and/or NX options at the same time are not compatible".to_string());
+        }
+        if incr && pairs.len() > 2 {
+            return Err("[ERROR]: INCR option supports a single increment-element pair".to_string());
+        }
+
+        let mut scored = vec![];
+        for pair in pairs.chunks(2) {
+            scored.push((parse_float(pair[0], NOT_A_FLOAT)?, pair[1]));
+        }
+
+        let key: &str = &args[0].as_text();
+        // XX only ever updates members, so it never creates the key either
+        if xx && keyspace.get_zset(key)?.is_none() {
+            return Ok(if incr { Reply::Nil } else { Reply::Integer(0) });
+        }
+
+        let zset = keyspace.get_or_create_zset(key)?;
+        let (mut added, mut changed) = (0, 0);
+        let mut last_score = None;
+        let mut result = Ok(());
+        for (score, member) in scored {
+            let current = zset.score(member);
+            let score = match current {
+                Some(old) if incr => old + score,
+                _ => score,
+            };
+            if score.is_nan() {
+                result = Err(NAN_SCORE.to_string());
+                break;
+            }
+
+            match current {
+                Some(_) if nx => continue,
+                None if xx => continue,
+                Some(old) if (gt && score <= old) || (lt && score >= old) => continue,
+                Some(old) => {
+                    if old != score {
+                        zset.insert(member.to_vec(), score);
+                        changed += 1;
+                    }
+                }
+                None => {
+                    zset.insert(member.to_vec(), score);
+                    added += 1;
+                }
+            }
+            last_score = Some(score);
+        }
+
+        // the key might have been created just now, without any member making it in
+        keyspace.remove_if_empty(key);
+        result?;
+
+        if incr {
+            return Ok(last_score.map_or(Reply::Nil, Reply::Double));
+        }
+
+        Ok(Reply::Integer(if ch { added + changed } else { added }))
+    }
+
+    pub fn zrem(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zrem/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for zrem!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let zset = match keyspace.get_zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        let removed = args[1..].iter().filter(|m| zset.remove(m)).count();
+        keyspace.remove_if_empty(key);
+
+        Ok(Reply::Integer(removed as i64))
+    }
+
+    pub fn zscore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zscore/
+
+        if args.len() != 2 {
+            return Err("[ERROR]: zscore requires exactly two arguments!".to_string());
+        }
+
+        match keyspace.get_zset(&args[0].as_text())?.and_then(|zset| zset.score(args[1])) {
+            Some(score) => Ok(Reply::Double(score)),
+            None => Ok(Reply::Nil),
+        }
+    }
+
+    pub fn zincrby(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zincrby/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: zincrby requires exactly three arguments!".to_string());
+        }
+
+        let increment = parse_float(args[1], NOT_A_FLOAT)?;
+        let key: &str = &args[0].as_text();
+        let member = args[2];
+
+        if let Some(zset) = keyspace.get_zset(key)? {
+            if zset.score(member).is_some_and(|score| (score + increment).is_nan()) {
+                return Err(NAN_SCORE.to_string());
+            }
+        }
+
+        let zset = keyspace.get_or_create_zset(key)?;
+        let score = zset.score(member).unwrap_or(0.0) + increment;
+        zset.insert(member.to_vec(), score);
+
+        Ok(Reply::Double(score))
+    }
+
+    pub fn zcard(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zcard/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: zcard requires only one argument!".to_string());
+        }
+
+        match keyspace.get_zset(&args[0].as_text())? {
+            Some(zset) => Ok(Reply::Integer(zset.len() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn zcount(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zcount/
+
+        if args.len() != 3 {
+            return Err("[ERROR]: zcount requires exactly three arguments!".to_string());
+        }
+
+        let min = parse_score_bound(args[1])?;
+        let max = parse_score_bound(args[2])?;
+
+        match keyspace.get_zset(&args[0].as_text())? {
+            Some(zset) => Ok(Reply::Integer(zset.range_by_score(min, max).len() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    fn rank_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, reverse: bool, name: &str) -> Result {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(format!("[ERROR]: Usage: {name} KEY MEMBER [WITHSCORE]"));
+        }
+
+        let with_score = match args.get(2) {
+            Some(option) if option.as_text().eq_ignore_ascii_case("withscore") => true,
+            Some(_) => return Err(SYNTAX_ERROR.to_string()),
+            None => false,
+        };
+
+        let zset = match keyspace.get_zset(&args[0].as_text())? {
+            Some(zset) => zset,
+            None => return Ok(Reply::Nil),
+        };
+        let (rank, score) = match (zset.rank(args[1]), zset.score(args[1])) {
+            (Some(rank), Some(score)) => (rank, score),
+            _ => return Ok(Reply::Nil),
+        };
+
+        let rank = if reverse { zset.len() - 1 - rank } else { rank } as i64;
+        if with_score {
+            return Ok(Reply::Array(vec![Reply::Integer(rank), Reply::Double(score)]));
+        }
+
+        Ok(Reply::Integer(rank))
+    }
+
+    pub fn zrank(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zrank/
+
+        rank_generic(keyspace, args, false, "zrank")
+    }
+
+    pub fn zrevrank(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zrevrank/
+
+        rank_generic(keyspace, args, true, "zrevrank")
+    }
+
+    #[derive(PartialEq)]
+    enum RangeBy {
+        Rank,
+        Score,
+        Lex,
+    }
+
+    // Shared by ZRANGE and ZRANGESTORE, `args` are KEY START STOP followed by the options.
+    // Returns the members in the requested order and whether WITHSCORES was given.
+    fn range_generic(
+        keyspace: &Keyspace,
+        args: &[&[u8]],
+        allow_with_scores: bool,
+    ) -> core::result::Result<(ScoredMembers, bool), String> {
+        let mut by = RangeBy::Rank;
+        let mut reverse = false;
+        let mut limit = None;
+        let mut with_scores = false;
+
+        let mut idx = 3;
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "byscore" if by == RangeBy::Rank => by = RangeBy::Score,
+                "bylex" if by == RangeBy::Rank => by = RangeBy::Lex,
+                "rev" => reverse = true,
+                "withscores" if allow_with_scores => with_scores = true,
+                "limit" if idx + 2 < args.len() => {
+                    let offset = args[idx + 1].parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())?;
+                    let count = args[idx + 2].parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())?;
+                    limit = Some((offset, count));
+                    idx += 2;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+            idx += 1;
+        }
+
+        if limit.is_some() && by == RangeBy::Rank {
+            return Err("[ERROR]: syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX".to_string());
+        }
+        if with_scores && by == RangeBy::Lex {
+            return Err("[ERROR]: syntax error, WITHSCORES not supported in combination with BYLEX".to_string());
+        }
+
+        // REV swaps the bounds, the higher one comes first
+        let (start, stop) = (args[1], args[2]);
+        let (min, max) = if reverse && by != RangeBy::Rank { (stop, start) } else { (start, stop) };
+
+        let empty = SortedSet::new();
+        let zset = keyspace.get_zset(&args[0].as_text())?.unwrap_or(&empty);
+
+        let mut members: Vec<(&[u8], f64)> = match by {
+            RangeBy::Rank => {
+                let start = start.parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())?;
+                let stop = stop.parse::<i64>().map_err(|_| NOT_AN_INTEGER.to_string())?;
+                let len = zset.len() as i64;
+
+                let start = if start < 0 { (start + len).max(0) } else { start };
+                let stop = if stop < 0 { stop + len } else { stop.min(len - 1) };
+                if start > stop || start >= len {
+                    return Ok((vec![], with_scores));
+                }
+
+                let members: Box<dyn Iterator<Item = (&[u8], f64)>> =
+                    if reverse { Box::new(zset.iter().rev()) } else { Box::new(zset.iter()) };
+                members.skip(start as usize).take((stop - start + 1) as usize).collect()
+            }
+            RangeBy::Score => {
+                let mut members = zset.range_by_score(parse_score_bound(min)?, parse_score_bound(max)?);
+                if reverse {
+                    members.reverse();
+                }
+                members
+            }
+            RangeBy::Lex => {
+                let (min, max) = (LexBound::parse(min)?, LexBound::parse(max)?);
+                if reverse {
+                    zset.iter()
+                        .rev()
+                        .skip_while(|(member, _)| !max.is_above(member))
+                        .take_while(|(member, _)| min.is_below(member))
+                        .collect()
+                } else {
+                    zset.iter()
+                        .skip_while(|(member, _)| !min.is_below(member))
+                        .take_while(|(member, _)| max.is_above(member))
+                        .collect()
+                }
+            }
+        };
+
+        // a negative offset gives back nothing, a negative count everything after the offset
+        if let Some((offset, count)) = limit {
+            if offset < 0 {
+                members.clear();
+            } else {
+                let count = if count < 0 { usize::MAX } else { count as usize };
+                members = members.into_iter().skip(offset as usize).take(count).collect();
+            }
+        }
+
+        Ok((members.into_iter().map(|(m, score)| (m.to_vec(), score)).collect(), with_scores))
+    }
+
+    pub fn zrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zrange/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for zrange!".to_string());
+        }
+
+        let (members, with_scores) = range_generic(keyspace, &args, true)?;
+
+        Ok(scored_reply(members, with_scores))
+    }
+
+    // Overwrites `destination` no matter its type, an empty sorted set deletes it instead
+    fn store(keyspace: &mut Keyspace, destination: &str, zset: SortedSet) -> Result {
+        let len = zset.len();
+        if zset.is_empty() {
+            keyspace.remove(destination);
+        } else {
+            keyspace.insert(destination.to_string(), RedisObject::SortedSet(zset));
+        }
+
+        Ok(Reply::Integer(len as i64))
+    }
+
+    pub fn zrangestore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zrangestore/
+
+        if args.len() < 4 {
+            return Err("[ERROR]: At least four arguments required for zrangestore!".to_string());
+        }
+
+        let (members, _) = range_generic(keyspace, &args[1..], false)?;
+        let mut zset = SortedSet::new();
+        for (member, score) in members {
+            zset.insert(member, score);
+        }
+
+        store(keyspace, &args[0].as_text(), zset)
+    }
+
+    fn pop_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, max: bool, name: &str) -> Result {
+        if args.is_empty() || args.len() > 2 {
+            return Err(format!("[ERROR]: Usage: {name} KEY [COUNT]"));
+        }
+
+        let count = match args.get(1) {
+            Some(count) => match count.parse::<i64>() {
+                Ok(count) if count < 0 => return Err("[ERROR]: value is out of range, must be positive".to_string()),
+                Ok(count) => count as usize,
+                Err(_) => return Err(NOT_AN_INTEGER.to_string()),
+            },
+            None => 1,
+        };
+
+        let key: &str = &args[0].as_text();
+        let zset = match keyspace.get_zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(Reply::Array(vec![])),
+        };
+
+        let mut popped = vec![];
+        for _ in 0..count {
+            match if max { zset.pop_last() } else { zset.pop_first() } {
+                Some(entry) => popped.push(entry),
+                None => break,
+            }
+        }
+        keyspace.remove_if_empty(key);
+
+        Ok(scored_reply(popped, true))
+    }
+
+    pub fn zpopmin(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zpopmin/
+
+        pop_generic(keyspace, args, false, "zpopmin")
+    }
+
+    pub fn zpopmax(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zpopmax/
+
+        pop_generic(keyspace, args, true, "zpopmax")
+    }
+
+    #[derive(Clone, Copy)]
+    enum Aggregate {
+        Sum,
+        Min,
+        Max,
+    }
+
+    impl Aggregate {
+        fn apply(self, a: f64, b: f64) -> f64 {
+            match self {
+                // inf + -inf is NaN, which Redis turns into 0
+                Aggregate::Sum => Some(a + b).filter(|sum| !sum.is_nan()).unwrap_or(0.0),
+                Aggregate::Min => a.min(b),
+                Aggregate::Max => a.max(b),
+            }
+        }
+    }
+
+    // `args` are DESTINATION NUMKEYS KEY [KEY ...] [WEIGHTS ...] [AGGREGATE SUM|MIN|MAX]
+    fn combine_store(keyspace: &mut Keyspace, args: Vec<&[u8]>, union: bool, name: &str) -> Result {
+        if args.len() < 3 {
+            return Err(format!("[ERROR]: At least three arguments required for {name}!"));
+        }
+
+        let numkeys = match args[1].parse::<i64>() {
+            Ok(n) if n > 0 => n as usize,
+            Ok(_) => return Err(format!("[ERROR]: at least 1 input key is needed for '{name}' command")),
+            Err(_) => return Err(NOT_AN_INTEGER.to_string()),
+        };
+        if numkeys > args.len() - 2 {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let keys = &args[2..2 + numkeys];
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+
+        let mut idx = 2 + numkeys;
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "weights" if idx + numkeys < args.len() => {
+                    for (i, weight) in args[idx + 1..=idx + numkeys].iter().enumerate() {
+                        weights[i] = parse_float(weight, "[ERROR]: weight value is not a float")?;
+                    }
+                    idx += numkeys;
+                }
+                "aggregate" if idx + 1 < args.len() => {
+                    aggregate = match args[idx + 1].as_text().to_lowercase().as_str() {
+                        "sum" => Aggregate::Sum,
+                        "min" => Aggregate::Min,
+                        "max" => Aggregate::Max,
+                        _ => return Err(SYNTAX_ERROR.to_string()),
+                    };
+                    idx += 1;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+            idx += 1;
+        }
+
+        // plain sets can be used as inputs too, all of their members score 1
+        let mut sources = vec![];
+        for (key, weight) in keys.iter().zip(&weights) {
+            let key: &str = &key.as_text();
+            let members: HashMap<Vec<u8>, f64> = match keyspace.get_zset(key) {
+                Ok(zset) => zset
+                    .map(|zset| zset.iter().map(|(m, score)| (m.to_vec(), score)).collect())
+                    .unwrap_or_default(),
+                Err(_) => keyspace
+                    .get_set(key)?
+                    .map(|set| set.iter().map(|m| (m.clone(), 1.0)).collect())
+                    .unwrap_or_default(),
+            };
+
+            // 0 * inf is NaN, which Redis turns into 0
+            let weighted = members
+                .into_iter()
+                .map(|(m, score)| (m, Some(score * weight).filter(|s| !s.is_nan()).unwrap_or(0.0)))
+                .collect::<HashMap<_, _>>();
+            sources.push(weighted);
+        }
+
+        let mut result = sources[0].clone();
+        for source in &sources[1..] {
+            if union {
+                for (member, score) in source {
+                    result
+                        .entry(member.clone())
+                        .and_modify(|current| *current = aggregate.apply(*current, *score))
+                        .or_insert(*score);
+                }
+            } else {
+                result.retain(|member, current| match source.get(member) {
+                    Some(score) => {
+                        *current = aggregate.apply(*current, *score);
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+
+        let mut zset = SortedSet::new();
+        for (member, score) in result {
+            zset.insert(member, score);
+        }
+
+        store(keyspace, &args[0].as_text(), zset)
+    }
+
+    pub fn zunionstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zunionstore/
+
+        combine_store(keyspace, args, true, "zunionstore")
+    }
+
+    pub fn zinterstore(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/zinterstore/
+
+        combine_store(keyspace, args, false, "zinterstore")
+    }
+}
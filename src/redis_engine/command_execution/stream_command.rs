@@ -0,0 +1,875 @@
+pub mod stream {
+    use crate::redis_engine::{unix_time_ms, CommandArg, Keyspace};
+    use crate::redis_engine::{ConsumerGroup, Fields, PendingEntry, Stream, StreamId, TrimStrategy};
+    use super::super::Reply;
+    use std::ops::{Bound, RangeBounds};
+    type Result = super::super::Result;
+
+    const NOT_AN_INTEGER: &str = "[ERROR]: value is not an integer or out of range";
+    const SYNTAX_ERROR: &str = "[ERROR]: syntax error";
+    const INVALID_ID: &str = "[ERROR]: Invalid stream ID specified as stream command argument";
+
+    fn parse_id(arg: &[u8], default_seq: u64) -> core::result::Result<StreamId, String> {
+        StreamId::parse(&arg.as_text(), default_seq).ok_or_else(|| INVALID_ID.to_string())
+    }
+
+    // Range bounds: "-" and "+" are the smallest and the largest ID, "(" excludes the ID itself.
+    // An incomplete "ms" ID covers the whole millisecond, so it is "ms-0" as a start and "ms-<max>" as an end.
+    fn parse_range_bound(arg: &[u8], is_start: bool) -> core::result::Result<Bound<StreamId>, String> {
+        let default_seq = if is_start { 0 } else { u64::MAX };
+
+        match arg {
+            b"-" => Ok(Bound::Included(StreamId::MIN)),
+            b"+" => Ok(Bound::Included(StreamId::MAX)),
+            [b'(', id @ ..] => Ok(Bound::Excluded(parse_id(id, default_seq)?)),
+            id => Ok(Bound::Included(parse_id(id, default_seq)?)),
+        }
+    }
+
+    fn parse_count(arg: Option<&&[u8]>) -> core::result::Result<usize, String> {
+        match arg.map(|count| count.parse::<i64>()) {
+            Some(Ok(count)) => Ok(count.max(0) as usize),
+            _ => Err(NOT_AN_INTEGER.to_string()),
+        }
+    }
+
+    fn no_group_error(key: &str, group: &str) -> String {
+        format!("NOGROUP No such key '{key}' or consumer group '{group}'")
+    }
+
+    fn entry_reply(id: &StreamId, fields: &Fields) -> Reply {
+        let fields = fields
+            .iter()
+            .flat_map(|(field, value)| [Reply::Bulk(field.clone()), Reply::Bulk(value.clone())])
+            .collect();
+
+        Reply::Array(vec![Reply::bulk(&id.to_string()), Reply::Array(fields)])
+    }
+
+    fn entries_reply<'a>(entries: impl Iterator<Item = (&'a StreamId, &'a Fields)>) -> Reply {
+        Reply::Array(entries.map(|(id, fields)| entry_reply(id, fields)).collect())
+    }
+
+    // Parses MAXLEN|MINID [=|~] THRESHOLD [LIMIT COUNT] starting at `args[idx]`.
+    // Returns the strategy, the most entries to evict and the index right after the options.
+    fn parse_trim(args: &[&[u8]], mut idx: usize) -> core::result::Result<(TrimStrategy, usize, usize), String> {
+        let by_len = args[idx].as_text().eq_ignore_ascii_case("maxlen");
+        idx += 1;
+
+        // approximate trimming is allowed to leave extra entries around, so it is done exactly here
+        let mut approximate = false;
+        match args.get(idx).copied() {
+            Some(b"=") => idx += 1,
+            Some(b"~") => {
+                approximate = true;
+                idx += 1;
+            }
+            _ => {}
+        }
+
+        let threshold = match args.get(idx) {
+            Some(threshold) => *threshold,
+            None => return Err(SYNTAX_ERROR.to_string()),
+        };
+        let strategy = if by_len {
+            match threshold.parse::<i64>() {
+                Ok(max_len) if max_len >= 0 => TrimStrategy::MaxLen(max_len as usize),
+                Ok(_) => return Err("[ERROR]: The MAXLEN argument must be >= 0.".to_string()),
+                Err(_) => return Err(NOT_AN_INTEGER.to_string()),
+            }
+        } else {
+            TrimStrategy::MinId(parse_id(threshold, 0)?)
+        };
+        idx += 1;
+
+        let mut limit = usize::MAX;
+        if args.get(idx).is_some_and(|arg| arg.as_text().eq_ignore_ascii_case("limit")) {
+            if !approximate {
+                return Err("[ERROR]: syntax error, LIMIT cannot be used without the special ~ option".to_string());
+            }
+
+            limit = match args.get(idx + 1).map(|limit| limit.parse::<i64>()) {
+                Some(Ok(limit)) if limit >= 0 => limit as usize,
+                Some(Ok(_)) => return Err("[ERROR]: The LIMIT argument must be >= 0.".to_string()),
+                _ => return Err(NOT_AN_INTEGER.to_string()),
+            };
+            idx += 2;
+        }
+
+        // a limit of 0 means there is no limit
+        if limit == 0 {
+            limit = usize::MAX;
+        }
+
+        Ok((strategy, limit, idx))
+    }
+
+    pub fn xadd(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xadd/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for xadd!".to_string());
+        }
+
+        let mut create = true;
+        let mut trim = None;
+        let mut idx = 1;
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "nomkstream" => {
+                    create = false;
+                    idx += 1;
+                }
+                "maxlen" | "minid" => {
+                    let (strategy, limit, next) = parse_trim(&args, idx)?;
+                    trim = Some((strategy, limit));
+                    idx = next;
+                }
+                _ => break,
+            }
+        }
+
+        let pairs = args.get(idx + 1..).unwrap_or_default();
+        if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+            return Err("[ERROR]: wrong number of arguments for 'xadd' command".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        if !create && keyspace.get_stream(key)?.is_none() {
+            return Ok(Reply::Nil);
+        }
+
+        let requested: &str = &args[idx].as_text();
+        let id = match keyspace.get_stream(key)? {
+            Some(stream) => stream.next_id(requested, unix_time_ms())?,
+            None => Stream::new().next_id(requested, unix_time_ms())?,
+        };
+
+        let stream = keyspace.get_or_create_stream(key)?;
+        stream.add(id, pairs.chunks(2).map(|pair| (pair[0].to_vec(), pair[1].to_vec())).collect());
+        if let Some((strategy, limit)) = trim {
+            stream.trim(&strategy, limit);
+        }
+
+        Ok(Reply::bulk(&id.to_string()))
+    }
+
+    pub fn xlen(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xlen/
+
+        if args.len() != 1 {
+            return Err("[ERROR]: xlen requires only one argument!".to_string());
+        }
+
+        match keyspace.get_stream(&args[0].as_text())? {
+            Some(stream) => Ok(Reply::Integer(stream.len() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    fn range_generic(keyspace: &mut Keyspace, args: Vec<&[u8]>, reverse: bool, name: &str) -> Result {
+        if args.len() != 3 && args.len() != 5 {
+            return Err(format!("[ERROR]: Usage: {name} KEY {} [COUNT count]", if reverse { "END START" } else { "START END" }));
+        }
+
+        // XREVRANGE takes the end first
+        let (start, end) = if reverse { (args[2], args[1]) } else { (args[1], args[2]) };
+        let start = parse_range_bound(start, true)?;
+        let end = parse_range_bound(end, false)?;
+
+        let mut count = usize::MAX;
+        if args.len() == 5 {
+            if !args[3].as_text().eq_ignore_ascii_case("count") {
+                return Err(SYNTAX_ERROR.to_string());
+            }
+            count = parse_count(args.get(4))?;
+        }
+
+        let stream = match keyspace.get_stream(&args[0].as_text())? {
+            Some(stream) => stream,
+            None => return Ok(Reply::Array(vec![])),
+        };
+
+        if reverse {
+            Ok(entries_reply(stream.range(start, end).rev().take(count)))
+        } else {
+            Ok(entries_reply(stream.range(start, end).take(count)))
+        }
+    }
+
+    pub fn xrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xrange/
+
+        range_generic(keyspace, args, false, "xrange")
+    }
+
+    pub fn xrevrange(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xrevrange/
+
+        range_generic(keyspace, args, true, "xrevrange")
+    }
+
+    pub fn xdel(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xdel/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for xdel!".to_string());
+        }
+
+        let ids = args[1..].iter().map(|id| parse_id(id, 0)).collect::<core::result::Result<Vec<_>, _>>()?;
+        let stream = match keyspace.get_stream_mut(&args[0].as_text())? {
+            Some(stream) => stream,
+            None => return Ok(Reply::Integer(0)),
+        };
+
+        let deleted = ids.into_iter().filter(|id| stream.remove(*id)).count();
+
+        Ok(Reply::Integer(deleted as i64))
+    }
+
+    pub fn xtrim(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xtrim/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for xtrim!".to_string());
+        }
+        if !["maxlen", "minid"].contains(&args[1].as_text().to_lowercase().as_str()) {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let (strategy, limit, idx) = parse_trim(&args, 1)?;
+        if idx != args.len() {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        match keyspace.get_stream_mut(&args[0].as_text())? {
+            Some(stream) => Ok(Reply::Integer(stream.trim(&strategy, limit) as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    // The arguments after STREAMS are the keys followed by one ID for each of them
+    fn check_streams(args: &[&[u8]], name: &str) -> core::result::Result<(), String> {
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(format!(
+                "[ERROR]: Unbalanced '{name}' list of streams: for each stream key an ID or '$' must be specified."
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn xread(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xread/
+
+        let mut count = usize::MAX;
+        let mut idx = 0;
+        loop {
+            match args.get(idx).map(|arg| arg.as_text().to_lowercase()).as_deref() {
+                Some("count") => {
+                    count = parse_count(args.get(idx + 1))?;
+                    idx += 2;
+                }
+                Some("block") => return Err("[ERROR]: BLOCK is not supported by xread".to_string()),
+                Some("streams") => break,
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+        }
+
+        let streams = &args[idx + 1..];
+        check_streams(streams, "xread")?;
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+
+        let mut replies = vec![];
+        for (key, id) in keys.iter().zip(ids) {
+            let key: &str = &key.as_text();
+            let stream = keyspace.get_stream(key)?;
+
+            // "$" stands for the last ID, so only the entries added after this call would show up
+            let after = match *id {
+                b"$" => stream.map_or(StreamId::MIN, |stream| stream.last_id),
+                id => parse_id(id, 0)?,
+            };
+
+            if let Some(stream) = stream {
+                let entries: Vec<_> = stream.range(Bound::Excluded(after), Bound::Unbounded).take(count).collect();
+                if !entries.is_empty() {
+                    replies.push(Reply::Array(vec![Reply::bulk(key), entries_reply(entries.into_iter())]));
+                }
+            }
+        }
+
+        if replies.is_empty() {
+            return Ok(Reply::Nil);
+        }
+
+        Ok(Reply::Array(replies))
+    }
+
+    pub fn xgroup(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xgroup/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: Usage: xgroup CREATE|DESTROY|SETID|CREATECONSUMER|DELCONSUMER KEY GROUP ...".to_string());
+        }
+
+        let subcommand = args[0].as_text().to_lowercase();
+        let key: &str = &args[1].as_text();
+        let group_name = args[2].as_text().to_string();
+
+        // only CREATE can make a new stream, with MKSTREAM
+        let mkstream = subcommand == "create" && args[3..].iter().any(|arg| arg.as_text().eq_ignore_ascii_case("mkstream"));
+        let stream = match keyspace.get_stream_mut(key)? {
+            Some(stream) => stream,
+            None if mkstream => keyspace.get_or_create_stream(key)?,
+            None => {
+                return Err("[ERROR]: The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".to_string())
+            }
+        };
+
+        // "$" stands for the last ID of the stream
+        let group_id = |arg: &[u8], stream: &Stream| match arg {
+            b"$" => Ok(stream.last_id),
+            id => parse_id(id, 0),
+        };
+        let no_group = || format!("NOGROUP No such consumer group '{group_name}' for key name '{key}'");
+
+        match subcommand.as_str() {
+            "create" => {
+                let id = match args.get(3) {
+                    Some(id) => group_id(id, stream)?,
+                    None => return Err(SYNTAX_ERROR.to_string()),
+                };
+                if stream.groups.contains_key(&group_name) {
+                    return Err("BUSYGROUP Consumer Group name already exists".to_string());
+                }
+
+                let group = ConsumerGroup { last_delivered_id: id, ..ConsumerGroup::default() };
+                stream.groups.insert(group_name, group);
+                Ok(Reply::ok())
+            }
+            "setid" => {
+                let id = match args.get(3) {
+                    Some(id) => group_id(id, stream)?,
+                    None => return Err(SYNTAX_ERROR.to_string()),
+                };
+
+                match stream.groups.get_mut(&group_name) {
+                    Some(group) => {
+                        group.last_delivered_id = id;
+                        Ok(Reply::ok())
+                    }
+                    None => Err(no_group()),
+                }
+            }
+            "destroy" => Ok(Reply::Integer(stream.groups.remove(&group_name).is_some() as i64)),
+            "createconsumer" | "delconsumer" => {
+                let consumer = match args.get(3) {
+                    Some(consumer) => consumer.as_text().to_string(),
+                    None => return Err(SYNTAX_ERROR.to_string()),
+                };
+                let group = stream.groups.get_mut(&group_name).ok_or_else(no_group)?;
+
+                if subcommand == "createconsumer" {
+                    let created = !group.consumers.contains_key(&consumer);
+                    group.consumers.entry(consumer).or_insert_with(unix_time_ms);
+                    return Ok(Reply::Integer(created as i64));
+                }
+
+                // the pending entries of the consumer are gone along with it
+                let before = group.pending.len();
+                group.pending.retain(|_, entry| entry.consumer != consumer);
+                group.consumers.remove(&consumer);
+                Ok(Reply::Integer((before - group.pending.len()) as i64))
+            }
+            _ => Err(format!("[ERROR]: unknown subcommand '{subcommand}'.")),
+        }
+    }
+
+    pub fn xreadgroup(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xreadgroup/
+
+        if args.len() < 3 || !args[0].as_text().eq_ignore_ascii_case("group") {
+            return Err("[ERROR]: Missing GROUP option for XREADGROUP".to_string());
+        }
+
+        let group_name = args[1].as_text().to_string();
+        let consumer = args[2].as_text().to_string();
+
+        let mut count = usize::MAX;
+        let mut noack = false;
+        let mut idx = 3;
+        loop {
+            match args.get(idx).map(|arg| arg.as_text().to_lowercase()).as_deref() {
+                Some("count") => {
+                    count = parse_count(args.get(idx + 1))?;
+                    idx += 2;
+                }
+                Some("noack") => {
+                    noack = true;
+                    idx += 1;
+                }
+                Some("block") => return Err("[ERROR]: BLOCK is not supported by xreadgroup".to_string()),
+                Some("streams") => break,
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+        }
+
+        let streams = &args[idx + 1..];
+        check_streams(streams, "xreadgroup")?;
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+
+        // every stream has to have the group before anything gets delivered
+        let mut requests = vec![];
+        for (key, id) in keys.iter().zip(ids) {
+            let key = key.as_text().to_string();
+            let has_group = keyspace.get_stream(&key)?.is_some_and(|stream| stream.groups.contains_key(&group_name));
+            if !has_group {
+                return Err(format!(
+                    "NOGROUP No such key '{key}' or consumer group '{group_name}' in XREADGROUP with GROUP option"
+                ));
+            }
+
+            // ">" asks for entries never delivered to the group, an ID for the history of the consumer
+            let after = match *id {
+                b">" => None,
+                id => Some(parse_id(id, 0)?),
+            };
+            requests.push((key, after));
+        }
+
+        let now = unix_time_ms();
+        let mut replies = vec![];
+        for (key, after) in requests {
+            let stream = keyspace.get_stream_mut(&key)?.unwrap();
+            let group = stream.groups.get_mut(&group_name).unwrap();
+            group.consumers.insert(consumer.clone(), now);
+
+            let entries = match after {
+                None => {
+                    let entries: Vec<Reply> = stream
+                        .entries
+                        .range((Bound::Excluded(group.last_delivered_id), Bound::Unbounded))
+                        .take(count)
+                        .map(|(id, fields)| {
+                            group.last_delivered_id = *id;
+                            if !noack {
+                                let pending = PendingEntry { consumer: consumer.clone(), delivery_time: now, delivery_count: 1 };
+                                group.pending.insert(*id, pending);
+                            }
+                            entry_reply(id, fields)
+                        })
+                        .collect();
+
+                    if entries.is_empty() {
+                        continue;
+                    }
+                    entries
+                }
+                // entries deleted from the stream since they were delivered show up without their fields
+                Some(after) => group
+                    .pending_of(&consumer)
+                    .filter(|(id, _)| **id > after)
+                    .take(count)
+                    .map(|(id, _)| match stream.entries.get(id) {
+                        Some(fields) => entry_reply(id, fields),
+                        None => Reply::Array(vec![Reply::bulk(&id.to_string()), Reply::Nil]),
+                    })
+                    .collect(),
+            };
+
+            replies.push(Reply::Array(vec![Reply::bulk(&key), Reply::Array(entries)]));
+        }
+
+        if replies.is_empty() {
+            return Ok(Reply::Nil);
+        }
+
+        Ok(Reply::Array(replies))
+    }
+
+    pub fn xack(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xack/
+
+        if args.len() < 3 {
+            return Err("[ERROR]: At least three arguments required for xack!".to_string());
+        }
+
+        let ids = args[2..].iter().map(|id| parse_id(id, 0)).collect::<core::result::Result<Vec<_>, _>>()?;
+        let group = keyspace
+            .get_stream_mut(&args[0].as_text())?
+            .and_then(|stream| stream.groups.get_mut(&*args[1].as_text()));
+
+        match group {
+            Some(group) => Ok(Reply::Integer(ids.iter().filter(|id| group.pending.remove(id).is_some()).count() as i64)),
+            None => Ok(Reply::Integer(0)),
+        }
+    }
+
+    pub fn xpending(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xpending/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: At least two arguments required for xpending!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let group_name: &str = &args[1].as_text();
+        let group = keyspace
+            .get_stream(key)?
+            .and_then(|stream| stream.groups.get(group_name))
+            .ok_or_else(|| no_group_error(key, group_name))?;
+
+        // the summary: how many entries are pending, their ID range and how many each consumer has
+        if args.len() == 2 {
+            let (first, last) = match (group.pending.keys().next(), group.pending.keys().next_back()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => return Ok(Reply::Array(vec![Reply::Integer(0), Reply::Nil, Reply::Nil, Reply::Nil])),
+            };
+
+            let mut per_consumer = std::collections::BTreeMap::new();
+            for entry in group.pending.values() {
+                *per_consumer.entry(&entry.consumer).or_insert(0) += 1;
+            }
+            let consumers = per_consumer
+                .into_iter()
+                .map(|(consumer, count)| Reply::Array(vec![Reply::bulk(consumer), Reply::bulk(&count.to_string())]))
+                .collect();
+
+            return Ok(Reply::Array(vec![
+                Reply::Integer(group.pending.len() as i64),
+                Reply::bulk(&first.to_string()),
+                Reply::bulk(&last.to_string()),
+                Reply::Array(consumers),
+            ]));
+        }
+
+        // the extended form: [IDLE min-idle-time] start end count [consumer]
+        let mut idx = 2;
+        let mut min_idle = 0;
+        if args[idx].as_text().eq_ignore_ascii_case("idle") {
+            min_idle = match args.get(idx + 1).map(|idle| idle.parse::<u64>()) {
+                Some(Ok(idle)) => idle,
+                _ => return Err(NOT_AN_INTEGER.to_string()),
+            };
+            idx += 2;
+        }
+        if args.len() < idx + 3 || args.len() > idx + 4 {
+            return Err(SYNTAX_ERROR.to_string());
+        }
+
+        let start = parse_range_bound(args[idx], true)?;
+        let end = parse_range_bound(args[idx + 1], false)?;
+        let count = parse_count(args.get(idx + 2))?;
+        let consumer = args.get(idx + 3).map(|consumer| consumer.as_text().to_string());
+
+        let now = unix_time_ms();
+        let entries = group
+            .pending
+            .iter()
+            .filter(|(id, entry)| {
+                (start, end).contains(*id)
+                    && consumer.as_ref().is_none_or(|consumer| *consumer == entry.consumer)
+                    && now.saturating_sub(entry.delivery_time) >= min_idle
+            })
+            .take(count)
+            .map(|(id, entry)| {
+                Reply::Array(vec![
+                    Reply::bulk(&id.to_string()),
+                    Reply::bulk(&entry.consumer),
+                    Reply::Integer(now.saturating_sub(entry.delivery_time) as i64),
+                    Reply::Integer(entry.delivery_count as i64),
+                ])
+            })
+            .collect();
+
+        Ok(Reply::Array(entries))
+    }
+
+    // How a claimed entry gets handed over to its new consumer
+    struct Claim<'a> {
+        consumer: &'a str,
+        delivery_time: u64,
+        retry_count: Option<u64>,
+        just_id: bool,
+    }
+
+    impl Claim<'_> {
+        fn apply(&self, entry: &mut PendingEntry) {
+            entry.consumer = self.consumer.to_string();
+            entry.delivery_time = self.delivery_time;
+
+            // JUSTID doesn't count as a delivery
+            match self.retry_count {
+                Some(count) => entry.delivery_count = count,
+                None if !self.just_id => entry.delivery_count += 1,
+                None => {}
+            }
+        }
+
+        fn reply(&self, id: &StreamId, fields: &Fields) -> Reply {
+            if self.just_id {
+                Reply::bulk(&id.to_string())
+            } else {
+                entry_reply(id, fields)
+            }
+        }
+    }
+
+    fn parse_min_idle(arg: &[u8], name: &str) -> core::result::Result<u64, String> {
+        match arg.parse::<i64>() {
+            Ok(idle) => Ok(idle.max(0) as u64),
+            Err(_) => Err(format!("[ERROR]: Invalid min-idle-time argument for {name}")),
+        }
+    }
+
+    pub fn xclaim(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xclaim/
+
+        if args.len() < 5 {
+            return Err("[ERROR]: At least five arguments required for xclaim!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let group_name: &str = &args[1].as_text();
+        let consumer: &str = &args[2].as_text();
+        let min_idle = parse_min_idle(args[3], "XCLAIM")?;
+
+        // the IDs go until the first argument which isn't one, the options follow
+        let mut ids = vec![];
+        let mut idx = 4;
+        while let Some(id) = args.get(idx).and_then(|id| StreamId::parse(&id.as_text(), 0)) {
+            ids.push(id);
+            idx += 1;
+        }
+
+        let now = unix_time_ms();
+        let mut delivery_time = now;
+        let mut retry_count = None;
+        let mut force = false;
+        let mut just_id = false;
+        let mut last_id = None;
+        while idx < args.len() {
+            let option = args[idx].as_text().to_lowercase();
+            let value = || match args.get(idx + 1).map(|value| value.parse::<i64>()) {
+                Some(Ok(value)) => Ok(value.max(0) as u64),
+                _ => Err(format!("[ERROR]: Invalid {} option argument for XCLAIM", option.to_uppercase())),
+            };
+
+            match option.as_str() {
+                "idle" => delivery_time = now.saturating_sub(value()?),
+                "time" => delivery_time = value()?,
+                "retrycount" => retry_count = Some(value()?),
+                "lastid" => last_id = Some(parse_id(args.get(idx + 1).copied().unwrap_or_default(), 0)?),
+                "force" => force = true,
+                "justid" => just_id = true,
+                _ => return Err(format!("[ERROR]: Unrecognized XCLAIM option '{}'", args[idx].as_text())),
+            }
+            idx += if ["force", "justid"].contains(&option.as_str()) { 1 } else { 2 };
+            if idx > args.len() {
+                return Err(SYNTAX_ERROR.to_string());
+            }
+        }
+
+        let stream = match keyspace.get_stream_mut(key)? {
+            Some(stream) if stream.groups.contains_key(group_name) => stream,
+            _ => return Err(no_group_error(key, group_name)),
+        };
+        let group = stream.groups.get_mut(group_name).unwrap();
+        if let Some(last_id) = last_id {
+            group.last_delivered_id = group.last_delivered_id.max(last_id);
+        }
+        group.consumers.insert(consumer.to_string(), now);
+
+        let claim = Claim { consumer, delivery_time, retry_count, just_id };
+        let mut claimed = vec![];
+        for id in ids {
+            let fields = stream.entries.get(&id);
+
+            // FORCE claims entries which exist in the stream even when they aren't pending,
+            // those have never been delivered, so they can't be too fresh to claim
+            let forced = force && fields.is_some() && !group.pending.contains_key(&id);
+            if forced {
+                let entry = PendingEntry { consumer: consumer.to_string(), delivery_time: now, delivery_count: 0 };
+                group.pending.insert(id, entry);
+            }
+
+            let entry = match group.pending.get_mut(&id) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let fields = match fields {
+                Some(fields) => fields,
+                None => {
+                    // the entry got deleted from the stream, so there is nothing left to claim
+                    group.pending.remove(&id);
+                    continue;
+                }
+            };
+
+            if !forced && now.saturating_sub(entry.delivery_time) < min_idle {
+                continue;
+            }
+
+            claim.apply(entry);
+            claimed.push(claim.reply(&id, fields));
+        }
+
+        Ok(Reply::Array(claimed))
+    }
+
+    pub fn xautoclaim(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xautoclaim/
+
+        if args.len() < 5 {
+            return Err("[ERROR]: At least five arguments required for xautoclaim!".to_string());
+        }
+
+        let key: &str = &args[0].as_text();
+        let group_name: &str = &args[1].as_text();
+        let consumer: &str = &args[2].as_text();
+        let min_idle = parse_min_idle(args[3], "XAUTOCLAIM")?;
+        let start = parse_range_bound(args[4], true)?;
+
+        let mut count = 100;
+        let mut just_id = false;
+        let mut idx = 5;
+        while idx < args.len() {
+            match args[idx].as_text().to_lowercase().as_str() {
+                "count" => {
+                    count = match args.get(idx + 1).map(|count| count.parse::<i64>()) {
+                        // Redis also rejects counts that would overflow the scan limit below
+                        Some(Ok(count)) if count > 0 && count <= i64::MAX / 10 => count as usize,
+                        Some(Ok(_)) => return Err("[ERROR]: COUNT must be > 0".to_string()),
+                        _ => return Err(NOT_AN_INTEGER.to_string()),
+                    };
+                    idx += 2;
+                }
+                "justid" => {
+                    just_id = true;
+                    idx += 1;
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+        }
+
+        let stream = match keyspace.get_stream_mut(key)? {
+            Some(stream) if stream.groups.contains_key(group_name) => stream,
+            _ => return Err(no_group_error(key, group_name)),
+        };
+        let group = stream.groups.get_mut(group_name).unwrap();
+        let now = unix_time_ms();
+        group.consumers.insert(consumer.to_string(), now);
+
+        // like Redis, scan no more than 10 pending entries per claimed one
+        let candidates: Vec<StreamId> = group
+            .pending
+            .range((start, Bound::Unbounded))
+            .take(count * 10)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let claim = Claim { consumer, delivery_time: now, retry_count: None, just_id };
+        let mut claimed = vec![];
+        let mut deleted = vec![];
+        let mut cursor = StreamId::MIN;
+        for (i, id) in candidates.iter().enumerate() {
+            if claimed.len() == count {
+                cursor = *id;
+                break;
+            }
+            if i + 1 == candidates.len() {
+                // the scan stopped before the end of the pending entries, the next call picks up from there
+                cursor = group
+                    .pending
+                    .range((Bound::Excluded(*id), Bound::Unbounded))
+                    .next()
+                    .map_or(StreamId::MIN, |(id, _)| *id);
+            }
+
+            let fields = match stream.entries.get(id) {
+                Some(fields) => fields,
+                None => {
+                    group.pending.remove(id);
+                    deleted.push(Reply::bulk(&id.to_string()));
+                    continue;
+                }
+            };
+
+            let entry = group.pending.get_mut(id).unwrap();
+            if now.saturating_sub(entry.delivery_time) >= min_idle {
+                claim.apply(entry);
+                claimed.push(claim.reply(id, fields));
+            }
+        }
+
+        Ok(Reply::Array(vec![Reply::bulk(&cursor.to_string()), Reply::Array(claimed), Reply::Array(deleted)]))
+    }
+
+    pub fn xinfo(keyspace: &mut Keyspace, args: Vec<&[u8]>) -> Result {
+        // https://redis.io/commands/xinfo/
+
+        if args.len() < 2 {
+            return Err("[ERROR]: Usage: xinfo STREAM|GROUPS|CONSUMERS KEY [GROUP]".to_string());
+        }
+
+        let subcommand = args[0].as_text().to_lowercase();
+        let key: &str = &args[1].as_text();
+        let stream = keyspace.get_stream(key)?.ok_or_else(|| "[ERROR]: no such key".to_string())?;
+        let pair = |name: &str, value: Reply| (Reply::bulk(name), value);
+        let id_reply = |id: &StreamId| Reply::bulk(&id.to_string());
+
+        match subcommand.as_str() {
+            "stream" if args.len() == 2 => {
+                let first = stream.entries.iter().next().map_or(Reply::Nil, |(id, fields)| entry_reply(id, fields));
+                let last = stream.entries.iter().next_back().map_or(Reply::Nil, |(id, fields)| entry_reply(id, fields));
+
+                Ok(Reply::Map(vec![
+                    pair("length", Reply::Integer(stream.len() as i64)),
+                    pair("last-generated-id", id_reply(&stream.last_id)),
+                    pair("max-deleted-entry-id", id_reply(&stream.max_deleted_id)),
+                    pair("entries-added", Reply::Integer(stream.entries_added as i64)),
+                    pair("groups", Reply::Integer(stream.groups.len() as i64)),
+                    pair("first-entry", first),
+                    pair("last-entry", last),
+                ]))
+            }
+            "groups" if args.len() == 2 => {
+                let groups = stream
+                    .groups
+                    .iter()
+                    .map(|(name, group)| {
+                        Reply::Map(vec![
+                            pair("name", Reply::bulk(name)),
+                            pair("consumers", Reply::Integer(group.consumers.len() as i64)),
+                            pair("pending", Reply::Integer(group.pending.len() as i64)),
+                            pair("last-delivered-id", id_reply(&group.last_delivered_id)),
+                        ])
+                    })
+                    .collect();
+
+                Ok(Reply::Array(groups))
+            }
+            "consumers" if args.len() == 3 => {
+                let group_name: &str = &args[2].as_text();
+                let group = stream.groups.get(group_name).ok_or_else(|| no_group_error(key, group_name))?;
+
+                let now = unix_time_ms();
+                let consumers = group
+                    .consumers
+                    .iter()
+                    .map(|(name, seen_time)| {
+                        Reply::Map(vec![
+                            pair("name", Reply::bulk(name)),
+                            pair("pending", Reply::Integer(group.pending_of(name).count() as i64)),
+                            pair("idle", Reply::Integer(now.saturating_sub(*seen_time) as i64)),
+                        ])
+                    })
+                    .collect();
+
+                Ok(Reply::Array(consumers))
+            }
+            _ => Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+}
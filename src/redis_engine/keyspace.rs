@@ -0,0 +1,510 @@
+use super::{unix_time_ms, RedisValue, SortedSet, Stream};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub const WRONGTYPE_ERROR: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+// How many keys with a TTL get checked per round of the active expiry, same as Redis
+const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;
+// Upper bound for a single active expiry cycle, so it can't stall the event loop
+const ACTIVE_EXPIRE_TIME_LIMIT: std::time::Duration = std::time::Duration::from_millis(25);
+
+// Every value stored under a key, tagged with its Redis type
+#[derive(Clone, Debug)]
+pub enum RedisObject {
+    String(RedisValue),
+    // a deque, so pushing and popping at either end is O(1)
+    List(VecDeque<RedisValue>),
+    Hash(HashMap<String, RedisValue>),
+    Set(HashSet<Vec<u8>>),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+impl RedisObject {
+    // https://redis.io/commands/type/
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RedisObject::String(_) => "string",
+            RedisObject::List(_) => "list",
+            RedisObject::Hash(_) => "hash",
+            RedisObject::Set(_) => "set",
+            RedisObject::SortedSet(_) => "zset",
+            RedisObject::Stream(_) => "stream",
+        }
+    }
+}
+
+// Deadlines of the keys that have a TTL, as unix time in milliseconds.
+// The keys are kept in a vec as well, so the active expiry can pick random ones in O(1).
+#[derive(Default)]
+struct Expires {
+    deadlines: HashMap<String, (u64, usize)>,
+    keys: Vec<String>,
+}
+
+impl Expires {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn get(&self, key: &str) -> Option<u64> {
+        self.deadlines.get(key).map(|(at, _)| *at)
+    }
+
+    fn insert(&mut self, key: String, at: u64) {
+        if let Some(entry) = self.deadlines.get_mut(&key) {
+            entry.0 = at;
+            return;
+        }
+
+        self.deadlines.insert(key.clone(), (at, self.keys.len()));
+        self.keys.push(key);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<u64> {
+        let (at, slot) = self.deadlines.remove(key)?;
+
+        // fill the hole with the last key and point it to its new slot
+        self.keys.swap_remove(slot);
+        if let Some(moved) = self.keys.get(slot) {
+            self.deadlines.get_mut(moved).unwrap().1 = slot;
+        }
+
+        Some(at)
+    }
+
+    fn entry_at(&self, slot: usize) -> (&String, u64) {
+        let key = &self.keys[slot];
+        (key, self.deadlines[key].0)
+    }
+}
+
+// One namespace for the keys of every type, so a key can only ever hold one kind of value.
+// Keys with a TTL are expired lazily, i.e. whenever they get accessed after their deadline,
+// and actively by `active_expire_cycle`, which the server runs periodically.
+pub struct Keyspace {
+    data: HashMap<String, RedisObject>,
+    expires: Expires,
+    // state of the xorshift generator behind `next_random`
+    seed: u64,
+    // how many clients are blocked on each key, only those keys get reported as ready
+    blocked_keys: HashMap<String, usize>,
+    // keys clients are blocked on that got a list since the server last looked, so they might be served now
+    ready_keys: HashSet<String>,
+}
+
+impl Default for Keyspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keyspace {
+    pub fn new() -> Self {
+        Self {
+            data: HashMap::new(),
+            expires: Expires::default(),
+            seed: unix_time_ms() | 1,
+            blocked_keys: HashMap::new(),
+            ready_keys: HashSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn is_expired(&self, key: &str) -> bool {
+        matches!(self.expires.get(key), Some(at) if at <= unix_time_ms())
+    }
+
+    // Lazy expiry, every access that can modify a key goes through here first
+    fn expire_if_needed(&mut self, key: &str) -> bool {
+        if !self.is_expired(key) {
+            return false;
+        }
+
+        self.data.remove(key);
+        self.expires.remove(key);
+
+        true
+    }
+
+    // Expired keys are treated as missing even if nobody has deleted them yet
+    fn lookup(&self, key: &str) -> Option<&RedisObject> {
+        if self.is_expired(key) {
+            None
+        } else {
+            self.data.get(key)
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&RedisObject> {
+        self.lookup(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut RedisObject> {
+        self.expire_if_needed(key);
+        self.data.get_mut(key)
+    }
+
+    // Stores a new value under the key, dropping any TTL the old one had
+    pub fn insert(&mut self, key: String, value: RedisObject) {
+        if matches!(value, RedisObject::List(_)) {
+            self.signal_ready(&key);
+        }
+        self.expires.remove(&key);
+        self.data.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<RedisObject> {
+        self.expire_if_needed(key);
+        self.expires.remove(key);
+        self.data.remove(key)
+    }
+
+    // Moves the value along with its TTL to another key, overwriting whatever was there.
+    // Returns false if there's no such key.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let (value, expiry) = match self.remove_with_expiry(from) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        self.insert(to.to_string(), value);
+        if let Some(at) = expiry {
+            self.expires.insert(to.to_string(), at);
+        }
+
+        true
+    }
+
+    fn remove_with_expiry(&mut self, key: &str) -> Option<(RedisObject, Option<u64>)> {
+        self.expire_if_needed(key);
+        let at = self.expires.remove(key);
+
+        self.data.remove(key).map(|value| (value, at))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RedisObject)> {
+        let now = unix_time_ms();
+
+        self.data
+            .iter()
+            .filter(move |(k, _)| !matches!(self.expires.get(k), Some(at) if at <= now))
+    }
+
+    // Deadline of the key as unix time in milliseconds, if it has one
+    pub fn expiry(&self, key: &str) -> Option<u64> {
+        if self.is_expired(key) {
+            None
+        } else {
+            self.expires.get(key)
+        }
+    }
+
+    // Returns false if there's no such key
+    pub fn set_expiry(&mut self, key: &str, at: u64) -> bool {
+        self.expire_if_needed(key);
+        if !self.data.contains_key(key) {
+            return false;
+        }
+
+        self.expires.insert(key.to_string(), at);
+
+        true
+    }
+
+    // Makes the key live forever again. Returns false if it didn't have a TTL.
+    pub fn persist(&mut self, key: &str) -> bool {
+        self.expire_if_needed(key);
+
+        self.expires.remove(key).is_some()
+    }
+
+    // Used for sampling keys and by the commands that pick random elements
+    pub fn next_random(&mut self) -> u64 {
+        // xorshift64, good enough for picking elements and no need to pull in a crate for it
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+
+        self.seed
+    }
+
+    // Active expiry, done the same way Redis does it: sample some of the keys with a TTL
+    // and delete the expired ones. If more than a quarter of the sample had expired there
+    // are probably a lot more of them, so keep going until we run out of time.
+    // Returns the number of deleted keys.
+    pub fn active_expire_cycle(&mut self) -> usize {
+        let start = std::time::Instant::now();
+        let mut deleted = 0;
+
+        loop {
+            let now = unix_time_ms();
+            let sample = ACTIVE_EXPIRE_KEYS_PER_LOOP.min(self.expires.len());
+            let mut expired = 0;
+
+            for _ in 0..sample {
+                if self.expires.len() == 0 {
+                    break;
+                }
+
+                let slot = (self.next_random() % self.expires.len() as u64) as usize;
+                let (key, at) = self.expires.entry_at(slot);
+                if at <= now {
+                    let key = key.clone();
+                    self.data.remove(&key);
+                    self.expires.remove(&key);
+                    expired += 1;
+                }
+            }
+            deleted += expired;
+
+            if sample == 0 || expired * 4 <= sample || start.elapsed() >= ACTIVE_EXPIRE_TIME_LIMIT {
+                return deleted;
+            }
+        }
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<Option<&RedisValue>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::String(value)) => Ok(Some(value)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_string_mut(&mut self, key: &str) -> Result<Option<&mut RedisValue>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::String(value)) => Ok(Some(value)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    // Stores new bytes under the key, keeping the TTL if it had one
+    pub fn update_string(&mut self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        match self.get_string_mut(key)? {
+            Some(value) => *value = RedisValue::from_bytes(bytes),
+            None => self.insert(key.to_string(), RedisObject::String(RedisValue::from_bytes(bytes))),
+        }
+
+        Ok(())
+    }
+
+    // The bytes of the string under the key, created empty if missing, to be changed in place.
+    // Keeps the TTL and doesn't copy the value, which matters for large bitmaps.
+    pub fn get_or_create_string_bytes_mut(&mut self, key: &str) -> Result<&mut Vec<u8>, String> {
+        self.expire_if_needed(key);
+        let value = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::String(RedisValue::from_bytes(b"")));
+
+        match value {
+            RedisObject::String(value) => Ok(value.bytes_mut()),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    pub fn get_list(&self, key: &str) -> Result<Option<&VecDeque<RedisValue>>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_list_mut(&mut self, key: &str) -> Result<Option<&mut VecDeque<RedisValue>>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or_create_list(&mut self, key: &str) -> Result<&mut VecDeque<RedisValue>, String> {
+        self.expire_if_needed(key);
+        if !self.data.contains_key(key) {
+            self.signal_ready(key);
+        }
+        let list = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::List(VecDeque::new()));
+
+        match list {
+            RedisObject::List(list) => Ok(list),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    pub fn get_hash(&self, key: &str) -> Result<Option<&HashMap<String, RedisValue>>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_hash_mut(&mut self, key: &str) -> Result<Option<&mut HashMap<String, RedisValue>>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or_create_hash(&mut self, key: &str) -> Result<&mut HashMap<String, RedisValue>, String> {
+        self.expire_if_needed(key);
+        let hash = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::Hash(HashMap::new()));
+
+        match hash {
+            RedisObject::Hash(hash) => Ok(hash),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    pub fn get_set(&self, key: &str) -> Result<Option<&HashSet<Vec<u8>>>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_set_mut(&mut self, key: &str) -> Result<Option<&mut HashSet<Vec<u8>>>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or_create_set(&mut self, key: &str) -> Result<&mut HashSet<Vec<u8>>, String> {
+        self.expire_if_needed(key);
+        let set = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::Set(HashSet::new()));
+
+        match set {
+            RedisObject::Set(set) => Ok(set),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    pub fn get_zset(&self, key: &str) -> Result<Option<&SortedSet>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::SortedSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_zset_mut(&mut self, key: &str) -> Result<Option<&mut SortedSet>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::SortedSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or_create_zset(&mut self, key: &str) -> Result<&mut SortedSet, String> {
+        self.expire_if_needed(key);
+        let zset = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::SortedSet(SortedSet::new()));
+
+        match zset {
+            RedisObject::SortedSet(zset) => Ok(zset),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    pub fn get_stream(&self, key: &str) -> Result<Option<&Stream>, String> {
+        match self.lookup(key) {
+            Some(RedisObject::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_stream_mut(&mut self, key: &str) -> Result<Option<&mut Stream>, String> {
+        match self.get_mut(key) {
+            Some(RedisObject::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE_ERROR.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or_create_stream(&mut self, key: &str) -> Result<&mut Stream, String> {
+        self.expire_if_needed(key);
+        let stream = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| RedisObject::Stream(Stream::new()));
+
+        match stream {
+            RedisObject::Stream(stream) => Ok(stream),
+            _ => Err(WRONGTYPE_ERROR.to_string()),
+        }
+    }
+
+    // Redis never keeps empty lists, hashes, sets or sorted sets around, the key goes away with the last element.
+    // Streams are the exception, they keep their IDs and consumer groups even without entries.
+    pub fn remove_if_empty(&mut self, key: &str) {
+        let empty = match self.data.get(key) {
+            Some(RedisObject::List(list)) => list.is_empty(),
+            Some(RedisObject::Hash(hash)) => hash.is_empty(),
+            Some(RedisObject::Set(set)) => set.is_empty(),
+            Some(RedisObject::SortedSet(zset)) => zset.is_empty(),
+            _ => false,
+        };
+
+        if empty {
+            self.data.remove(key);
+            self.expires.remove(key);
+        }
+    }
+
+    pub fn block_on(&mut self, keys: &[String]) {
+        for key in keys {
+            *self.blocked_keys.entry(key.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn unblock_from(&mut self, keys: &[String]) {
+        for key in keys {
+            if let Some(count) = self.blocked_keys.get_mut(key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.blocked_keys.remove(key);
+                }
+            }
+        }
+    }
+
+    fn signal_ready(&mut self, key: &str) {
+        if self.blocked_keys.contains_key(key) {
+            self.ready_keys.insert(key.to_string());
+        }
+    }
+
+    pub fn take_ready_keys(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.ready_keys)
+    }
+}
@@ -1,55 +1,103 @@
 #![allow(unused_assignments, clippy::inherent_to_string)]
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 
 mod util;
 use util::*;
+mod keyspace;
+pub use keyspace::{Keyspace, RedisObject};
+mod zset;
+pub use zset::SortedSet;
+mod stream;
+pub use stream::{ConsumerGroup, Fields, PendingEntry, Stream, StreamId, TrimStrategy};
 
 mod command_execution;
+pub use command_execution::Reply;
+pub use command_execution::list_command::list::Blocking;
+pub mod resp;
+use command_execution::bit_command::bit;
+use command_execution::geo_command::geo;
 use command_execution::hash_command::hash;
+use command_execution::hyperloglog_command::hyperloglog;
 use command_execution::kvp_command::kvp;
 use command_execution::list_command::list;
 use command_execution::one_off_command::one_off;
+use command_execution::set_command::set;
+use command_execution::sorted_set_command::sorted_set;
+// renamed, since `stream` is the module of the data type itself
+use command_execution::stream_command::stream as stream_cmd;
 
 pub const DUMP_FILE_NAME: &str = "dump.my_rdb";
 pub const DEBUG_DUMP_FILE_NAME: &str = "debug_dump.my_rdb";
 #[derive(Clone)]
 pub struct ExecutionContext {
-    pub key_value_pairs: Arc<Mutex<HashMap<String, RedisValue>>>,
-    lists: Arc<Mutex<HashMap<String, Vec<RedisValue>>>>,
-    // Full type here is:
-    //std::sync::Arc<std::sync::Mutex<std::collections::HashMap<std::string::String, std::collections::HashMap<std::string::String, RedisValue>>>>
-    hashes: Arc<Mutex<HashMap<String, HashMap<String, RedisValue>>>>,
+    // Every key lives in the same keyspace, no matter the type of its value
+    pub keyspace: Arc<Mutex<Keyspace>>,
 }
 
 impl ExecutionContext {
     fn new() -> Self {
         Self {
-            key_value_pairs: Arc::new(Mutex::new(HashMap::new())),
-            lists: Arc::new(Mutex::new(HashMap::new())),
-            hashes: Arc::new(Mutex::new(HashMap::new())),
+            keyspace: Arc::new(Mutex::new(Keyspace::new())),
         }
     }
 
-    fn from_file_contents(text: String) -> Self {
-        let mut context = ExecutionContext::new();
+    pub(crate) fn from_file_contents(text: String) -> Self {
+        let context = ExecutionContext::new();
         let mut key_value_pairs = HashMap::new();
         let mut lists = HashMap::new();
         let mut hashes = HashMap::new();
+        let mut sets = HashMap::new();
+        let mut sorted_sets = HashMap::new();
+        let mut streams = HashMap::new();
+        let mut expires = HashMap::new();
 
         if let Ok(val) = serde_json::from_str(&text) {
             key_value_pairs = get_kvps_from_json("key_value_pairs".to_string(), &val);
             lists           = get_lists_from_json(&val);
             hashes          = get_hashes_from_json(&val);
+            sets            = get_sets_from_json(&val);
+            sorted_sets     = get_sorted_sets_from_json(&val);
+            streams         = get_streams_from_json(&val);
+            expires         = get_expires_from_json(&val);
         } else {
             println!("[ERROR]: file {DUMP_FILE_NAME} has been corrupted.\nHINT: Please delete it and run the program again.");
             std::process::exit(1);
         }
 
-        context.key_value_pairs = Arc::new(Mutex::new(key_value_pairs));
-        context.lists = Arc::new(Mutex::new(lists));
-        context.hashes = Arc::new(Mutex::new(hashes));
+        // the dump still keeps one section per type, older dumps may have the same key
+        // in more than one of them, in which case the last section wins
+        let mut keyspace = context.keyspace.lock().unwrap();
+        for (k, v) in key_value_pairs {
+            keyspace.insert(k, RedisObject::String(v));
+        }
+        for (k, v) in lists {
+            keyspace.insert(k, RedisObject::List(v));
+        }
+        for (k, v) in hashes {
+            keyspace.insert(k, RedisObject::Hash(v));
+        }
+        for (k, v) in sets {
+            keyspace.insert(k, RedisObject::Set(v));
+        }
+        for (k, v) in sorted_sets {
+            keyspace.insert(k, RedisObject::SortedSet(v));
+        }
+        for (k, v) in streams {
+            keyspace.insert(k, RedisObject::Stream(v));
+        }
+
+        // keys which expired while the server was down are dropped, the rest get their TTL back
+        let now = unix_time_ms();
+        for (k, at) in expires {
+            if at <= now {
+                keyspace.remove(&k);
+            } else {
+                keyspace.set_expiry(&k, at);
+            }
+        }
+        drop(keyspace);
 
         context
     }
@@ -57,9 +105,32 @@ impl ExecutionContext {
     pub fn to_string(&self) -> String {
         use serde_json::{from_str, to_string_pretty, Value};
 
-        let kvps: &HashMap<String, RedisValue> = &self.key_value_pairs.lock().unwrap();
-        let lists: &HashMap<String, Vec<RedisValue>> = &self.lists.lock().unwrap();
-        let hashes: &HashMap<String, HashMap<String, RedisValue>> = &self.hashes.lock().unwrap();
+        let keyspace = self.keyspace.lock().unwrap();
+        let mut kvps: HashMap<&String, &RedisValue> = HashMap::new();
+        let mut lists: HashMap<&String, &VecDeque<RedisValue>> = HashMap::new();
+        let mut hashes: HashMap<&String, &HashMap<String, RedisValue>> = HashMap::new();
+        let mut sets: HashMap<&String, Vec<RedisValue>> = HashMap::new();
+        // scores are written as strings, since JSON numbers can't hold inf
+        let mut sorted_sets: HashMap<&String, Vec<(RedisValue, String)>> = HashMap::new();
+        let mut streams: HashMap<&String, Value> = HashMap::new();
+        let mut expires: HashMap<&String, u64> = HashMap::new();
+
+        for (k, v) in keyspace.iter() {
+            match v {
+                RedisObject::String(value) => { kvps.insert(k, value); }
+                RedisObject::List(list) => { lists.insert(k, list); }
+                RedisObject::Hash(hash) => { hashes.insert(k, hash); }
+                RedisObject::Set(set) => { sets.insert(k, set.iter().map(|m| RedisValue::from_bytes(m)).collect()); }
+                RedisObject::SortedSet(zset) => {
+                    sorted_sets.insert(k, zset.iter().map(|(m, score)| (RedisValue::from_bytes(m), score.to_string())).collect());
+                }
+                RedisObject::Stream(stream) => { streams.insert(k, stream_to_json(stream)); }
+            }
+
+            if let Some(at) = keyspace.expiry(k) {
+                expires.insert(k, at);
+            }
+        }
 
         let mut final_str = String::from("{\n\t\"key_value_pairs\": ");
         if let Ok(s) = to_string_pretty(&kvps) {
@@ -74,13 +145,33 @@ impl ExecutionContext {
         if let Ok(s) = to_string_pretty(&hashes) {
             final_str.push_str("\t\"hashes\": ");
             final_str.push_str(&s);
+            final_str.push_str(",\n");
+        }
+        if let Ok(s) = to_string_pretty(&sets) {
+            final_str.push_str("\t\"sets\": ");
+            final_str.push_str(&s);
+            final_str.push_str(",\n");
+        }
+        if let Ok(s) = to_string_pretty(&sorted_sets) {
+            final_str.push_str("\t\"sorted_sets\": ");
+            final_str.push_str(&s);
+            final_str.push_str(",\n");
+        }
+        if let Ok(s) = to_string_pretty(&streams) {
+            final_str.push_str("\t\"streams\": ");
+            final_str.push_str(&s);
+            final_str.push_str(",\n");
+        }
+        if let Ok(s) = to_string_pretty(&expires) {
+            final_str.push_str("\t\"expires\": ");
+            final_str.push_str(&s);
             final_str.push('\n');
         }
         final_str.push_str("\n}");
 
         if from_str::<Value>(&final_str).is_ok() {
             // falltru
-        } else if kvps.len() != 0 && lists.len() != 0 && hashes.len() != 0 {
+        } else if !kvps.is_empty() && !lists.is_empty() && !hashes.is_empty() {
             println!("Data has been corrupted. Cannot save.");
             std::process::exit(1);
         }
@@ -89,11 +180,14 @@ impl ExecutionContext {
     }
 }
 
+// Clones of an executor share the same context, so one executor can serve
+// every client connection.
 #[derive(Clone)]
 pub struct Executor {
     pub context: ExecutionContext,
     pub setup_properly: bool,
     dump_file_path: &'static str,
+    save_lock: Arc<Mutex<()>>,
 }
 
 pub fn setup_executor(debug_mode: bool) -> Executor {
@@ -108,7 +202,7 @@ pub fn setup_executor(debug_mode: bool) -> Executor {
 
     let path = path::Path::new(&dump_file_path);
     if !path.exists() {
-        if fs::File::create(&dump_file_path).is_err() {
+        if fs::File::create(dump_file_path).is_err() {
             eprintln!("[ERROR]: Cannot create a dump file! \nHINT: This can usually be resolved by running the program again.");
             std::process::exit(1);
         } else {
@@ -121,11 +215,31 @@ pub fn setup_executor(debug_mode: bool) -> Executor {
     },
     "hashes": {
 
+    },
+    "sets": {
+
+    },
+    "sorted_sets": {
+
+    },
+    "streams": {
+
+    },
+    "expires": {
+
     }
 }"#;
-            fs::write(&dump_file_path, setup_data).unwrap();
+            fs::write(dump_file_path, setup_data).unwrap();
+
+            // nothing to load on the first start, so begin with an empty keyspace
+            return Executor {
+                context: ExecutionContext::new(),
+                setup_properly: true,
+                dump_file_path,
+                save_lock: Arc::new(Mutex::new(())),
+            };
         }
-    } else if let Ok(text) = fs::read_to_string(&dump_file_path) {
+    } else if let Ok(text) = fs::read_to_string(dump_file_path) {
         let context = ExecutionContext::from_file_contents(text);
         setup_properly = true;
 
@@ -134,6 +248,7 @@ pub fn setup_executor(debug_mode: bool) -> Executor {
                 context,
                 setup_properly,
                 dump_file_path,
+                save_lock: Arc::new(Mutex::new(())),
             };
         }
     } else {
@@ -149,10 +264,11 @@ impl Executor {
             context: ExecutionContext::new(),
             setup_properly: false,
             dump_file_path: "",
+            save_lock: Arc::new(Mutex::new(())),
         }
     }
 
-    fn exec_one_off(&self, command: &str, args: Vec<&str>) -> command_execution::Result {
+    fn exec_one_off(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
         match command {
             "echo" => one_off::echo(args),
             "ping" => one_off::ping(args),
@@ -161,7 +277,7 @@ impl Executor {
                 Delete all the keys of all the existing databases, not just the currently selected one.
                 This command never fails.
                  */
-                Ok("(nil)".to_string())
+                Ok(Reply::Nil)
             }
             _ => {
                 panic!("This will never be reached");
@@ -169,16 +285,44 @@ impl Executor {
         }
     }
 
-    fn exec_kvp_command(&self, command: &str, args: Vec<&str>) -> command_execution::Result {
-        let ctx = &self.context.key_value_pairs;
+    fn exec_kvp_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        let ctx = &self.context.keyspace;
         match command {
             "set" => kvp::set(&mut ctx.lock().unwrap(), args),
+            "setnx" => kvp::setnx(&mut ctx.lock().unwrap(), args),
+            "setex" => kvp::setex(&mut ctx.lock().unwrap(), args),
+            "psetex" => kvp::psetex(&mut ctx.lock().unwrap(), args),
             "get" => kvp::get(&mut ctx.lock().unwrap(), args),
+            "getset" => kvp::getset(&mut ctx.lock().unwrap(), args),
+            "getdel" => kvp::getdel(&mut ctx.lock().unwrap(), args),
+            "getex" => kvp::getex(&mut ctx.lock().unwrap(), args),
+            "incr" => kvp::incr(&mut ctx.lock().unwrap(), args),
+            "decr" => kvp::decr(&mut ctx.lock().unwrap(), args),
+            "incrby" => kvp::incrby(&mut ctx.lock().unwrap(), args),
+            "decrby" => kvp::decrby(&mut ctx.lock().unwrap(), args),
+            "incrbyfloat" => kvp::incrbyfloat(&mut ctx.lock().unwrap(), args),
+            "append" => kvp::append(&mut ctx.lock().unwrap(), args),
+            "strlen" => kvp::strlen(&mut ctx.lock().unwrap(), args),
+            "getrange" => kvp::getrange(&mut ctx.lock().unwrap(), args),
+            "setrange" => kvp::setrange(&mut ctx.lock().unwrap(), args),
+            "lcs" => kvp::lcs(&mut ctx.lock().unwrap(), args),
+            // the multi key commands hold the lock for all of their keys, so they are atomic
+            "mget" => kvp::mget(&mut ctx.lock().unwrap(), args),
+            "mset" => kvp::mset(&mut ctx.lock().unwrap(), args),
+            "msetnx" => kvp::msetnx(&mut ctx.lock().unwrap(), args),
             "key" => kvp::key(&mut ctx.lock().unwrap(), args),
             "type" => kvp::r#type(&mut ctx.lock().unwrap(), args),
             "del" => kvp::del(&mut ctx.lock().unwrap(), args),
-            "unlink" => kvp::unlink(ctx, args.iter().map(|x| x.to_string()).collect()),
-            "expire" => kvp::expire(ctx, args.iter().map(|x| x.to_string()).collect()),
+            "unlink" => kvp::unlink(ctx, args.iter().map(|x| x.as_text().to_string()).collect()),
+            "expire" => kvp::expire(&mut ctx.lock().unwrap(), args),
+            "pexpire" => kvp::pexpire(&mut ctx.lock().unwrap(), args),
+            "expireat" => kvp::expireat(&mut ctx.lock().unwrap(), args),
+            "pexpireat" => kvp::pexpireat(&mut ctx.lock().unwrap(), args),
+            "ttl" => kvp::ttl(&mut ctx.lock().unwrap(), args),
+            "pttl" => kvp::pttl(&mut ctx.lock().unwrap(), args),
+            "expiretime" => kvp::expiretime(&mut ctx.lock().unwrap(), args),
+            "pexpiretime" => kvp::pexpiretime(&mut ctx.lock().unwrap(), args),
+            "persist" => kvp::persist(&mut ctx.lock().unwrap(), args),
             "rename" => kvp::rename(&mut ctx.lock().unwrap(), args),
             _ => {
                 panic!("This will never be reached");
@@ -186,33 +330,154 @@ impl Executor {
         }
     }
 
-    fn exec_list_command(&self, command: &str, args: Vec<&str>) -> command_execution::Result {
+    fn exec_hyperloglog_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "pfadd" => hyperloglog::pfadd(&mut self.context.keyspace.lock().unwrap(), args),
+            "pfcount" => hyperloglog::pfcount(&mut self.context.keyspace.lock().unwrap(), args),
+            "pfmerge" => hyperloglog::pfmerge(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_list_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "llen" => list::llen(&mut self.context.keyspace.lock().unwrap(), args),
+            "lrem" => list::lrem(&mut self.context.keyspace.lock().unwrap(), args),
+            "lindex" => list::lindex(&mut self.context.keyspace.lock().unwrap(), args),
+            "lpop" => list::lpop(&mut self.context.keyspace.lock().unwrap(), args),
+            "rpop" => list::rpop(&mut self.context.keyspace.lock().unwrap(), args),
+            "lpush" => list::lpush(&mut self.context.keyspace.lock().unwrap(), args),
+            "rpush" => list::rpush(&mut self.context.keyspace.lock().unwrap(), args),
+            "lset" => list::lset(&mut self.context.keyspace.lock().unwrap(), args),
+            "lrange" => list::lrange(&mut self.context.keyspace.lock().unwrap(), args),
+            "ltrim" => list::ltrim(&mut self.context.keyspace.lock().unwrap(), args),
+            "linsert" => list::linsert(&mut self.context.keyspace.lock().unwrap(), args),
+            "lpos" => list::lpos(&mut self.context.keyspace.lock().unwrap(), args),
+            "lmove" => list::lmove(&mut self.context.keyspace.lock().unwrap(), args),
+            "rpoplpush" => list::rpoplpush(&mut self.context.keyspace.lock().unwrap(), args),
+            "lmpop" => list::lmpop(&mut self.context.keyspace.lock().unwrap(), args),
+            "lpushx" => list::lpushx(&mut self.context.keyspace.lock().unwrap(), args),
+            "rpushx" => list::rpushx(&mut self.context.keyspace.lock().unwrap(), args),
+            "blpop" => list::blpop(&mut self.context.keyspace.lock().unwrap(), args),
+            "brpop" => list::brpop(&mut self.context.keyspace.lock().unwrap(), args),
+            "blmove" => list::blmove(&mut self.context.keyspace.lock().unwrap(), args),
+            "blmpop" => list::blmpop(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_hash_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
         match command {
-            "llen" => list::llen(&mut self.context.lists.lock().unwrap(), args),
-            "lrem" => list::lrem(&mut self.context.lists.lock().unwrap(), args),
-            "lindex" => list::lindex(&mut self.context.lists.lock().unwrap(), args),
-            "lpop" => list::lpop(&mut self.context.lists.lock().unwrap(), args),
-            "rpop" => list::rpop(&mut self.context.lists.lock().unwrap(), args),
-            "lpush" => list::lpush(&mut self.context.lists.lock().unwrap(), args),
-            "rpush" => list::rpush(&mut self.context.lists.lock().unwrap(), args),
-            "lset" => list::lset(&mut self.context.lists.lock().unwrap(), args),
+            "hget" => hash::hget(&mut self.context.keyspace.lock().unwrap(), args),
+            "hexists" => hash::hexists(&mut self.context.keyspace.lock().unwrap(), args),
+            "hdel" => hash::hdel(&mut self.context.keyspace.lock().unwrap(), args),
+            "hgetall" => hash::hgetall(&mut self.context.keyspace.lock().unwrap(), args),
+            "hkeys" => hash::hkeys(&mut self.context.keyspace.lock().unwrap(), args),
+            "hlen" => hash::hlen(&mut self.context.keyspace.lock().unwrap(), args),
+            "hmset" => hash::hmset(&mut self.context.keyspace.lock().unwrap(), args),
+            "hset" => hash::hset(&mut self.context.keyspace.lock().unwrap(), args),
+            "hvals" => hash::hvals(&mut self.context.keyspace.lock().unwrap(), args),
             _ => {
                 panic!("This will never be reached");
             }
         }
     }
 
-    fn exec_hash_command(&self, command: &str, args: Vec<&str>) -> command_execution::Result {
+    fn exec_bit_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
         match command {
-            "hget" => hash::hget(&mut self.context.hashes.lock().unwrap(), args),
-            "hexists" => hash::hexists(&mut self.context.hashes.lock().unwrap(), args),
-            "hdel" => hash::hdel(&mut self.context.hashes.lock().unwrap(), args),
-            "hgetall" => hash::hgetall(&mut self.context.hashes.lock().unwrap(), args),
-            "hkeys" => hash::hkeys(&mut self.context.hashes.lock().unwrap(), args),
-            "hlen" => hash::hlen(&mut self.context.hashes.lock().unwrap(), args),
-            "hmset" => hash::hmset(&mut self.context.hashes.lock().unwrap(), args),
-            "hset" => hash::hset(&mut self.context.hashes.lock().unwrap(), args),
-            "hvals" => hash::hvals(&mut self.context.hashes.lock().unwrap(), args),
+            "setbit" => bit::setbit(&mut self.context.keyspace.lock().unwrap(), args),
+            "getbit" => bit::getbit(&mut self.context.keyspace.lock().unwrap(), args),
+            "bitcount" => bit::bitcount(&mut self.context.keyspace.lock().unwrap(), args),
+            "bitpos" => bit::bitpos(&mut self.context.keyspace.lock().unwrap(), args),
+            "bitop" => bit::bitop(&mut self.context.keyspace.lock().unwrap(), args),
+            "bitfield" => bit::bitfield(&mut self.context.keyspace.lock().unwrap(), args),
+            "bitfield_ro" => bit::bitfield_ro(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_set_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "sadd" => set::sadd(&mut self.context.keyspace.lock().unwrap(), args),
+            "srem" => set::srem(&mut self.context.keyspace.lock().unwrap(), args),
+            "smembers" => set::smembers(&mut self.context.keyspace.lock().unwrap(), args),
+            "sismember" => set::sismember(&mut self.context.keyspace.lock().unwrap(), args),
+            "smismember" => set::smismember(&mut self.context.keyspace.lock().unwrap(), args),
+            "scard" => set::scard(&mut self.context.keyspace.lock().unwrap(), args),
+            "spop" => set::spop(&mut self.context.keyspace.lock().unwrap(), args),
+            "srandmember" => set::srandmember(&mut self.context.keyspace.lock().unwrap(), args),
+            "smove" => set::smove(&mut self.context.keyspace.lock().unwrap(), args),
+            "sinter" => set::sinter(&mut self.context.keyspace.lock().unwrap(), args),
+            "sunion" => set::sunion(&mut self.context.keyspace.lock().unwrap(), args),
+            "sdiff" => set::sdiff(&mut self.context.keyspace.lock().unwrap(), args),
+            "sinterstore" => set::sinterstore(&mut self.context.keyspace.lock().unwrap(), args),
+            "sunionstore" => set::sunionstore(&mut self.context.keyspace.lock().unwrap(), args),
+            "sdiffstore" => set::sdiffstore(&mut self.context.keyspace.lock().unwrap(), args),
+            "sintercard" => set::sintercard(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_sorted_set_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "zadd" => sorted_set::zadd(&mut self.context.keyspace.lock().unwrap(), args),
+            "zrem" => sorted_set::zrem(&mut self.context.keyspace.lock().unwrap(), args),
+            "zscore" => sorted_set::zscore(&mut self.context.keyspace.lock().unwrap(), args),
+            "zincrby" => sorted_set::zincrby(&mut self.context.keyspace.lock().unwrap(), args),
+            "zcard" => sorted_set::zcard(&mut self.context.keyspace.lock().unwrap(), args),
+            "zcount" => sorted_set::zcount(&mut self.context.keyspace.lock().unwrap(), args),
+            "zrank" => sorted_set::zrank(&mut self.context.keyspace.lock().unwrap(), args),
+            "zrevrank" => sorted_set::zrevrank(&mut self.context.keyspace.lock().unwrap(), args),
+            "zrange" => sorted_set::zrange(&mut self.context.keyspace.lock().unwrap(), args),
+            "zrangestore" => sorted_set::zrangestore(&mut self.context.keyspace.lock().unwrap(), args),
+            "zpopmin" => sorted_set::zpopmin(&mut self.context.keyspace.lock().unwrap(), args),
+            "zpopmax" => sorted_set::zpopmax(&mut self.context.keyspace.lock().unwrap(), args),
+            "zunionstore" => sorted_set::zunionstore(&mut self.context.keyspace.lock().unwrap(), args),
+            "zinterstore" => sorted_set::zinterstore(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_geo_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "geoadd" => geo::geoadd(&mut self.context.keyspace.lock().unwrap(), args),
+            "geopos" => geo::geopos(&mut self.context.keyspace.lock().unwrap(), args),
+            "geodist" => geo::geodist(&mut self.context.keyspace.lock().unwrap(), args),
+            "geohash" => geo::geohash(&mut self.context.keyspace.lock().unwrap(), args),
+            "geosearch" => geo::geosearch(&mut self.context.keyspace.lock().unwrap(), args),
+            "geosearchstore" => geo::geosearchstore(&mut self.context.keyspace.lock().unwrap(), args),
+            _ => {
+                panic!("This will never be reached");
+            }
+        }
+    }
+
+    fn exec_stream_command(&self, command: &str, args: Vec<&[u8]>) -> command_execution::Result {
+        match command {
+            "xadd" => stream_cmd::xadd(&mut self.context.keyspace.lock().unwrap(), args),
+            "xlen" => stream_cmd::xlen(&mut self.context.keyspace.lock().unwrap(), args),
+            "xrange" => stream_cmd::xrange(&mut self.context.keyspace.lock().unwrap(), args),
+            "xrevrange" => stream_cmd::xrevrange(&mut self.context.keyspace.lock().unwrap(), args),
+            "xdel" => stream_cmd::xdel(&mut self.context.keyspace.lock().unwrap(), args),
+            "xtrim" => stream_cmd::xtrim(&mut self.context.keyspace.lock().unwrap(), args),
+            "xread" => stream_cmd::xread(&mut self.context.keyspace.lock().unwrap(), args),
+            "xgroup" => stream_cmd::xgroup(&mut self.context.keyspace.lock().unwrap(), args),
+            "xreadgroup" => stream_cmd::xreadgroup(&mut self.context.keyspace.lock().unwrap(), args),
+            "xack" => stream_cmd::xack(&mut self.context.keyspace.lock().unwrap(), args),
+            "xpending" => stream_cmd::xpending(&mut self.context.keyspace.lock().unwrap(), args),
+            "xclaim" => stream_cmd::xclaim(&mut self.context.keyspace.lock().unwrap(), args),
+            "xautoclaim" => stream_cmd::xautoclaim(&mut self.context.keyspace.lock().unwrap(), args),
+            "xinfo" => stream_cmd::xinfo(&mut self.context.keyspace.lock().unwrap(), args),
             _ => {
                 panic!("This will never be reached");
             }
@@ -220,61 +485,101 @@ impl Executor {
     }
 
     pub fn exec(&self, command: String) -> command_execution::Result {
-        let mut command_words = command.split(' ');
-        let clone = command_words.next().unwrap().to_lowercase().clone();
+        self.exec_args(split_args(command.as_bytes())?)
+    }
+
+    pub fn exec_args(&self, args: Vec<Vec<u8>>) -> command_execution::Result {
+        if args.is_empty() {
+            return Err("[ERROR]: No command provided.".to_string());
+        }
+
+        let clone = args[0].as_text().to_lowercase();
         let cmd_name = clone.as_str();
-        let cmd_args = command_words.collect::<Vec<_>>();
+        let cmd_args = args[1..].iter().map(|x| x.as_slice()).collect::<Vec<_>>();
 
         match cmd_name {
             "echo" | "ping" | "flushall" => self.exec_one_off(cmd_name, cmd_args),
-            "set" | "get" | "key" | "type" | "del" | "unlink" | "expire" | "rename" => {
+            "set" | "get" | "key" | "type" | "del" | "unlink" | "expire" | "rename" | "pexpire" | "expireat"
+            | "pexpireat" | "ttl" | "pttl" | "expiretime" | "pexpiretime" | "persist" | "setnx" | "setex"
+            | "psetex" | "getset" | "getdel" | "getex" | "incr" | "decr" | "incrby" | "decrby" | "incrbyfloat"
+            | "append" | "strlen" | "getrange" | "setrange" | "lcs" | "mget" | "mset" | "msetnx" => {
                 self.exec_kvp_command(cmd_name, cmd_args)
             }
-            "llen" | "lrem" | "lindex" | "lpop" | "rpop" | "lpush" | "rpush" | "lset" => {
+            "pfadd" | "pfcount" | "pfmerge" => self.exec_hyperloglog_command(cmd_name, cmd_args),
+            "llen" | "lrem" | "lindex" | "lpop" | "rpop" | "lpush" | "rpush" | "lset" | "lrange" | "ltrim"
+            | "linsert" | "lpos" | "lmove" | "rpoplpush" | "lmpop" | "lpushx" | "rpushx" | "blpop" | "brpop"
+            | "blmove" | "blmpop" => {
                 self.exec_list_command(cmd_name, cmd_args)
             }
             "hget" | "hexists" | "hdel" | "hgetall" | "hkeys" | "hlen" | "hmset" | "hset" | "hvals" => {
                 self.exec_hash_command(cmd_name, cmd_args)
             },
+            "setbit" | "getbit" | "bitcount" | "bitpos" | "bitop" | "bitfield" | "bitfield_ro" => {
+                self.exec_bit_command(cmd_name, cmd_args)
+            }
+            "sadd" | "srem" | "smembers" | "sismember" | "smismember" | "scard" | "spop" | "srandmember" | "smove"
+            | "sinter" | "sunion" | "sdiff" | "sinterstore" | "sunionstore" | "sdiffstore" | "sintercard" => {
+                self.exec_set_command(cmd_name, cmd_args)
+            }
+            "zadd" | "zrem" | "zscore" | "zincrby" | "zcard" | "zcount" | "zrank" | "zrevrank" | "zrange" | "zrangestore"
+            | "zpopmin" | "zpopmax" | "zunionstore" | "zinterstore" => {
+                self.exec_sorted_set_command(cmd_name, cmd_args)
+            }
+            "geoadd" | "geopos" | "geodist" | "geohash" | "geosearch" | "geosearchstore" => {
+                self.exec_geo_command(cmd_name, cmd_args)
+            }
+            "xadd" | "xlen" | "xrange" | "xrevrange" | "xdel" | "xtrim" | "xread" | "xgroup" | "xreadgroup" | "xack"
+            | "xpending" | "xclaim" | "xautoclaim" | "xinfo" => {
+                self.exec_stream_command(cmd_name, cmd_args)
+            }
             _ => Err(format!("Unknown command \"{cmd_name}\" provided.")),
         }
     }
 
-    pub fn expire_value(&self, key: &str) -> command_execution::Result {
-        self.exec_kvp_command("del", vec![key])
+    // Deletes a sample of the keys whose TTL has run out, see `Keyspace::active_expire_cycle`
+    pub fn active_expire_cycle(&self) -> usize {
+        self.context.keyspace.lock().unwrap().active_expire_cycle()
+    }
+
+    // Saves into another dump file from now on, e.g. so the tests that go through the server
+    // leave the debug dump alone
+    pub fn with_dump_file(mut self, dump_file_path: &'static str) -> Self {
+        self.dump_file_path = dump_file_path;
+        self
     }
 
     pub fn save(&self) {
+        // clients can trigger saves concurrently, so make sure they don't write over each other
+        let _guard = self.save_lock.lock().unwrap();
         let string_value = self.context.to_string();
 
         let path = std::path::Path::new(&self.dump_file_path);
         if !path.exists() {
-            if std::fs::File::create(&self.dump_file_path).is_err() {
+            if std::fs::File::create(self.dump_file_path).is_err() {
                 eprintln!("[ERROR]: Cannot create a dump file!");
             }
         } else {
-            std::fs::write(&self.dump_file_path, &string_value).unwrap();
+            std::fs::write(self.dump_file_path, &string_value).unwrap();
         }
     }
-}
 
-pub static mut EXPIRY_LIST: Vec<ScheduledExpiry> = vec![];
+    // What the command would block on when there's nothing for it to pop, None if it never blocks
+    pub fn blocking(&self, args: &[Vec<u8>]) -> Option<Blocking> {
+        let args: Vec<&[u8]> = args.iter().map(|arg| arg.as_slice()).collect();
+        let (command, args) = args.split_first()?;
 
-#[derive(Debug)]
-pub struct ScheduledExpiry {
-    pub start: std::time::SystemTime,
-    pub wait_time: u64,
-    pub key: String,
-}
+        list::blocking(&command.as_text().to_lowercase(), args)
+    }
 
-impl ScheduledExpiry {
-    pub fn create(start: std::time::SystemTime, wait_time: u64, key: String) {
-        unsafe {
-            EXPIRY_LIST.push(Self {
-                start,
-                wait_time,
-                key,
-            })
-        }
+    pub fn block_on(&self, keys: &[String]) {
+        self.context.keyspace.lock().unwrap().block_on(keys);
+    }
+
+    pub fn unblock_from(&self, keys: &[String]) {
+        self.context.keyspace.lock().unwrap().unblock_from(keys);
+    }
+
+    pub fn take_ready_keys(&self) -> HashSet<String> {
+        self.context.keyspace.lock().unwrap().take_ready_keys()
     }
 }
@@ -0,0 +1,360 @@
+// RESP (REdis Serialization Protocol) wire layer.
+// https://redis.io/docs/reference/protocol-spec/
+
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+const MAX_ARRAY_LENGTH: i64 = 1024 * 1024;
+
+// Ok(None) means that the buffer does not hold a complete frame yet, otherwise we get the
+// parsed frame and the number of bytes it occupied.
+pub type ParseResult<T> = core::result::Result<Option<(T, usize)>, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    pub fn from_version(version: i64) -> Option<Self> {
+        match version {
+            2 => Some(Protocol::Resp2),
+            3 => Some(Protocol::Resp3),
+            _ => None,
+        }
+    }
+
+    pub fn version(&self) -> i64 {
+        match self {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RespValue {
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Vec<u8>),
+    Array(Vec<RespValue>),
+    // "$-1\r\n", the nil reply of RESP2
+    Nil,
+    // "*-1\r\n"
+    NilArray,
+
+    // RESP3 only types, which get downgraded to their RESP2 equivalents when
+    // the connection has not been switched over with HELLO.
+    // https://github.com/antirez/RESP3/blob/master/spec.md
+    Null,
+    Map(Vec<(RespValue, RespValue)>),
+    Set(Vec<RespValue>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    Verbatim { format: String, text: Vec<u8> },
+    Push(Vec<RespValue>),
+    Attribute {
+        attributes: Vec<(RespValue, RespValue)>,
+        value: Box<RespValue>,
+    },
+}
+
+impl RespValue {
+    pub fn bulk(value: &str) -> Self {
+        RespValue::BulkString(value.as_bytes().to_vec())
+    }
+
+    pub fn error(message: &str) -> Self {
+        RespValue::Error(error_message(message))
+    }
+
+    pub fn encode(&self, protocol: Protocol) -> Vec<u8> {
+        let mut out = vec![];
+        self.write_to(&mut out, protocol);
+
+        out
+    }
+
+    fn write_to(&self, out: &mut Vec<u8>, protocol: Protocol) {
+        use Protocol::*;
+
+        match (self, protocol) {
+            (RespValue::SimpleString(s), _) => write_line(out, b'+', s),
+            (RespValue::Error(e), _) => write_line(out, b'-', e),
+            (RespValue::Integer(n), _) => write_line(out, b':', &n.to_string()),
+            (RespValue::BulkString(bytes), _) => write_blob(out, b'$', bytes),
+            (RespValue::Array(items), _) => write_aggregate(out, b'*', items, protocol),
+            (RespValue::Nil | RespValue::Null, Resp2) => out.extend_from_slice(b"$-1\r\n"),
+            (RespValue::NilArray, Resp2) => out.extend_from_slice(b"*-1\r\n"),
+            (RespValue::Nil | RespValue::NilArray | RespValue::Null, Resp3) => {
+                out.extend_from_slice(b"_\r\n")
+            }
+            (RespValue::Map(pairs), Resp2) => {
+                write_line(out, b'*', &(pairs.len() * 2).to_string());
+                for (k, v) in pairs {
+                    k.write_to(out, protocol);
+                    v.write_to(out, protocol);
+                }
+            }
+            (RespValue::Map(pairs), Resp3) => {
+                write_line(out, b'%', &pairs.len().to_string());
+                for (k, v) in pairs {
+                    k.write_to(out, protocol);
+                    v.write_to(out, protocol);
+                }
+            }
+            (RespValue::Set(items), Resp2) | (RespValue::Push(items), Resp2) => {
+                write_aggregate(out, b'*', items, protocol)
+            }
+            (RespValue::Set(items), Resp3) => write_aggregate(out, b'~', items, protocol),
+            (RespValue::Push(items), Resp3) => write_aggregate(out, b'>', items, protocol),
+            (RespValue::Double(d), Resp2) => write_blob(out, b'$', format_double(*d).as_bytes()),
+            (RespValue::Double(d), Resp3) => write_line(out, b',', &format_double(*d)),
+            (RespValue::Boolean(b), Resp2) => write_line(out, b':', if *b { "1" } else { "0" }),
+            (RespValue::Boolean(b), Resp3) => write_line(out, b'#', if *b { "t" } else { "f" }),
+            (RespValue::BigNumber(n), Resp2) => write_blob(out, b'$', n.as_bytes()),
+            (RespValue::BigNumber(n), Resp3) => write_line(out, b'(', n),
+            (RespValue::Verbatim { text, .. }, Resp2) => write_blob(out, b'$', text),
+            (RespValue::Verbatim { format, text }, Resp3) => {
+                let mut blob = format.as_bytes().to_vec();
+                blob.push(b':');
+                blob.extend_from_slice(text);
+                write_blob(out, b'=', &blob);
+            }
+            (RespValue::Attribute { value, .. }, Resp2) => value.write_to(out, protocol),
+            (RespValue::Attribute { attributes, value }, Resp3) => {
+                write_line(out, b'|', &attributes.len().to_string());
+                for (k, v) in attributes {
+                    k.write_to(out, protocol);
+                    v.write_to(out, protocol);
+                }
+                value.write_to(out, protocol);
+            }
+        }
+    }
+}
+
+pub(crate) fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{}", d)
+    }
+}
+
+fn write_blob(out: &mut Vec<u8>, prefix: u8, bytes: &[u8]) {
+    write_line(out, prefix, &bytes.len().to_string());
+    out.extend_from_slice(bytes);
+    out.extend_from_slice(b"\r\n");
+}
+
+fn write_aggregate(out: &mut Vec<u8>, prefix: u8, items: &[RespValue], protocol: Protocol) {
+    write_line(out, prefix, &items.len().to_string());
+    for item in items {
+        item.write_to(out, protocol);
+    }
+}
+
+fn write_line(out: &mut Vec<u8>, prefix: u8, line: &str) {
+    out.push(prefix);
+    // a simple string or an error cannot span multiple lines
+    out.extend(line.bytes().map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b }));
+    out.extend_from_slice(b"\r\n");
+}
+
+fn error_message(message: &str) -> String {
+    // Engine errors look like "[ERROR]: <text>", while RESP clients expect an
+    // upper-case error code as the first word, e.g. "ERR <text>".
+    let text = message.strip_prefix("[ERROR]:").unwrap_or(message).trim_start();
+    let code = text.split(' ').next().unwrap_or("");
+
+    if code.len() > 1 && code.bytes().all(|b| b.is_ascii_uppercase()) {
+        text.to_string()
+    } else {
+        format!("ERR {text}")
+    }
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+fn parse_length(line: &[u8]) -> core::result::Result<i64, String> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "Protocol error: invalid length".to_string())
+}
+
+fn parse_blob(buf: &[u8], line: &[u8], consumed: usize) -> ParseResult<Vec<u8>> {
+    let len = parse_length(line)?;
+    if !(0..=MAX_BULK_LENGTH).contains(&len) {
+        return Err("Protocol error: invalid bulk length".to_string());
+    }
+
+    let len = len as usize;
+    if buf.len() < consumed + len + 2 {
+        return Ok(None);
+    }
+    if &buf[consumed + len..consumed + len + 2] != b"\r\n" {
+        return Err("Protocol error: expected '\\r\\n' after bulk string".to_string());
+    }
+
+    Ok(Some((buf[consumed..consumed + len].to_vec(), consumed + len + 2)))
+}
+
+fn parse_items(buf: &[u8], count: usize, mut consumed: usize) -> ParseResult<Vec<RespValue>> {
+    let mut items = vec![];
+    for _ in 0..count {
+        match parse(&buf[consumed..])? {
+            Some((item, used)) => {
+                items.push(item);
+                consumed += used;
+            }
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some((items, consumed)))
+}
+
+fn parse_aggregate_length(line: &[u8]) -> core::result::Result<usize, String> {
+    let len = parse_length(line)?;
+    if !(0..=MAX_ARRAY_LENGTH).contains(&len) {
+        return Err("Protocol error: invalid multibulk length".to_string());
+    }
+
+    Ok(len as usize)
+}
+
+fn into_pairs(items: Vec<RespValue>) -> Vec<(RespValue, RespValue)> {
+    let mut pairs = vec![];
+    let mut iter = items.into_iter();
+    while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+        pairs.push((k, v));
+    }
+
+    pairs
+}
+
+// Parses a single RESP2 or RESP3 value from the start of `buf`.
+pub fn parse(buf: &[u8]) -> ParseResult<RespValue> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let line_end = match find_crlf(buf) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let line = &buf[1..line_end];
+    let text = || String::from_utf8_lossy(line).to_string();
+    let consumed = line_end + 2;
+
+    let parsed = match buf[0] {
+        b'+' => Some((RespValue::SimpleString(text()), consumed)),
+        b'-' => Some((RespValue::Error(text()), consumed)),
+        b':' => Some((RespValue::Integer(parse_length(line)?), consumed)),
+        b'_' => Some((RespValue::Null, consumed)),
+        b'#' => match line {
+            b"t" => Some((RespValue::Boolean(true), consumed)),
+            b"f" => Some((RespValue::Boolean(false), consumed)),
+            _ => return Err("Protocol error: invalid boolean".to_string()),
+        },
+        b',' => {
+            let d = match text().as_str() {
+                "inf" => f64::INFINITY,
+                "-inf" => f64::NEG_INFINITY,
+                other => other
+                    .parse::<f64>()
+                    .map_err(|_| "Protocol error: invalid double".to_string())?,
+            };
+            Some((RespValue::Double(d), consumed))
+        }
+        b'(' => Some((RespValue::BigNumber(text()), consumed)),
+        b'$' if line == b"-1" => Some((RespValue::Nil, consumed)),
+        b'$' => parse_blob(buf, line, consumed)?.map(|(b, c)| (RespValue::BulkString(b), c)),
+        b'!' => parse_blob(buf, line, consumed)?
+            .map(|(b, c)| (RespValue::Error(String::from_utf8_lossy(&b).to_string()), c)),
+        b'=' => parse_blob(buf, line, consumed)?.map(|(b, c)| {
+            // the first three bytes are the format, followed by a ':'
+            let (format, text) = if b.len() >= 4 && b[3] == b':' {
+                (String::from_utf8_lossy(&b[..3]).to_string(), b[4..].to_vec())
+            } else {
+                ("txt".to_string(), b)
+            };
+            (RespValue::Verbatim { format, text }, c)
+        }),
+        b'*' if line == b"-1" => Some((RespValue::NilArray, consumed)),
+        b'*' => parse_items(buf, parse_aggregate_length(line)?, consumed)?
+            .map(|(items, c)| (RespValue::Array(items), c)),
+        b'~' => parse_items(buf, parse_aggregate_length(line)?, consumed)?
+            .map(|(items, c)| (RespValue::Set(items), c)),
+        b'>' => parse_items(buf, parse_aggregate_length(line)?, consumed)?
+            .map(|(items, c)| (RespValue::Push(items), c)),
+        b'%' => parse_items(buf, parse_aggregate_length(line)? * 2, consumed)?
+            .map(|(items, c)| (RespValue::Map(into_pairs(items)), c)),
+        b'|' => match parse_items(buf, parse_aggregate_length(line)? * 2, consumed)? {
+            // an attribute is always followed by the value it describes
+            Some((items, c)) => parse(&buf[c..])?.map(|(value, used)| {
+                (
+                    RespValue::Attribute {
+                        attributes: into_pairs(items),
+                        value: Box::new(value),
+                    },
+                    c + used,
+                )
+            }),
+            None => None,
+        },
+        other => {
+            return Err(format!(
+                "Protocol error: unexpected type byte '{}'",
+                other as char
+            ))
+        }
+    };
+
+    Ok(parsed)
+}
+
+// Parses a client request, which is either a RESP array of bulk strings or an
+// inline command terminated by a newline, the way telnet sends it.
+pub fn parse_command(buf: &[u8]) -> ParseResult<Vec<Vec<u8>>> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    if buf[0] != b'*' {
+        let line_end = match buf.iter().position(|&b| b == b'\n') {
+            Some(pos) => pos,
+            None => return Ok(None),
+        };
+
+        let args = super::util::split_args(&buf[..line_end])?;
+
+        return Ok(Some((args, line_end + 1)));
+    }
+
+    match parse(buf)? {
+        Some((RespValue::Array(items), consumed)) => {
+            let mut args = vec![];
+            for item in items {
+                if let RespValue::BulkString(bytes) = item {
+                    args.push(bytes);
+                } else {
+                    return Err("Protocol error: expected bulk string".to_string());
+                }
+            }
+
+            Ok(Some((args, consumed)))
+        }
+        Some((RespValue::NilArray, consumed)) => Ok(Some((vec![], consumed))),
+        Some(_) => Err("Protocol error: expected array".to_string()),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+// Entry IDs are the unix time in milliseconds the entry was added at,
+// plus a sequence number for entries added within the same millisecond
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    // Parses "ms-seq", or just "ms" in which case the sequence number is `default_seq`
+    pub fn parse(text: &str, default_seq: u64) -> Option<Self> {
+        match text.split_once('-') {
+            Some((ms, seq)) => Some(Self { ms: ms.parse().ok()?, seq: seq.parse().ok()? }),
+            None => Some(Self { ms: text.parse().ok()?, seq: default_seq }),
+        }
+    }
+
+    pub fn next(self) -> Option<Self> {
+        if self.seq < u64::MAX {
+            Some(Self { ms: self.ms, seq: self.seq + 1 })
+        } else if self.ms < u64::MAX {
+            Some(Self { ms: self.ms + 1, seq: 0 })
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+// Field-value pairs of an entry, in the order they were given
+pub type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+// An entry that was delivered to a consumer of a group but hasn't been acknowledged yet
+#[derive(Clone, Debug)]
+pub struct PendingEntry {
+    pub consumer: String,
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConsumerGroup {
+    pub last_delivered_id: StreamId,
+    // consumer names along with the last time they were seen, as unix time in milliseconds
+    pub consumers: BTreeMap<String, u64>,
+    // the pending entries list (PEL) of the whole group
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+}
+
+impl ConsumerGroup {
+    pub fn pending_of<'a>(&'a self, consumer: &'a str) -> impl Iterator<Item = (&'a StreamId, &'a PendingEntry)> {
+        self.pending.iter().filter(move |(_, entry)| entry.consumer == consumer)
+    }
+}
+
+pub enum TrimStrategy {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Stream {
+    pub entries: BTreeMap<StreamId, Fields>,
+    pub last_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Works out the ID of a new entry from what XADD was given: "*", "ms-*" or an explicit ID
+    pub fn next_id(&self, requested: &str, now: u64) -> Result<StreamId, String> {
+        const INVALID_ID: &str = "[ERROR]: Invalid stream ID specified as stream command argument";
+        const TOO_SMALL: &str = "[ERROR]: The ID specified in XADD is equal or smaller than the target stream top item";
+
+        if requested == "*" {
+            if now > self.last_id.ms {
+                return Ok(StreamId { ms: now, seq: 0 });
+            }
+
+            return self
+                .last_id
+                .next()
+                .ok_or_else(|| "[ERROR]: The stream has exhausted the last possible ID, unable to add more items".to_string());
+        }
+
+        if let Some(ms) = requested.strip_suffix("-*") {
+            let ms = ms.parse::<u64>().map_err(|_| INVALID_ID.to_string())?;
+
+            return match ms.cmp(&self.last_id.ms) {
+                std::cmp::Ordering::Greater => Ok(StreamId { ms, seq: 0 }),
+                std::cmp::Ordering::Equal if self.last_id.seq < u64::MAX => Ok(StreamId { ms, seq: self.last_id.seq + 1 }),
+                _ => Err(TOO_SMALL.to_string()),
+            };
+        }
+
+        let id = StreamId::parse(requested, 0).ok_or_else(|| INVALID_ID.to_string())?;
+        if id == StreamId::MIN {
+            return Err("[ERROR]: The ID specified in XADD must be greater than 0-0".to_string());
+        }
+        if id <= self.last_id {
+            return Err(TOO_SMALL.to_string());
+        }
+
+        Ok(id)
+    }
+
+    // The ID has to come from `next_id`, so it is greater than every one before it
+    pub fn add(&mut self, id: StreamId, fields: Fields) {
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    pub fn remove(&mut self, id: StreamId) -> bool {
+        if self.entries.remove(&id).is_none() {
+            return false;
+        }
+
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        true
+    }
+
+    pub fn range(&self, start: Bound<StreamId>, end: Bound<StreamId>) -> impl DoubleEndedIterator<Item = (&StreamId, &Fields)> {
+        let empty = match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e)) | (Bound::Excluded(s), Bound::Included(e)) => s >= e,
+            _ => false,
+        };
+
+        // BTreeMap panics on backwards ranges, so those become an empty one
+        if empty {
+            return self.entries.range((Bound::Included(StreamId::MIN), Bound::Excluded(StreamId::MIN)));
+        }
+
+        self.entries.range((start, end))
+    }
+
+    // Evicts the oldest entries, but no more than `limit` of them. Returns how many got evicted.
+    pub fn trim(&mut self, strategy: &TrimStrategy, limit: usize) -> usize {
+        let mut evicted = 0;
+
+        while evicted < limit {
+            let oldest = match self.entries.keys().next() {
+                Some(id) => *id,
+                None => break,
+            };
+
+            let keep = match strategy {
+                TrimStrategy::MaxLen(max_len) => self.entries.len() <= *max_len,
+                TrimStrategy::MinId(min_id) => oldest >= *min_id,
+            };
+            if keep {
+                break;
+            }
+
+            self.entries.remove(&oldest);
+            evicted += 1;
+        }
+
+        evicted
+    }
+}
@@ -1,12 +1,126 @@
 #![allow(clippy::inherent_to_string)]
 
+// Splits an inline command into its arguments the way redis-cli does (sdssplitargs):
+// runs of whitespace separate the arguments, "double quotes" support escapes such as
+// \n or \x41 and 'single quotes' take everything literally except for \'.
+pub fn split_args(line: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let unbalanced = || "Protocol error: unbalanced quotes in request".to_string();
+    let mut args = vec![];
+    let mut i = 0;
+
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= line.len() {
+            return Ok(args);
+        }
+
+        let mut current = vec![];
+        let mut in_double_quotes = false;
+        let mut in_single_quotes = false;
+
+        loop {
+            if in_double_quotes {
+                match line.get(i) {
+                    None => return Err(unbalanced()),
+                    Some(b'\\') if i + 3 < line.len()
+                        && line[i + 1] == b'x'
+                        && line[i + 2].is_ascii_hexdigit()
+                        && line[i + 3].is_ascii_hexdigit() =>
+                    {
+                        let hex = std::str::from_utf8(&line[i + 2..i + 4]).unwrap();
+                        current.push(u8::from_str_radix(hex, 16).unwrap());
+                        i += 3;
+                    }
+                    Some(b'\\') if i + 1 < line.len() => {
+                        i += 1;
+                        current.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    }
+                    Some(b'"') => {
+                        // the closing quote must be followed by a space or nothing at all
+                        if i + 1 < line.len() && !line[i + 1].is_ascii_whitespace() {
+                            return Err(unbalanced());
+                        }
+                        in_double_quotes = false;
+                    }
+                    Some(&c) => current.push(c),
+                }
+            } else if in_single_quotes {
+                match line.get(i) {
+                    None => return Err(unbalanced()),
+                    Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                        i += 1;
+                        current.push(b'\'');
+                    }
+                    Some(b'\'') => {
+                        if i + 1 < line.len() && !line[i + 1].is_ascii_whitespace() {
+                            return Err(unbalanced());
+                        }
+                        in_single_quotes = false;
+                    }
+                    Some(&c) => current.push(c),
+                }
+            } else {
+                match line.get(i) {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double_quotes = true,
+                    Some(b'\'') => in_single_quotes = true,
+                    Some(&c) => current.push(c),
+                }
+            }
+            i += 1;
+        }
+
+        args.push(current);
+    }
+}
+
+// Command arguments are raw bytes, these helpers read them as text or numbers
+pub trait CommandArg {
+    fn as_text(&self) -> std::borrow::Cow<'_, str>;
+    fn parse<T: std::str::FromStr>(&self) -> Result<T, String>;
+}
+
+impl CommandArg for [u8] {
+    fn as_text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(self)
+    }
+
+    fn parse<T: std::str::FromStr>(&self) -> Result<T, String> {
+        std::str::from_utf8(self)
+            .ok()
+            .and_then(|text| text.parse::<T>().ok())
+            .ok_or_else(|| format!("\"{}\" is not a valid value", self.as_text()))
+    }
+}
+
+// Current unix time in milliseconds, which is what key deadlines are stored in
+pub fn unix_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 type IntegerType = i64;
 
+// Dump file key marking a value that is not valid UTF-8 and thus stored base64 encoded
+const BASE64_JSON_KEY: &str = "base64";
+
 #[derive(Clone)]
 pub struct RedisValue {
     is_int: bool,
     value_t1: Option<IntegerType>,
-    value_t2: Option<String>,
+    value_t2: Option<Vec<u8>>,
 }
 
 impl RedisValue {
@@ -14,7 +128,7 @@ impl RedisValue {
         Self {
             is_int: false,
             value_t1: None,
-            value_t2: Some(value)
+            value_t2: Some(value.into_bytes())
         }
     }
 
@@ -27,44 +141,87 @@ impl RedisValue {
     }
 
     pub fn from_str(value: &str) -> Self {
+        Self::from_bytes(value.as_bytes())
+    }
+
+    pub fn from_bytes(value: &[u8]) -> Self {
+        // only values that read back as the exact same bytes are stored as integers,
+        // otherwise e.g. "007" or "+7" would turn into "7"
         match value.parse::<IntegerType>() {
-            Ok(n) =>  RedisValue::from_int(n),
-            Err(_) => RedisValue::from_string(value.to_string())
+            Ok(n) if n.to_string().as_bytes() == value => RedisValue::from_int(n),
+            _ => Self {
+                is_int: false,
+                value_t1: None,
+                value_t2: Some(value.to_vec())
+            }
         }
     }
 
     pub fn from_json(value: &serde_json::Value) -> Self {
-        let typ = value.is_number();
-        let mut is_int = false;
-        let mut value_t1 = None;
-        let mut value_t2 = None;
-
-        if typ {
-            is_int = true;
-            value_t1 = value.as_i64();
+        use base64::Engine;
+
+        if value.is_number() {
+            RedisValue::from_int(value.as_i64().unwrap())
+        } else if let Some(encoded) = value[BASE64_JSON_KEY].as_str() {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .expect("Invalid base64 value in the dump file!");
+
+            RedisValue::from_bytes(&bytes)
         } else {
-            value_t2 = Some(value.as_str().unwrap().to_string());
+            RedisValue::from_string(value.as_str().unwrap().to_string())
         }
+    }
 
-        Self {
-            is_int,
-            value_t1,
-            value_t2
+    pub fn to_string(&self) -> String {
+        String::from_utf8_lossy(&self.to_bytes()).to_string()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.is_int {
+            format!("{}", &self.value_t1.unwrap()).into_bytes()
+        } else {
+            self.value_t2.as_ref().unwrap().clone()
         }
     }
 
-    pub fn to_string(&self) -> String {
+    // The bytes of the value, only integers have to be formatted into a new buffer
+    pub fn as_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         if self.is_int {
-            format!("{}", &self.value_t1.unwrap())
+            std::borrow::Cow::Owned(self.to_bytes())
         } else {
-            let clone = &self.value_t2.as_ref().unwrap();
-            clone.to_string()
+            std::borrow::Cow::Borrowed(self.value_t2.as_ref().unwrap())
+        }
+    }
+
+    // The stored bytes, to be changed in place. An integer gets turned into its digits first.
+    pub fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        if self.is_int {
+            *self = Self::from_string(self.to_string());
         }
+
+        self.value_t2.as_mut().unwrap()
     }
 
+    // The value as an integer, which also covers digits that were written as raw bytes, e.g. by SETBIT
+    pub fn as_int(&self) -> Option<IntegerType> {
+        if self.is_int {
+            return self.value_t1;
+        }
+
+        let bytes = self.value_t2.as_ref().unwrap();
+        // i64::MIN is the longest integer there is, with 20 characters
+        if bytes.len() > 20 {
+            return None;
+        }
+        match bytes.parse::<IntegerType>() {
+            Ok(n) if n.to_string().as_bytes() == bytes.as_slice() => Some(n),
+            _ => None,
+        }
+    }
 
     pub fn to_result_value(&self) -> crate::redis_engine::command_execution::Result {
-        Ok(self.to_string())
+        Ok(crate::redis_engine::Reply::Bulk(self.to_bytes()))
     }
 
     pub fn is_a_number(&self) -> bool {
@@ -78,7 +235,7 @@ impl RedisValue {
     pub fn string_value(&self) -> String {
         let cl = self.value_t2.as_ref().unwrap();
 
-        cl.to_string()
+        String::from_utf8_lossy(cl).to_string()
     }
 }
 
@@ -89,28 +246,36 @@ impl std::fmt::Debug for RedisValue {
         if self.is_int {
             write!(f, " {} (Integer) }}", self.value_t1.as_ref().unwrap())
         } else {
-            write!(f, " \"{}\" (String) }}", self.value_t2.as_ref().unwrap())
+            write!(f, " {:?} (String) }}", self.string_value())
         }
     }
 }
 
-use serde::ser::{Serialize, Serializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 
 impl Serialize for RedisValue {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        use base64::Engine;
+
         if self.is_a_number() {
             serializer.serialize_i64(self.value_t1.unwrap())
+        } else if let Ok(text) = std::str::from_utf8(self.value_t2.as_ref().unwrap()) {
+            serializer.serialize_str(text)
         } else {
-            let clone = &self.value_t2.as_ref().unwrap().clone();
-            serializer.serialize_str(clone)
+            // JSON strings can't hold arbitrary bytes
+            let encoded = base64::engine::general_purpose::STANDARD.encode(self.value_t2.as_ref().unwrap());
+            let mut map = serializer.serialize_map(Some(1))?;
+            map.serialize_entry(BASE64_JSON_KEY, &encoded)?;
+            map.end()
         }
     }
 }
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use super::{ConsumerGroup, PendingEntry, SortedSet, Stream, StreamId};
 
 pub fn get_kvps_from_json(key: String, value: &serde_json::Value) -> HashMap<String, RedisValue> {
     let mut map = HashMap::new();
@@ -122,14 +287,14 @@ pub fn get_kvps_from_json(key: String, value: &serde_json::Value) -> HashMap<Str
     map
 }
 
-pub fn get_lists_from_json(value: &serde_json::Value) -> HashMap<String, Vec<RedisValue>> {
+pub fn get_lists_from_json(value: &serde_json::Value) -> HashMap<String, VecDeque<RedisValue>> {
     let mut map = HashMap::new();
 
     for (k,v) in value["lists"].as_object().unwrap() {
         if let Some(arr) = v.as_array() {
-            let mut vector = Vec::new();
+            let mut vector = VecDeque::new();
             for i in arr {
-                vector.push(RedisValue::from_json(i))
+                vector.push_back(RedisValue::from_json(i))
             }
             map.insert(k.clone(), vector);
         } else {
@@ -158,3 +323,163 @@ pub fn get_hashes_from_json(value: &serde_json::Value) -> HashMap<String, HashMa
 
     map
 }
+
+// Set members, stored as arrays. Older dump files don't have this section at all.
+pub fn get_sets_from_json(value: &serde_json::Value) -> HashMap<String, HashSet<Vec<u8>>> {
+    let mut map = HashMap::new();
+
+    if let Some(obj) = value["sets"].as_object() {
+        for (k, v) in obj {
+            if let Some(arr) = v.as_array() {
+                map.insert(k.to_string(), arr.iter().map(|m| RedisValue::from_json(m).to_bytes()).collect());
+            } else {
+                panic!("Invalid value or format for set!");
+            }
+        }
+    }
+
+    map
+}
+
+// Members of sorted sets along with their scores, stored as [member, score] pairs.
+// Older dump files don't have this section at all.
+pub fn get_sorted_sets_from_json(value: &serde_json::Value) -> HashMap<String, SortedSet> {
+    let mut map = HashMap::new();
+
+    if let Some(obj) = value["sorted_sets"].as_object() {
+        for (k, v) in obj {
+            let mut zset = SortedSet::new();
+            for pair in v.as_array().expect("Invalid value or format for sorted set!") {
+                match pair.as_array().map(|pair| pair.as_slice()) {
+                    Some([member, score]) => {
+                        let score = score
+                            .as_str()
+                            .and_then(|score| score.parse::<f64>().ok())
+                            .expect("Invalid score for sorted set member!");
+                        zset.insert(RedisValue::from_json(member).to_bytes(), score);
+                    }
+                    _ => panic!("Invalid value or format for sorted set!"),
+                }
+            }
+
+            map.insert(k.to_string(), zset);
+        }
+    }
+
+    map
+}
+
+// Streams are written as
+// { "last_id", "max_deleted_id", "entries_added", "entries": [[id, [field, value, ...]], ...],
+//   "groups": { name: { "last_delivered_id", "consumers": { name: seen_time },
+//                       "pending": [[id, consumer, delivery_time, delivery_count], ...] } } }
+pub fn stream_to_json(stream: &Stream) -> serde_json::Value {
+    use serde_json::json;
+
+    let bytes = |b: &[u8]| serde_json::to_value(RedisValue::from_bytes(b)).unwrap();
+    let entries: Vec<_> = stream
+        .entries
+        .iter()
+        .map(|(id, fields)| {
+            let fields: Vec<_> = fields.iter().flat_map(|(f, v)| [bytes(f), bytes(v)]).collect();
+            json!([id.to_string(), fields])
+        })
+        .collect();
+
+    let mut groups = serde_json::Map::new();
+    for (name, group) in &stream.groups {
+        let pending: Vec<_> = group
+            .pending
+            .iter()
+            .map(|(id, entry)| json!([id.to_string(), entry.consumer, entry.delivery_time, entry.delivery_count]))
+            .collect();
+
+        groups.insert(
+            name.clone(),
+            json!({
+                "last_delivered_id": group.last_delivered_id.to_string(),
+                "consumers": group.consumers,
+                "pending": pending,
+            }),
+        );
+    }
+
+    json!({
+        "last_id": stream.last_id.to_string(),
+        "max_deleted_id": stream.max_deleted_id.to_string(),
+        "entries_added": stream.entries_added,
+        "entries": entries,
+        "groups": groups,
+    })
+}
+
+// Older dump files don't have the "streams" section at all
+pub fn get_streams_from_json(value: &serde_json::Value) -> HashMap<String, Stream> {
+    const INVALID: &str = "Invalid value or format for stream!";
+
+    let id = |v: &serde_json::Value| StreamId::parse(v.as_str().expect(INVALID), 0).expect(INVALID);
+    let mut map = HashMap::new();
+
+    if let Some(obj) = value["streams"].as_object() {
+        for (k, v) in obj {
+            let mut stream = Stream {
+                last_id: id(&v["last_id"]),
+                max_deleted_id: id(&v["max_deleted_id"]),
+                entries_added: v["entries_added"].as_u64().expect(INVALID),
+                ..Stream::default()
+            };
+
+            for entry in v["entries"].as_array().expect(INVALID) {
+                let fields = entry[1].as_array().expect(INVALID);
+                let fields = fields
+                    .chunks(2)
+                    .map(|pair| (RedisValue::from_json(&pair[0]).to_bytes(), RedisValue::from_json(&pair[1]).to_bytes()))
+                    .collect();
+                stream.entries.insert(id(&entry[0]), fields);
+            }
+
+            for (name, g) in v["groups"].as_object().expect(INVALID) {
+                let mut group = ConsumerGroup {
+                    last_delivered_id: id(&g["last_delivered_id"]),
+                    ..ConsumerGroup::default()
+                };
+
+                for (consumer, seen_time) in g["consumers"].as_object().expect(INVALID) {
+                    group.consumers.insert(consumer.clone(), seen_time.as_u64().expect(INVALID));
+                }
+                for entry in g["pending"].as_array().expect(INVALID) {
+                    let pending = PendingEntry {
+                        consumer: entry[1].as_str().expect(INVALID).to_string(),
+                        delivery_time: entry[2].as_u64().expect(INVALID),
+                        delivery_count: entry[3].as_u64().expect(INVALID),
+                    };
+                    group.pending.insert(id(&entry[0]), pending);
+                }
+
+                stream.groups.insert(name.clone(), group);
+            }
+
+            map.insert(k.to_string(), stream);
+        }
+    }
+
+    map
+}
+
+// Deadlines of the keys with a TTL, as unix time in milliseconds.
+// Older dump files don't have this section at all.
+pub fn get_expires_from_json(value: &serde_json::Value) -> HashMap<String, u64> {
+    let mut map = HashMap::new();
+
+    if let Some(obj) = value["expires"].as_object() {
+        for (k, v) in obj {
+            if let Some(at) = v.as_u64() {
+                map.insert(k.to_string(), at);
+            } else {
+                panic!("Invalid value or format for expiry time!");
+            }
+        }
+    }
+
+    map
+}
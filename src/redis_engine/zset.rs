@@ -0,0 +1,397 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Bound;
+
+// Scores with a total order, so they can be used to sort the members.
+// NaN is rejected by the commands, so it never makes it into a sorted set.
+#[derive(Clone, Copy, Debug)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Upper bound for the number of levels of the skiplist, same as Redis. With a quarter of the
+// nodes making it to the next level this is plenty for any set that fits into memory.
+const MAX_LEVEL: usize = 32;
+// The header node, which holds no member and only starts the links of every level
+const HEAD: usize = 0;
+
+// A link to the next node on one level, along with how many nodes it skips over.
+// The spans add up to the rank of a node, which is what makes ZRANK O(log n).
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    forward: Option<usize>,
+    span: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    member: Vec<u8>,
+    score: Score,
+    backward: Option<usize>,
+    levels: Vec<Link>,
+}
+
+impl Node {
+    // Whether the node sorts before the given score and member
+    fn is_before(&self, score: Score, member: &[u8]) -> bool {
+        (self.score, self.member.as_slice()) < (score, member)
+    }
+}
+
+// Members ordered by score and then lexicographically, the same order Redis uses.
+// Like in Redis, a skiplist with spans gives the ordered walks and the ranks in O(log n),
+// while the map answers score lookups in O(1). The nodes live in an arena and point to each
+// other by index, the slots of the removed ones get reused.
+#[derive(Clone, Debug)]
+pub struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    level: usize,
+    seed: u64,
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        let head = Node {
+            member: vec![],
+            score: Score(f64::NEG_INFINITY),
+            backward: None,
+            levels: vec![Link { forward: None, span: 0 }; MAX_LEVEL],
+        };
+
+        Self {
+            scores: HashMap::new(),
+            nodes: vec![Some(head)],
+            free: vec![],
+            tail: None,
+            level: 1,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    // Adds the member or updates its score. Returns true if the member is new.
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        // -0.0 and 0.0 are the same score
+        let score = score + 0.0;
+
+        match self.scores.insert(member.clone(), score) {
+            Some(old) => {
+                self.unlink(Score(old), &member);
+                self.link(member, Score(score));
+                false
+            }
+            None => {
+                self.link(member, Score(score));
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => {
+                self.unlink(Score(score), member);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Members from the lowest to the highest score
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            set: self,
+            front: self.node(HEAD).levels[0].forward,
+            back: self.tail,
+            remaining: self.len(),
+        }
+    }
+
+    // 0-based position of the member in ascending order
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = Score(self.score(member)?);
+        let mut rank = 0;
+        let mut x = HEAD;
+
+        // walk down to the member, adding up the spans of the links on the way
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.node(x).levels[i].forward {
+                if !self.node(next).is_before(score, member) {
+                    break;
+                }
+                rank += self.node(x).levels[i].span;
+                x = next;
+            }
+        }
+
+        // x is the last node before the member, so the member is the next one
+        Some(rank)
+    }
+
+    // Members whose score falls between `min` and `max`, in ascending order
+    pub fn range_by_score(&self, min: Bound<f64>, max: Bound<f64>) -> Vec<(&[u8], f64)> {
+        // skip down to the last node below the range
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.node(x).levels[i].forward {
+                let below = match min {
+                    Bound::Included(min) => self.node(next).score.0 < min,
+                    Bound::Excluded(min) => self.node(next).score.0 <= min,
+                    Bound::Unbounded => false,
+                };
+                if !below {
+                    break;
+                }
+                x = next;
+            }
+        }
+
+        let mut members = vec![];
+        let mut next = self.node(x).levels[0].forward;
+        while let Some(idx) = next {
+            let node = self.node(idx);
+            let in_range = match max {
+                Bound::Included(max) => node.score.0 <= max,
+                Bound::Excluded(max) => node.score.0 < max,
+                Bound::Unbounded => true,
+            };
+            if !in_range {
+                break;
+            }
+
+            members.push((node.member.as_slice(), node.score.0));
+            next = node.levels[0].forward;
+        }
+
+        members
+    }
+
+    pub fn pop_first(&mut self) -> Option<(Vec<u8>, f64)> {
+        let first = self.node(HEAD).levels[0].forward?;
+        self.pop(first)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(Vec<u8>, f64)> {
+        let last = self.tail?;
+        self.pop(last)
+    }
+
+    fn pop(&mut self, idx: usize) -> Option<(Vec<u8>, f64)> {
+        let member = self.node(idx).member.clone();
+        let score = self.scores.remove(&member)?;
+        self.unlink(Score(score), &member);
+
+        Some((member, score))
+    }
+
+    fn node(&self, idx: usize) -> &Node {
+        self.nodes[idx].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node {
+        self.nodes[idx].as_mut().unwrap()
+    }
+
+    // Every level has a quarter of the nodes of the one below it, same as in Redis
+    fn random_level(&mut self) -> usize {
+        // xorshift64, there is no need to pull in a crate for it
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+
+        let level = 1 + (self.seed.trailing_zeros() / 2) as usize;
+        level.min(MAX_LEVEL)
+    }
+
+    // The last node before the score and member on every level, and the rank of each of them
+    fn find_predecessors(&self, score: Score, member: &[u8]) -> ([usize; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.node(x).levels[i].forward {
+                if !self.node(next).is_before(score, member) {
+                    break;
+                }
+                rank[i] += self.node(x).levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        (update, rank)
+    }
+
+    // Adds a node for a member that isn't in the skiplist yet
+    fn link(&mut self, member: Vec<u8>, score: Score) {
+        let (mut update, mut rank) = self.find_predecessors(score, &member);
+
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                // the len of the skiplist, which doesn't count the new member yet
+                self.node_mut(HEAD).levels[i].span = self.len() - 1;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            backward: if update[0] == HEAD { None } else { Some(update[0]) },
+            levels: vec![Link { forward: None, span: 0 }; level],
+        };
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = Some(node);
+                idx
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let before = self.node(update[i]).levels[i];
+            let skipped = rank[0] - rank[i];
+
+            self.node_mut(idx).levels[i] = Link {
+                forward: before.forward,
+                span: before.span - skipped,
+            };
+            self.node_mut(update[i]).levels[i] = Link {
+                forward: Some(idx),
+                span: skipped + 1,
+            };
+        }
+        // the links above the new node skip over it now
+        for (i, &before) in update.iter().enumerate().take(self.level).skip(level) {
+            self.node_mut(before).levels[i].span += 1;
+        }
+
+        match self.node(idx).levels[0].forward {
+            Some(next) => self.node_mut(next).backward = Some(idx),
+            None => self.tail = Some(idx),
+        }
+    }
+
+    // Removes the node of a member, the score has to be the one it is stored with
+    fn unlink(&mut self, score: Score, member: &[u8]) {
+        let (update, _) = self.find_predecessors(score, member);
+        let idx = match self.node(update[0]).levels[0].forward {
+            Some(idx) if self.node(idx).member == member => idx,
+            _ => return,
+        };
+
+        for (i, &before) in update.iter().enumerate().take(self.level) {
+            if self.node(before).levels[i].forward == Some(idx) {
+                let removed = self.node(idx).levels[i];
+                let link = &mut self.node_mut(before).levels[i];
+                link.span += removed.span;
+                link.span -= 1;
+                link.forward = removed.forward;
+            } else {
+                self.node_mut(before).levels[i].span -= 1;
+            }
+        }
+
+        let backward = self.node(idx).backward;
+        match self.node(idx).levels[0].forward {
+            Some(next) => self.node_mut(next).backward = backward,
+            None => self.tail = backward,
+        }
+
+        while self.level > 1 && self.node(HEAD).levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+
+        self.nodes[idx] = None;
+        self.free.push(idx);
+    }
+}
+
+// Walks the members in either direction, see SortedSet::iter
+pub struct Iter<'a> {
+    set: &'a SortedSet,
+    front: Option<usize>,
+    back: Option<usize>,
+    // keeps the two ends from walking past each other
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a [u8], f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.set.node(self.front?);
+        self.front = node.levels[0].forward;
+        self.remaining -= 1;
+
+        Some((node.member.as_slice(), node.score.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.set.node(self.back?);
+        self.back = node.backward;
+        self.remaining -= 1;
+
+        Some((node.member.as_slice(), node.score.0))
+    }
+}
@@ -0,0 +1,273 @@
+use crate::redis_engine::{self, resp, Reply};
+use resp::{Protocol, RespValue};
+use std::io::{Read, Write};
+use std::time::Instant;
+
+// Same as the default "client-query-buffer-limit" of Redis
+const MAX_QUERY_BUFFER_LENGTH: usize = 1024 * 1024 * 1024;
+
+static NEXT_CLIENT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+// Per-connection state, which is not a part of the engine
+pub struct Client {
+    pub id: u64,
+    pub protocol: Protocol,
+    pub name: Option<String>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            protocol: Protocol::default(),
+            name: None,
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn hello(client: &mut Client, args: &[Vec<u8>]) -> RespValue {
+    // https://redis.io/commands/hello/
+
+    let mut protocol = client.protocol;
+    let mut name = client.name.clone();
+    let mut idx = 0;
+
+    if let Some(version) = args.first() {
+        let version = String::from_utf8_lossy(version);
+        if let Ok(v) = version.parse::<i64>() {
+            if let Some(p) = Protocol::from_version(v) {
+                protocol = p;
+            } else {
+                return RespValue::error("NOPROTO unsupported protocol version");
+            }
+        } else {
+            return RespValue::error("[ERROR]: Protocol version is not an integer or out of range");
+        }
+        idx = 1;
+    }
+
+    while idx < args.len() {
+        let option = String::from_utf8_lossy(&args[idx]).to_lowercase();
+
+        if option == "auth" && idx + 2 < args.len() {
+            // there are no users or passwords set up, so any credentials are accepted
+            idx += 3;
+        } else if option == "setname" && idx + 1 < args.len() {
+            name = Some(String::from_utf8_lossy(&args[idx + 1]).to_string());
+            idx += 2;
+        } else {
+            return RespValue::error(&format!("[ERROR]: Syntax error in HELLO option '{option}'"));
+        }
+    }
+
+    client.protocol = protocol;
+    client.name = name;
+
+    RespValue::Map(vec![
+        (RespValue::bulk("server"), RespValue::bulk("redis")),
+        (RespValue::bulk("version"), RespValue::bulk(env!("CARGO_PKG_VERSION"))),
+        (RespValue::bulk("proto"), RespValue::Integer(protocol.version())),
+        (RespValue::bulk("id"), RespValue::Integer(client.id as i64)),
+        (RespValue::bulk("mode"), RespValue::bulk("standalone")),
+        (RespValue::bulk("role"), RespValue::bulk("master")),
+        (RespValue::bulk("modules"), RespValue::Array(vec![])),
+    ])
+}
+
+pub fn exec_redis_engine(
+    executor: &redis_engine::Executor,
+    client: &mut Client,
+    args: Vec<Vec<u8>>,
+) -> RespValue {
+    if args[0].eq_ignore_ascii_case(b"hello") {
+        return hello(client, &args[1..]);
+    }
+
+    if !executor.setup_properly {
+        return RespValue::error("[ERROR]: The engine has not been set up properly.");
+    }
+
+    Reply::from_result(executor.exec_args(args)).to_resp()
+}
+
+// A client parked by a blocking command, until one of the keys gets a list pushed to it or the deadline passes
+pub struct Blocked {
+    args: Vec<Vec<u8>>,
+    pub keys: Vec<String>,
+    pub deadline: Option<Instant>,
+}
+
+// What came out of asking a connection to run its next command
+pub enum Progress {
+    // a command ran, its reply is queued up unless it blocked the client
+    Executed,
+    // there is no complete command to run, or the client is blocked
+    Waiting,
+    // the connection has to be closed because of a protocol error
+    Failed,
+}
+
+pub struct Connection {
+    pub stream: mio::net::TcpStream,
+    pub client: Client,
+    pub blocked: Option<Blocked>,
+    read_buf: Vec<u8>,
+    // how much of the read buffer has been run already
+    read_pos: usize,
+    write_buf: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: mio::net::TcpStream) -> Self {
+        Self {
+            stream,
+            client: Client::new(),
+            blocked: None,
+            read_buf: vec![],
+            read_pos: 0,
+            write_buf: vec![],
+        }
+    }
+
+    // Reads everything the socket has to offer right now.
+    // Returns false once the client has closed the connection.
+    pub fn fill_read_buf(&mut self) -> bool {
+        let mut data = [0_u8; 4096];
+
+        loop {
+            match self.stream.read(&mut data) {
+                Ok(0) => return false,
+                Ok(size) => self.read_buf.extend_from_slice(&data[..size]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    eprintln!("[ERROR]: {e}");
+                    return false;
+                }
+            }
+        }
+    }
+
+    // Runs the next complete command in the input buffer and queues up its reply.
+    // Whatever is left is the beginning of a command we haven't fully received yet,
+    // or the commands that came in after one that blocked the client.
+    pub fn run_next_command(&mut self, executor: &redis_engine::Executor) -> Progress {
+        while self.read_pos < self.read_buf.len() && self.blocked.is_none() {
+            match resp::parse_command(&self.read_buf[self.read_pos..]) {
+                Ok(Some((args, used))) => {
+                    self.read_pos += used;
+                    if args.is_empty() {
+                        continue;
+                    }
+
+                    self.run_command(executor, args);
+                    return Progress::Executed;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    // there is no way to find where the next command starts, so give up on the client
+                    self.write_buf.extend(RespValue::error(&e).encode(self.client.protocol));
+                    self.read_buf.clear();
+                    self.read_pos = 0;
+                    return Progress::Failed;
+                }
+            }
+        }
+        self.read_buf.drain(..self.read_pos);
+        self.read_pos = 0;
+
+        if self.read_buf.len() > MAX_QUERY_BUFFER_LENGTH {
+            eprintln!("[ERROR]: Client {} exceeded the query buffer limit", self.client.id);
+            return Progress::Failed;
+        }
+
+        Progress::Waiting
+    }
+
+    fn run_command(&mut self, executor: &redis_engine::Executor, args: Vec<Vec<u8>>) {
+        let blocking = executor.blocking(&args).map(|blocking| (blocking, args.clone()));
+        let reply = exec_redis_engine(executor, &mut self.client, args);
+
+        // a blocking command with nothing to pop parks the client instead of replying
+        match blocking {
+            Some((blocking, args)) if matches!(reply, RespValue::Nil | RespValue::NilArray) => {
+                executor.block_on(&blocking.keys);
+                self.blocked = Some(Blocked {
+                    args,
+                    keys: blocking.keys,
+                    deadline: blocking.timeout.map(|timeout| Instant::now() + timeout),
+                });
+            }
+            _ => self.write_buf.extend(reply.encode(self.client.protocol)),
+        }
+    }
+
+    // Runs the command the client is blocked on again, since one of its keys got a list.
+    // Returns true if the client got served, it stays blocked otherwise.
+    pub fn retry_blocked(&mut self, executor: &redis_engine::Executor) -> bool {
+        let args = match &self.blocked {
+            Some(blocked) => blocked.args.clone(),
+            None => return false,
+        };
+
+        let reply = exec_redis_engine(executor, &mut self.client, args);
+        if matches!(reply, RespValue::Nil | RespValue::NilArray) {
+            return false;
+        }
+
+        self.unblock(executor);
+        self.write_buf.extend(reply.encode(self.client.protocol));
+
+        true
+    }
+
+    pub fn unblock(&mut self, executor: &redis_engine::Executor) -> Option<Blocked> {
+        let blocked = self.blocked.take()?;
+        executor.unblock_from(&blocked.keys);
+
+        Some(blocked)
+    }
+
+    pub fn time_out(&mut self, executor: &redis_engine::Executor) {
+        if let Some(blocked) = self.unblock(executor) {
+            // only BLMOVE replies with a nil bulk string, the rest of the blocking commands reply with a nil array
+            let reply = if blocked.args[0].eq_ignore_ascii_case(b"blmove") {
+                RespValue::Nil
+            } else {
+                RespValue::NilArray
+            };
+            self.write_buf.extend(reply.encode(self.client.protocol));
+        }
+    }
+
+    pub fn has_pending_writes(&self) -> bool {
+        !self.write_buf.is_empty()
+    }
+
+    // Writes as much of the queued replies as the socket accepts.
+    // Returns false if the connection has broken down.
+    pub fn flush(&mut self) -> bool {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf) {
+                Ok(0) => return false,
+                Ok(size) => {
+                    self.write_buf.drain(..size);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    eprintln!("[ERROR]: {e}");
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,284 @@
+// Single threaded, event loop based TCP server wrapping the engine.
+// All of the sockets are multiplexed with mio (epoll under Linux) and the
+// timers run in between the I/O events, so they fire even if nobody is talking to us.
+// Since only one command runs at a time, clients blocked on a list get served right after
+// the command that pushed to it, even in the middle of a pipeline, before anyone else can pop from it.
+
+pub mod connection;
+use connection::{Connection, Progress};
+
+use crate::redis_engine;
+use mio::net::TcpListener;
+use mio::{Events, Interest, Poll, Token};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+const LISTENER: Token = Token(0);
+// Upper bound for how long the loop waits for I/O before running the timers
+const TICK: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub struct Server {
+    poll: Poll,
+    listener: TcpListener,
+    connections: HashMap<Token, Connection>,
+    next_token: usize,
+    // blocked clients, in the order they got blocked in
+    blocked: VecDeque<Token>,
+    executor: redis_engine::Executor,
+    scheduler: job_scheduler::JobScheduler<'static>,
+}
+
+impl Server {
+    pub fn new(listener: std::net::TcpListener, executor: redis_engine::Executor) -> std::io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        let mut listener = TcpListener::from_std(listener);
+
+        let poll = Poll::new()?;
+        poll.registry()
+            .register(&mut listener, LISTENER, Interest::READABLE)?;
+
+        let mut scheduler = job_scheduler::JobScheduler::new();
+        let clone = executor.clone();
+        /*
+        https://docs.rs/job_scheduler/latest/job_scheduler/
+        sec   min   hour   day of month   month   day of week   year
+         *     *     *      *              *       *             *
+         */
+        scheduler.add(job_scheduler::Job::new(
+            "* 5 * * * *".parse().unwrap(),
+            move || {
+                clone.save();
+            },
+        ));
+
+        Ok(Self {
+            poll,
+            listener,
+            connections: HashMap::new(),
+            next_token: LISTENER.0 + 1,
+            blocked: VecDeque::new(),
+            executor,
+            scheduler,
+        })
+    }
+
+    // Runs the event loop until `shutdown` gets set, e.g. by a signal handler.
+    pub fn run(&mut self, shutdown: Arc<AtomicBool>) -> std::io::Result<()> {
+        let mut events = Events::with_capacity(1024);
+
+        while !shutdown.load(Ordering::Relaxed) {
+            // wake up in time for the closest timeout of a blocked client
+            let timeout = match self.next_deadline() {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(TICK),
+                None => TICK,
+            };
+
+            if let Err(e) = self.poll.poll(&mut events, Some(timeout)) {
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            for event in events.iter() {
+                match event.token() {
+                    LISTENER => self.accept_connections(),
+                    token => self.handle_connection_event(token, event),
+                }
+            }
+
+            self.run_timers();
+        }
+
+        Ok(())
+    }
+
+    fn accept_connections(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, address)) => {
+                    let token = Token(self.next_token);
+                    self.next_token += 1;
+
+                    if let Err(e) = self
+                        .poll
+                        .registry()
+                        .register(&mut stream, token, Interest::READABLE)
+                    {
+                        eprintln!("[ERROR]: {e}");
+                        continue;
+                    }
+
+                    println!("New connection: {address}");
+                    self.connections.insert(token, Connection::new(stream));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    eprintln!("[ERROR]: {e}");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle_connection_event(&mut self, token: Token, event: &mio::event::Event) {
+        let connection = match self.connections.get_mut(&token) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        let mut open = true;
+        if event.is_readable() {
+            open = connection.fill_read_buf();
+        }
+
+        self.process_input(VecDeque::from([(token, open)]));
+    }
+
+    // Runs the commands the clients have sent one at a time and serves the blocked clients
+    // after each of them, so nobody gets to pop what was pushed for them. The served clients
+    // carry on with whatever they sent while being blocked once the current client is done.
+    fn process_input(&mut self, mut pending: VecDeque<(Token, bool)>) {
+        let mut executed = false;
+
+        while let Some((token, mut open)) = pending.pop_front() {
+            while let Some(connection) = self.connections.get_mut(&token) {
+                match connection.run_next_command(&self.executor) {
+                    Progress::Executed => {
+                        executed = true;
+                        let served = self.serve_blocked_clients();
+                        pending.extend(served.into_iter().map(|served| (served, true)));
+                    }
+                    Progress::Waiting => break,
+                    Progress::Failed => {
+                        open = false;
+                        break;
+                    }
+                }
+            }
+
+            self.finish_processing(token, open);
+        }
+
+        if executed {
+            self.executor.save();
+        }
+    }
+
+    // Sends out whatever the connection has to say and keeps track of it getting blocked
+    fn finish_processing(&mut self, token: Token, open: bool) {
+        let connection = match self.connections.get_mut(&token) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        // try to answer right away, we only wait for the socket to become writable if it's full
+        let open = connection.flush() && open;
+
+        if !open {
+            self.close_connection(token);
+            return;
+        }
+
+        if connection.blocked.is_some() && !self.blocked.contains(&token) {
+            self.blocked.push_back(token);
+        }
+
+        let interest = if connection.has_pending_writes() {
+            Interest::READABLE | Interest::WRITABLE
+        } else {
+            Interest::READABLE
+        };
+        if let Err(e) = self
+            .poll
+            .registry()
+            .reregister(&mut connection.stream, token, interest)
+        {
+            eprintln!("[ERROR]: {e}");
+            self.close_connection(token);
+        }
+    }
+
+    // Hands the lists that showed up to the clients waiting on them, the longest waiting one first.
+    // Serving a client can push to yet another key (BLMOVE), so this goes on until no key is left.
+    // Returns the clients that got served.
+    fn serve_blocked_clients(&mut self) -> Vec<Token> {
+        let mut served = vec![];
+
+        loop {
+            let ready = self.executor.take_ready_keys();
+            if ready.is_empty() {
+                return served;
+            }
+
+            for token in self.blocked.clone() {
+                let connection = match self.connections.get_mut(&token) {
+                    Some(connection) => connection,
+                    None => continue,
+                };
+
+                let waiting = match &connection.blocked {
+                    Some(blocked) => blocked.keys.iter().any(|key| ready.contains(key)),
+                    None => false,
+                };
+                if !waiting || !connection.retry_blocked(&self.executor) {
+                    continue;
+                }
+
+                self.blocked.retain(|blocked| *blocked != token);
+                served.push(token);
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.blocked
+            .iter()
+            .filter_map(|token| self.connections.get(token)?.blocked.as_ref()?.deadline)
+            .min()
+    }
+
+    // Replies with a nil to the blocked clients whose timeout has passed
+    fn expire_blocked_clients(&mut self) {
+        let now = Instant::now();
+        let mut expired_clients = VecDeque::new();
+
+        for token in self.blocked.clone() {
+            let connection = match self.connections.get_mut(&token) {
+                Some(connection) => connection,
+                None => continue,
+            };
+
+            let expired = match &connection.blocked {
+                Some(blocked) => blocked.deadline.is_some_and(|deadline| deadline <= now),
+                None => false,
+            };
+            if !expired {
+                continue;
+            }
+
+            connection.time_out(&self.executor);
+            self.blocked.retain(|blocked| *blocked != token);
+            expired_clients.push_back((token, true));
+        }
+
+        // carry on with whatever the timed out clients sent while they were blocked
+        self.process_input(expired_clients);
+    }
+
+    fn close_connection(&mut self, token: Token) {
+        self.blocked.retain(|blocked| *blocked != token);
+        if let Some(mut connection) = self.connections.remove(&token) {
+            connection.unblock(&self.executor);
+            let _ = self.poll.registry().deregister(&mut connection.stream);
+        }
+    }
+
+    fn run_timers(&mut self) {
+        self.scheduler.tick();
+        self.executor.active_expire_cycle();
+        self.expire_blocked_clients();
+    }
+}